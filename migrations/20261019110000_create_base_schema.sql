@@ -0,0 +1,76 @@
+-- The tables ghast ran against before it managed its schema. Existing databases already have
+-- them, so this only does anything on a fresh one.
+CREATE TABLE IF NOT EXISTS match_data (
+    match INTEGER PRIMARY KEY,
+    server TEXT NOT NULL,
+    start_time BIGINT NOT NULL,
+    duration INTEGER NOT NULL,
+    winner INTEGER NOT NULL,
+    team_one_score INTEGER NOT NULL,
+    team_two_score INTEGER NOT NULL,
+    map TEXT NOT NULL,
+    is_tourney BOOLEAN NOT NULL,
+    team_one_name TEXT DEFAULT 'Unknown',
+    team_two_name TEXT DEFAULT 'Unknown',
+    team_one_color INTEGER,
+    team_two_color INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS player_match_data (
+    player BYTEA NOT NULL,
+    match INTEGER NOT NULL REFERENCES match_data (match),
+    team INTEGER NOT NULL,
+    kills INTEGER NOT NULL,
+    deaths INTEGER NOT NULL,
+    assists INTEGER NOT NULL,
+    killstreak INTEGER NOT NULL,
+    dmg_dealt DOUBLE PRECISION NOT NULL,
+    dmg_taken DOUBLE PRECISION NOT NULL,
+    pickups INTEGER NOT NULL,
+    throws INTEGER NOT NULL,
+    passes INTEGER NOT NULL,
+    catches INTEGER NOT NULL,
+    strips INTEGER NOT NULL,
+    touchdowns INTEGER NOT NULL,
+    touchdown_passes INTEGER NOT NULL,
+    passing_blocks DOUBLE PRECISION DEFAULT 0.0,
+    receive_blocks DOUBLE PRECISION DEFAULT 0.0,
+    defensive_interceptions DOUBLE PRECISION DEFAULT 0.0,
+    pass_interceptions DOUBLE PRECISION DEFAULT 0.0,
+    damage_carrier DOUBLE PRECISION DEFAULT 0.0,
+    PRIMARY KEY (player, match)
+);
+
+CREATE TABLE IF NOT EXISTS player_identities (
+    uuid BYTEA PRIMARY KEY,
+    name TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS tournament (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    date BIGINT NOT NULL,
+    winner_team_id INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS tournament_team (
+    tournament_id INTEGER NOT NULL REFERENCES tournament (id),
+    team_id INTEGER NOT NULL,
+    captain_uuid BYTEA NOT NULL,
+    PRIMARY KEY (tournament_id, team_id)
+);
+
+CREATE TABLE IF NOT EXISTS tournament_team_player (
+    tournament_id INTEGER NOT NULL REFERENCES tournament (id),
+    team_id INTEGER NOT NULL,
+    player_uuid BYTEA NOT NULL,
+    PRIMARY KEY (tournament_id, player_uuid)
+);
+
+CREATE TABLE IF NOT EXISTS tournament_match (
+    tournament_id INTEGER NOT NULL REFERENCES tournament (id),
+    match_id INTEGER NOT NULL REFERENCES match_data (match),
+    team_one_tournament_id INTEGER NOT NULL,
+    team_two_tournament_id INTEGER NOT NULL,
+    PRIMARY KEY (tournament_id, match_id)
+);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS match_event (
+    match INTEGER NOT NULL REFERENCES match_data (match),
+    sequence INTEGER NOT NULL,
+    time_ms BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    actor BYTEA,
+    target BYTEA,
+    team INTEGER,
+    x DOUBLE PRECISION,
+    y DOUBLE PRECISION,
+    z DOUBLE PRECISION,
+    PRIMARY KEY (match, sequence)
+);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS player_name_history (
+    uuid BYTEA NOT NULL,
+    name TEXT NOT NULL,
+    first_seen BIGINT NOT NULL,
+    last_seen BIGINT NOT NULL,
+    PRIMARY KEY (uuid, name)
+);
+
+-- Name search matches substrings and near misses, which a plain index on lower(name) can't serve.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX IF NOT EXISTS player_name_history_name_trigrams
+    ON player_name_history USING gin (lower(name) gin_trgm_ops);
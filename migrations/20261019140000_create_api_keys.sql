@@ -0,0 +1,24 @@
+CREATE TABLE IF NOT EXISTS api_key (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    prefix TEXT NOT NULL,
+    key_hash TEXT NOT NULL UNIQUE,
+    scopes TEXT[] NOT NULL,
+    created_at BIGINT NOT NULL,
+    last_used_at BIGINT,
+    revoked_at BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS api_key_usage (
+    id BIGSERIAL PRIMARY KEY,
+    -- NULL for requests made with GHAST_ADMIN_TOKEN, which belong to no key
+    key_id INTEGER REFERENCES api_key (id),
+    time BIGINT NOT NULL,
+    method TEXT NOT NULL,
+    uri TEXT NOT NULL,
+    scope TEXT NOT NULL,
+    allowed BOOLEAN NOT NULL,
+    request_id TEXT
+);
+
+CREATE INDEX IF NOT EXISTS api_key_usage_key_time ON api_key_usage (key_id, time DESC);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS announced_tournament (
+    tournament_id INTEGER PRIMARY KEY REFERENCES tournament (id),
+    announced_at BIGINT NOT NULL
+);
+
+-- tournaments decided before announcements were recorded are treated as already announced, so
+-- turning this on doesn't post every past tournament at once
+INSERT INTO announced_tournament (tournament_id, announced_at)
+SELECT id, (EXTRACT(EPOCH FROM now()) * 1000)::BIGINT
+FROM tournament
+WHERE winner_team_id > 0
+ON CONFLICT DO NOTHING;
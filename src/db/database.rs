@@ -1,34 +1,110 @@
+use crate::db::model::api_key::{ApiKeyRecord, ApiKeyUsage};
+use crate::db::model::archive::{
+    ArchiveRecord, ArchiveTable, ArchivedAnnouncedTournament, ArchivedMatch, ArchivedMatchEvent,
+    ArchivedPlayerIdentity, ArchivedPlayerMatch, ArchivedTournamentMatch,
+};
 use crate::db::model::match_data::{MatchData, PlayerlessMatchData};
+use crate::db::model::match_event::{EventLocation, MatchEvent, MatchEventKind};
+use crate::db::model::name_history::PlayerNameRecord;
 use crate::db::model::player_match_stats::PlayerMatchStats;
 use crate::db::model::tournament::{
     TournamentBase, TournamentMatchMapping, TournamentTeam, TournamentTeamPlayer,
     TournamentWithCounts,
 };
+use crate::util::metrics::METRICS;
 use chrono::{DateTime, Utc};
-use log::warn;
-use sqlx::postgres::PgPoolOptions;
-use sqlx::{Pool, Postgres};
+use futures::stream::{BoxStream, StreamExt};
+use log::{info, warn};
+use sqlx::migrate::{MigrateError, Migrator};
+use sqlx::postgres::{PgConnection, PgPoolOptions};
+use sqlx::{Pool, Postgres, Transaction};
 use std::collections::HashMap;
 use std::str::from_utf8;
+use std::time::Duration;
+use tokio::sync::watch;
 use uuid::Uuid;
 
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
 pub struct Database {
     connection_pool: Pool<Postgres>,
+    /// Flips to true once Postgres has been reached and migrations have run.
+    ready: watch::Sender<bool>,
 }
 
 impl Database {
-    pub async fn new(database_path: &str) -> Self {
+    const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+    const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+    /// Sets up the pool without connecting. Nothing is usable until `connect` has succeeded.
+    pub fn new(database_path: &str) -> Result<Self, sqlx::Error> {
         let pool = PgPoolOptions::new()
             .max_connections(4)
-            .connect(format!("postgres://{database_path}").as_str())
-            .await
-            .unwrap();
-        Self {
+            .connect_lazy(format!("postgres://{database_path}").as_str())?;
+        Ok(Self {
             connection_pool: pool,
+            ready: watch::Sender::new(false),
+        })
+    }
+
+    /// Runs migrations, retrying with exponential backoff until Postgres can be reached. Anything
+    /// other than a connection failure, such as a migration that fails or no longer matches what
+    /// was applied, won't fix itself and is returned.
+    pub async fn connect(&self) -> Result<(), MigrateError> {
+        let mut backoff = Self::INITIAL_BACKOFF;
+        loop {
+            match MIGRATOR.run(&self.connection_pool).await {
+                Ok(()) => {
+                    info!("Connected to database and applied migrations");
+                    self.ready.send_replace(true);
+                    return Ok(());
+                }
+                Err(MigrateError::Execute(e)) if is_connection_error(&e) => {
+                    warn!("Error connecting to database, retrying in {backoff:?}: {e:?}")
+                }
+                Err(e) => return Err(e),
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(Self::MAX_BACKOFF);
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        *self.ready.borrow()
+    }
+
+    pub async fn wait_until_ready(&self) {
+        // the sender lives as long as `self`, so this can't fail
+        let _ = self.ready.subscribe().wait_for(|ready| *ready).await;
+    }
+
+    pub async fn ping(&self) -> Result<(), sqlx::Error> {
+        sqlx::query!("SELECT 1 AS one")
+            .fetch_one(&self.connection_pool)
+            .await
+            .map(|_| ())
+    }
+
+    /// Versions of the migrations that have been applied successfully, oldest first.
+    pub async fn get_applied_migrations(&self) -> Option<Vec<i64>> {
+        let _timer = METRICS.time_query("get_applied_migrations");
+        let result = sqlx::query_scalar!(
+            "SELECT version FROM _sqlx_migrations WHERE success ORDER BY version"
+        )
+        .fetch_all(&self.connection_pool)
+        .await;
+
+        match result {
+            Ok(versions) => Some(versions),
+            Err(e) => {
+                warn!("Error retrieving applied migrations: {e:?}");
+                None
+            }
         }
     }
 
     pub async fn get_match_by_id(&self, id: u32) -> Option<PlayerlessMatchData> {
+        let _timer = METRICS.time_query("get_match_by_id");
         let result = sqlx::query!(
             r#"
      SELECT m.server, m.start_time, m.duration, m.winner, m.team_one_score, m.team_two_score,
@@ -73,7 +149,86 @@ impl Database {
         }
     }
 
+    pub async fn get_matches_by_ids(
+        &self,
+        ids: &[u32],
+    ) -> Option<HashMap<u32, PlayerlessMatchData>> {
+        let _timer = METRICS.time_query("get_matches_by_ids");
+        let ids: Vec<i32> = ids.iter().map(|&id| id as i32).collect();
+        let result = sqlx::query!(
+            r#"
+     SELECT m.match, m.server, m.start_time, m.duration, m.winner, m.team_one_score, m.team_two_score,
+       m.map, m.is_tourney, m.team_one_name, m.team_two_name, m.team_one_color, m.team_two_color
+     FROM match_data m
+     WHERE m.match = ANY($1)
+     "#,
+            &ids
+        )
+        .fetch_all(&self.connection_pool)
+        .await;
+        match result {
+            Ok(records) => Some(
+                records
+                    .into_iter()
+                    .map(|record| {
+                        (
+                            record.r#match as u32,
+                            PlayerlessMatchData {
+                                server: record.server,
+                                start_time: record.start_time as u64,
+                                duration: record.duration as u32,
+                                winner: record.winner,
+                                team_one_score: record.team_one_score as u32,
+                                team_two_score: record.team_two_score as u32,
+                                map: record.map,
+                                is_tourney: record.is_tourney,
+                                team_one_name: record
+                                    .team_one_name
+                                    .unwrap_or_else(|| String::from("Unknown")),
+                                team_two_name: record
+                                    .team_two_name
+                                    .unwrap_or_else(|| String::from("Unknown")),
+                                team_one_color: record.team_one_color.map(|n| n as u32),
+                                team_two_color: record.team_two_color.map(|n| n as u32),
+                            },
+                        )
+                    })
+                    .collect(),
+            ),
+            Err(e) => {
+                warn!("Error retrieving matches {e:?}");
+                None
+            }
+        }
+    }
+
+    /// Ids of the latest `limit` started matches, newest first, optionally only those older
+    /// than `before`.
+    pub async fn get_recent_match_ids(&self, limit: u32, before: Option<u32>) -> Option<Vec<u32>> {
+        let _timer = METRICS.time_query("get_recent_match_ids");
+        let result = sqlx::query_scalar!(
+            r#"
+     SELECT match FROM match_data
+     WHERE start_time > 0 AND ($2::int IS NULL OR match < $2)
+     ORDER BY match DESC
+     LIMIT $1
+     "#,
+            i64::from(limit),
+            before.map(|id| id as i32)
+        )
+        .fetch_all(&self.connection_pool)
+        .await;
+        match result {
+            Ok(ids) => Some(ids.into_iter().map(|id| id as u32).collect()),
+            Err(e) => {
+                warn!("Error retrieving match ids {e:?}");
+                None
+            }
+        }
+    }
+
     pub async fn get_matches_all(&self) -> Option<HashMap<u32, MatchData>> {
+        let _timer = METRICS.time_query("get_matches_all");
         let result = sqlx::query!(
                 r#"
     SELECT m.match, m.server, m.start_time, m.duration, m.winner, m.team_one_score, m.team_two_score,
@@ -134,6 +289,7 @@ impl Database {
         start_time: DateTime<Utc>,
         end_time: DateTime<Utc>,
     ) -> Option<HashMap<u32, MatchData>> {
+        let _timer = METRICS.time_query("get_matches_between");
         let start_time_millis = start_time.timestamp_millis();
         let end_time_millis = end_time.timestamp_millis();
         let result = sqlx::query!(
@@ -192,10 +348,113 @@ impl Database {
         }
     }
 
+    /// The highest id of a match with player rows. Later headers aren't finished yet.
+    pub async fn get_latest_match_id(&self) -> Option<u32> {
+        let _timer = METRICS.time_query("get_latest_match_id");
+        let result = sqlx::query_scalar!(r#"SELECT MAX(match) FROM player_match_data"#)
+            .fetch_one(&self.connection_pool)
+            .await;
+        match result {
+            Ok(id) => id.map(|id| id as u32),
+            Err(e) => {
+                warn!("Error retrieving latest match id {e:?}");
+                None
+            }
+        }
+    }
+
+    /// Matches with an id above `after_id` that already have player rows, so a match is not
+    /// picked up between its header and its stats being written.
+    pub async fn get_matches_after(&self, after_id: u32) -> Option<HashMap<u32, MatchData>> {
+        let _timer = METRICS.time_query("get_matches_after");
+        let result = sqlx::query!(
+            r#"
+     SELECT m.match, m.server, m.start_time, m.duration, m.winner, m.team_one_score, m.team_two_score,
+       m.map, m.is_tourney, m.team_one_name, m.team_two_name, m.team_one_color, m.team_two_color,
+       COALESCE(ARRAY_REMOVE(ARRAY_AGG(p.player), NULL), '{}'::bytea[]) players
+     FROM match_data m JOIN player_match_data p ON m.match = p.match
+     WHERE m.match > $1
+     GROUP BY m.match
+     "#,
+            after_id as i32
+        )
+        .fetch_all(&self.connection_pool)
+        .await;
+        let mut match_data: HashMap<u32, MatchData> = HashMap::new();
+        match result {
+            Ok(records) => {
+                if records.is_empty() {
+                    return None;
+                }
+                for record in records {
+                    let datum = MatchData {
+                        server: record.server,
+                        start_time: record.start_time as u64,
+                        duration: record.duration as u32,
+                        winner: record.winner,
+                        team_one_score: record.team_one_score as u32,
+                        team_two_score: record.team_two_score as u32,
+                        map: record.map,
+                        is_tourney: record.is_tourney,
+                        team_one_name: record
+                            .team_one_name
+                            .unwrap_or_else(|| String::from("Unknown")),
+                        team_two_name: record
+                            .team_two_name
+                            .unwrap_or_else(|| String::from("Unknown")),
+                        team_one_color: record.team_one_color.map(|n| n as u32),
+                        team_two_color: record.team_two_color.map(|n| n as u32),
+                        players: record
+                            .players
+                            .unwrap_or(Vec::new())
+                            .into_iter()
+                            .map(Self::parse_uuid)
+                            .collect(),
+                    };
+                    match_data.insert(record.r#match as u32, datum);
+                }
+                Some(match_data)
+            }
+            Err(e) => {
+                warn!("Error retrieving matches {e:?}");
+                None
+            }
+        }
+    }
+
+    /// Matches between the two ids, exclusive, whose header has been written but none of whose
+    /// player rows have.
+    pub async fn get_unfinished_match_ids(
+        &self,
+        after_id: u32,
+        before_id: u32,
+    ) -> Option<Vec<u32>> {
+        let _timer = METRICS.time_query("get_unfinished_match_ids");
+        let result = sqlx::query_scalar!(
+            r#"
+     SELECT m.match FROM match_data m
+     WHERE m.match > $1 AND m.match < $2
+       AND NOT EXISTS (SELECT 1 FROM player_match_data p WHERE p.match = m.match)
+     "#,
+            after_id as i32,
+            before_id as i32
+        )
+        .fetch_all(&self.connection_pool)
+        .await;
+        match result {
+            Ok(ids) => Some(ids.into_iter().map(|id| id as u32).collect()),
+            Err(e) => {
+                warn!("Error retrieving unfinished matches {e:?}");
+                None
+            }
+        }
+    }
+
     pub async fn get_player_match_stats(
         &self,
         match_id: u32,
     ) -> Option<HashMap<Uuid, PlayerMatchStats>> {
+        let _timer = METRICS.time_query("get_player_match_stats");
         let result = sqlx::query!(
             r#"
      SELECT player, team, kills, deaths, assists, killstreak, dmg_dealt, dmg_taken, pickups,
@@ -258,130 +517,509 @@ impl Database {
         Some(player_stats)
     }
 
-    #[allow(dead_code)]
-    pub async fn get_username_from_uuid(&self, uuid: Uuid) -> Option<String> {
-        let uuid_bytes = uuid.as_bytes().to_vec();
-
-        let name = sqlx::query_scalar!(
-            r#"SELECT name FROM player_identities WHERE uuid = $1"#,
-            uuid_bytes.as_slice()
-        )
-        .fetch_optional(&self.connection_pool)
-        .await
-        .ok()
-        .flatten();
-
-        if name.is_some() {
-            return name;
-        }
-
-        let uuid_hyphenated_string = uuid.hyphenated().to_string();
-        let uuid_bytes = uuid_hyphenated_string.as_bytes();
-        let name = sqlx::query_scalar!(
-            r#"SELECT name FROM player_identities WHERE uuid = $1"#,
-            uuid_bytes
-        )
-        .fetch_optional(&self.connection_pool)
-        .await
-        .ok()
-        .flatten();
-
-        if name.is_some() {
-            return name;
-        }
-
-        None
+    pub async fn get_match_events(&self, match_id: u32) -> Option<Vec<MatchEvent>> {
+        let mut events = self.get_match_events_for(&[match_id]).await?;
+        Some(events.remove(&match_id).unwrap_or_default())
     }
 
-    pub async fn get_usernames_from_uuids(&self, uuids: &[Uuid]) -> HashMap<Uuid, String> {
-        let uuid_bytes: Vec<Vec<u8>> = uuids.iter().map(|u| u.as_bytes().to_vec()).collect();
-        let rows = sqlx::query!(
+    /// Event logs for several matches at once, each in order. Matches without a log are absent.
+    pub async fn get_match_events_for(
+        &self,
+        match_ids: &[u32],
+    ) -> Option<HashMap<u32, Vec<MatchEvent>>> {
+        let _timer = METRICS.time_query("get_match_events_for");
+        let match_ids_i32: Vec<i32> = match_ids.iter().map(|&id| id as i32).collect();
+        let result = sqlx::query!(
             r#"
-            SELECT name, uuid
-            FROM player_identities
-            WHERE uuid = ANY($1)
+            SELECT match, sequence, time_ms, kind, actor, target, team, x, y, z
+            FROM match_event
+            WHERE match = ANY($1)
+            ORDER BY match, sequence
             "#,
-            &uuid_bytes
+            &match_ids_i32
         )
         .fetch_all(&self.connection_pool)
-        .await
-        .expect("DB query failed");
+        .await;
 
-        let mut map = HashMap::new();
-        for row in rows {
-            map.insert(Self::parse_uuid(row.uuid), row.name);
+        match result {
+            Ok(records) => {
+                let mut events: HashMap<u32, Vec<MatchEvent>> = HashMap::new();
+                for record in records {
+                    let match_id = record.r#match as u32;
+                    let Some(kind) = MatchEventKind::parse(&record.kind) else {
+                        warn!("Unknown event kind {} in match {match_id}", record.kind);
+                        continue;
+                    };
+                    let location = match (record.x, record.y, record.z) {
+                        (Some(x), Some(y), Some(z)) => Some(EventLocation { x, y, z }),
+                        _ => None,
+                    };
+                    events.entry(match_id).or_default().push(MatchEvent {
+                        sequence: record.sequence as u32,
+                        time: record.time_ms as u64,
+                        kind,
+                        actor: record.actor.map(Self::parse_uuid),
+                        target: record.target.map(Self::parse_uuid),
+                        team: record.team,
+                        location,
+                    });
+                }
+                Some(events)
+            }
+            Err(e) => {
+                warn!("Error retrieving match events for {match_ids:?}: {e:?}");
+                None
+            }
         }
-        map
     }
 
-    fn parse_uuid(v: Vec<u8>) -> Uuid {
-        let s = if v.len() == 16 {
-            Uuid::from_bytes(Self::vec_as_arr(v))
-                .hyphenated()
-                .to_string()
-        } else {
-            from_utf8(&v[..]).unwrap().to_string()
-        };
-        Uuid::parse_str(s.as_str()).unwrap()
+    /// Replaces a match's whole event log, so a game server can safely resend it.
+    pub async fn replace_match_events(
+        &self,
+        match_id: u32,
+        events: &[MatchEvent],
+    ) -> Result<(), sqlx::Error> {
+        let _timer = METRICS.time_query("replace_match_events");
+        let mut tx = self.connection_pool.begin().await?;
+        sqlx::query!(
+            r#"DELETE FROM match_event WHERE match = $1"#,
+            match_id as i32
+        )
+        .execute(&mut *tx)
+        .await?;
+        for event in events {
+            Self::insert_match_event(&mut tx, match_id, event).await?;
+        }
+        tx.commit().await
     }
 
-    fn vec_as_arr<T, const N: usize>(v: Vec<T>) -> [T; N] {
-        v.try_into().unwrap_or_else(|v: Vec<T>| {
-            panic!("Expected a Vec of length {} but it was {}", N, v.len())
-        })
+    async fn insert_match_event(
+        conn: &mut PgConnection,
+        match_id: u32,
+        event: &MatchEvent,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO match_event (match, sequence, time_ms, kind, actor, target, team, x, y, z)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+            match_id as i32,
+            event.sequence as i32,
+            event.time as i64,
+            event.kind.as_str(),
+            event.actor.map(|uuid| uuid.as_bytes().to_vec()),
+            event.target.map(|uuid| uuid.as_bytes().to_vec()),
+            event.team,
+            event.location.map(|l| l.x),
+            event.location.map(|l| l.y),
+            event.location.map(|l| l.z),
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
     }
 
-    pub async fn get_tournaments_all(&self) -> Option<Vec<TournamentWithCounts>> {
+    /// Every match line recorded for the given players, under either stored uuid form, or only
+    /// each one's `latest` most recent.
+    pub async fn get_player_match_history(
+        &self,
+        uuids: &[Uuid],
+        latest: Option<u32>,
+    ) -> Option<HashMap<Uuid, Vec<(u32, PlayerMatchStats)>>> {
+        let _timer = METRICS.time_query("get_player_match_history");
+        let uuid_forms: Vec<Vec<u8>> = uuids
+            .iter()
+            .flat_map(|uuid| {
+                [
+                    uuid.as_bytes().to_vec(),
+                    uuid.hyphenated().to_string().into_bytes(),
+                ]
+            })
+            .collect();
         let result = sqlx::query!(
             r#"
-            SELECT
-                t.id,
-                t.name,
-                t.date,
-                t.winner_team_id,
-                COUNT(DISTINCT tm.match_id) as match_count,
-                COUNT(DISTINCT ttp.player_uuid) as player_count,
-                (SELECT COALESCE(ARRAY_AGG(captain_uuid ORDER BY team_id), '{}'::bytea[])
-                 FROM tournament_team WHERE tournament_id = t.id) as captain_uuids
-            FROM tournament t
-            LEFT JOIN tournament_match tm ON tm.tournament_id = t.id
-            LEFT JOIN tournament_team_player ttp ON ttp.tournament_id = t.id
-            GROUP BY t.id
-            ORDER BY t.date DESC
-            "#
+            SELECT match, player, team, kills, deaths, assists, killstreak, dmg_dealt, dmg_taken,
+                   pickups, throws, passes, catches, strips, touchdowns, touchdown_passes,
+                   passing_blocks, receive_blocks, defensive_interceptions, pass_interceptions,
+                   damage_carrier
+            FROM player_match_data
+            WHERE player = ANY($1)
+              AND ($2::BIGINT IS NULL OR (player, match) IN (
+                SELECT player, match FROM (
+                  SELECT player, match,
+                         ROW_NUMBER() OVER (PARTITION BY player ORDER BY match DESC) AS recency
+                  FROM player_match_data
+                  WHERE player = ANY($1)
+                ) ranked
+                WHERE recency <= $2
+              ))
+            "#,
+            &uuid_forms,
+            latest.map(i64::from)
         )
         .fetch_all(&self.connection_pool)
         .await;
 
         match result {
-            Ok(records) => {
-                let tournaments: Vec<TournamentWithCounts> = records
-                    .into_iter()
-                    .map(|record| TournamentWithCounts {
-                        id: record.id as u32,
-                        name: record.name,
-                        date: record.date as u64,
-                        winner_team_id: record.winner_team_id,
-                        match_count: record.match_count.unwrap_or(0) as u32,
-                        player_count: record.player_count.unwrap_or(0) as u32,
-                        captain_uuids: record
-                            .captain_uuids
-                            .unwrap_or_default()
-                            .into_iter()
-                            .map(Self::parse_uuid)
-                            .collect(),
-                    })
-                    .collect();
-                Some(tournaments)
-            }
-            Err(e) => {
-                warn!("Error retrieving tournaments {e:?}");
-                None
-            }
+            Ok(rows) => {
+                let mut history: HashMap<Uuid, Vec<(u32, PlayerMatchStats)>> = HashMap::new();
+                for record in rows {
+                    let stats = PlayerMatchStats {
+                        team: record.team,
+                        kills: record.kills as u32,
+                        deaths: record.deaths as u32,
+                        assists: record.assists as u32,
+                        killstreak: record.killstreak as u32,
+                        damage_dealt: record.dmg_dealt,
+                        damage_taken: record.dmg_taken,
+                        pickups: record.pickups as u32,
+                        throws: record.throws as u32,
+                        passes: record.passes as u32,
+                        catches: record.catches as u32,
+                        strips: record.strips as u32,
+                        touchdowns: record.touchdowns as u32,
+                        touchdown_passes: record.touchdown_passes as u32,
+                        passing_blocks: record.passing_blocks.unwrap_or(0.0) as f32,
+                        receive_blocks: record.receive_blocks.unwrap_or(0.0) as f32,
+                        defensive_interceptions: record.defensive_interceptions.unwrap_or(0.0)
+                            as u32,
+                        pass_interceptions: record.pass_interceptions.unwrap_or(0.0) as u32,
+                        damage_carrier: record.damage_carrier.unwrap_or(0.0) as f32,
+                    };
+                    history
+                        .entry(Self::parse_uuid(record.player))
+                        .or_default()
+                        .push((record.r#match as u32, stats));
+                }
+                Some(history)
+            }
+            Err(e) => {
+                warn!("Error retrieving player match history: {e:?}");
+                None
+            }
+        }
+    }
+
+    /// How many matches each of the given players has played, with their lines summed over all
+    /// of them. Both stored uuid forms count towards the same player. `killstreak` is their best
+    /// and `team` is left at zero.
+    pub async fn get_player_career_totals(
+        &self,
+        uuids: &[Uuid],
+    ) -> Option<HashMap<Uuid, (u32, PlayerMatchStats)>> {
+        let _timer = METRICS.time_query("get_player_career_totals");
+        let uuid_forms: Vec<Vec<u8>> = uuids
+            .iter()
+            .flat_map(|uuid| {
+                [
+                    uuid.as_bytes().to_vec(),
+                    uuid.hyphenated().to_string().into_bytes(),
+                ]
+            })
+            .collect();
+        let result = sqlx::query!(
+            r#"
+            SELECT
+                CASE WHEN length(player) = 16 THEN player
+                     ELSE decode(replace(convert_from(player, 'UTF8'), '-', ''), 'hex')
+                END AS "player!",
+                COUNT(*) AS "matches!",
+                SUM(kills) AS "kills!", SUM(deaths) AS "deaths!", SUM(assists) AS "assists!",
+                MAX(killstreak) AS "killstreak!", SUM(dmg_dealt) AS "dmg_dealt!",
+                SUM(dmg_taken) AS "dmg_taken!", SUM(pickups) AS "pickups!",
+                SUM(throws) AS "throws!", SUM(passes) AS "passes!", SUM(catches) AS "catches!",
+                SUM(strips) AS "strips!", SUM(touchdowns) AS "touchdowns!",
+                SUM(touchdown_passes) AS "touchdown_passes!",
+                COALESCE(SUM(passing_blocks), 0) AS "passing_blocks!",
+                COALESCE(SUM(receive_blocks), 0) AS "receive_blocks!",
+                COALESCE(SUM(defensive_interceptions), 0) AS "defensive_interceptions!",
+                COALESCE(SUM(pass_interceptions), 0) AS "pass_interceptions!",
+                COALESCE(SUM(damage_carrier), 0) AS "damage_carrier!"
+            FROM player_match_data
+            WHERE player = ANY($1)
+            GROUP BY 1
+            "#,
+            &uuid_forms
+        )
+        .fetch_all(&self.connection_pool)
+        .await;
+
+        match result {
+            Ok(rows) => Some(
+                rows.into_iter()
+                    .map(|record| {
+                        let totals = PlayerMatchStats {
+                            team: 0,
+                            kills: record.kills as u32,
+                            deaths: record.deaths as u32,
+                            assists: record.assists as u32,
+                            killstreak: record.killstreak as u32,
+                            damage_dealt: record.dmg_dealt,
+                            damage_taken: record.dmg_taken,
+                            pickups: record.pickups as u32,
+                            throws: record.throws as u32,
+                            passes: record.passes as u32,
+                            catches: record.catches as u32,
+                            strips: record.strips as u32,
+                            touchdowns: record.touchdowns as u32,
+                            touchdown_passes: record.touchdown_passes as u32,
+                            passing_blocks: record.passing_blocks as f32,
+                            receive_blocks: record.receive_blocks as f32,
+                            defensive_interceptions: record.defensive_interceptions as u32,
+                            pass_interceptions: record.pass_interceptions as u32,
+                            damage_carrier: record.damage_carrier as f32,
+                        };
+                        (
+                            Self::parse_uuid(record.player),
+                            (record.matches as u32, totals),
+                        )
+                    })
+                    .collect(),
+            ),
+            Err(e) => {
+                warn!("Error retrieving player career totals: {e:?}");
+                None
+            }
+        }
+    }
+
+    /// Every logged touchdown, grouped by match and in the order they were scored.
+    pub async fn get_touchdown_events(&self) -> Option<HashMap<u32, Vec<MatchEvent>>> {
+        let _timer = METRICS.time_query("get_touchdown_events");
+        let result = sqlx::query!(
+            r#"
+            SELECT match, sequence, time_ms, actor, team
+            FROM match_event
+            WHERE kind = 'touchdown'
+            ORDER BY match, sequence
+            "#
+        )
+        .fetch_all(&self.connection_pool)
+        .await;
+
+        match result {
+            Ok(records) => {
+                let mut touchdowns: HashMap<u32, Vec<MatchEvent>> = HashMap::new();
+                for record in records {
+                    touchdowns
+                        .entry(record.r#match as u32)
+                        .or_default()
+                        .push(MatchEvent {
+                            sequence: record.sequence as u32,
+                            time: record.time_ms as u64,
+                            kind: MatchEventKind::Touchdown,
+                            actor: record.actor.map(Self::parse_uuid),
+                            target: None,
+                            team: record.team,
+                            location: None,
+                        });
+                }
+                Some(touchdowns)
+            }
+            Err(e) => {
+                warn!("Error retrieving touchdown events: {e:?}");
+                None
+            }
+        }
+    }
+
+    /// Matches with an event log in which the player scored at least one touchdown.
+    pub async fn get_player_touchdown_matches(&self, uuid: Uuid) -> Option<Vec<u32>> {
+        let _timer = METRICS.time_query("get_player_touchdown_matches");
+        let uuid_forms = vec![
+            uuid.as_bytes().to_vec(),
+            uuid.hyphenated().to_string().into_bytes(),
+        ];
+        let result = sqlx::query_scalar!(
+            r#"
+            SELECT DISTINCT match
+            FROM match_event
+            WHERE kind = 'touchdown' AND actor = ANY($1)
+            ORDER BY match
+            "#,
+            &uuid_forms
+        )
+        .fetch_all(&self.connection_pool)
+        .await;
+
+        match result {
+            Ok(ids) => Some(ids.into_iter().map(|id| id as u32).collect()),
+            Err(e) => {
+                warn!("Error retrieving touchdown matches for {uuid}: {e:?}");
+                None
+            }
+        }
+    }
+
+    pub async fn get_username_from_uuid(&self, uuid: Uuid) -> Option<String> {
+        let _timer = METRICS.time_query("get_username_from_uuid");
+        let uuid_bytes = uuid.as_bytes().to_vec();
+
+        let name = sqlx::query_scalar!(
+            r#"SELECT name FROM player_identities WHERE uuid = $1"#,
+            uuid_bytes.as_slice()
+        )
+        .fetch_optional(&self.connection_pool)
+        .await
+        .ok()
+        .flatten();
+
+        if name.is_some() {
+            return name;
+        }
+
+        let uuid_hyphenated_string = uuid.hyphenated().to_string();
+        let uuid_bytes = uuid_hyphenated_string.as_bytes();
+        let name = sqlx::query_scalar!(
+            r#"SELECT name FROM player_identities WHERE uuid = $1"#,
+            uuid_bytes
+        )
+        .fetch_optional(&self.connection_pool)
+        .await
+        .ok()
+        .flatten();
+
+        if name.is_some() {
+            return name;
+        }
+
+        None
+    }
+
+    /// Looks names up under both stored uuid forms, like `get_username_from_uuid`.
+    pub async fn get_usernames_from_uuids(&self, uuids: &[Uuid]) -> Option<HashMap<Uuid, String>> {
+        let _timer = METRICS.time_query("get_usernames_from_uuids");
+        let uuid_bytes: Vec<Vec<u8>> = uuids
+            .iter()
+            .flat_map(|u| {
+                [
+                    u.as_bytes().to_vec(),
+                    u.hyphenated().to_string().into_bytes(),
+                ]
+            })
+            .collect();
+        let result = sqlx::query!(
+            r#"
+            SELECT name, uuid
+            FROM player_identities
+            WHERE uuid = ANY($1)
+            "#,
+            &uuid_bytes
+        )
+        .fetch_all(&self.connection_pool)
+        .await;
+
+        match result {
+            Ok(rows) => Some(
+                rows.into_iter()
+                    .map(|row| (Self::parse_uuid(row.uuid), row.name))
+                    .collect(),
+            ),
+            Err(e) => {
+                warn!("Error retrieving usernames: {e:?}");
+                None
+            }
+        }
+    }
+
+    pub fn parse_uuid(v: Vec<u8>) -> Uuid {
+        let s = if v.len() == 16 {
+            Uuid::from_bytes(Self::vec_as_arr(v))
+                .hyphenated()
+                .to_string()
+        } else {
+            from_utf8(&v[..]).unwrap().to_string()
+        };
+        Uuid::parse_str(s.as_str()).unwrap()
+    }
+
+    fn vec_as_arr<T, const N: usize>(v: Vec<T>) -> [T; N] {
+        v.try_into().unwrap_or_else(|v: Vec<T>| {
+            panic!("Expected a Vec of length {} but it was {}", N, v.len())
+        })
+    }
+
+    pub async fn get_tournaments_all(&self) -> Option<Vec<TournamentWithCounts>> {
+        let _timer = METRICS.time_query("get_tournaments_all");
+        let result = sqlx::query!(
+            r#"
+            SELECT
+                t.id,
+                t.name,
+                t.date,
+                t.winner_team_id,
+                COUNT(DISTINCT tm.match_id) as match_count,
+                COUNT(DISTINCT ttp.player_uuid) as player_count,
+                (SELECT COALESCE(ARRAY_AGG(captain_uuid ORDER BY team_id), '{}'::bytea[])
+                 FROM tournament_team WHERE tournament_id = t.id) as captain_uuids
+            FROM tournament t
+            LEFT JOIN tournament_match tm ON tm.tournament_id = t.id
+            LEFT JOIN tournament_team_player ttp ON ttp.tournament_id = t.id
+            GROUP BY t.id
+            ORDER BY t.date DESC
+            "#
+        )
+        .fetch_all(&self.connection_pool)
+        .await;
+
+        match result {
+            Ok(records) => {
+                let tournaments: Vec<TournamentWithCounts> = records
+                    .into_iter()
+                    .map(|record| TournamentWithCounts {
+                        id: record.id as u32,
+                        name: record.name,
+                        date: record.date as u64,
+                        winner_team_id: record.winner_team_id,
+                        match_count: record.match_count.unwrap_or(0) as u32,
+                        player_count: record.player_count.unwrap_or(0) as u32,
+                        captain_uuids: record
+                            .captain_uuids
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(Self::parse_uuid)
+                            .collect(),
+                    })
+                    .collect();
+                Some(tournaments)
+            }
+            Err(e) => {
+                warn!("Error retrieving tournaments {e:?}");
+                None
+            }
+        }
+    }
+
+    /// A hash of every row of the tournament tables, which changes whenever any of them does.
+    pub async fn get_tournament_fingerprint(&self) -> Option<String> {
+        let _timer = METRICS.time_query("get_tournament_fingerprint");
+        let result = sqlx::query_scalar!(
+            r#"
+            SELECT md5(
+                COALESCE((SELECT string_agg(t::TEXT, ',' ORDER BY t::TEXT) FROM tournament t), '')
+                || '|' || COALESCE((SELECT string_agg(t::TEXT, ',' ORDER BY t::TEXT)
+                                    FROM tournament_team t), '')
+                || '|' || COALESCE((SELECT string_agg(t::TEXT, ',' ORDER BY t::TEXT)
+                                    FROM tournament_team_player t), '')
+                || '|' || COALESCE((SELECT string_agg(t::TEXT, ',' ORDER BY t::TEXT)
+                                    FROM tournament_match t), '')
+            ) AS "fingerprint!"
+            "#
+        )
+        .fetch_one(&self.connection_pool)
+        .await;
+
+        match result {
+            Ok(fingerprint) => Some(fingerprint),
+            Err(e) => {
+                warn!("Error fingerprinting tournaments: {e:?}");
+                None
+            }
         }
     }
 
     pub async fn get_tournament_by_id(&self, id: u32) -> Option<TournamentBase> {
+        let _timer = METRICS.time_query("get_tournament_by_id");
         let result = sqlx::query!(
             r#"
             SELECT id, name, date, winner_team_id
@@ -408,15 +1046,101 @@ impl Database {
         }
     }
 
-    pub async fn get_tournament_teams(&self, tournament_id: u32) -> Option<Vec<TournamentTeam>> {
+    /// Sets a tournament's winner, or clears it with 0. False if there's no such tournament or
+    /// the team isn't one of its own.
+    pub async fn set_tournament_winner(&self, id: u32, winner_team_id: i32) -> Option<bool> {
+        let _timer = METRICS.time_query("set_tournament_winner");
+        let result = sqlx::query!(
+            r#"
+            UPDATE tournament SET winner_team_id = $2
+            WHERE id = $1
+              AND ($2 = 0 OR EXISTS (
+                SELECT 1 FROM tournament_team WHERE tournament_id = $1 AND team_id = $2
+              ))
+            "#,
+            id as i32,
+            winner_team_id
+        )
+        .execute(&self.connection_pool)
+        .await;
+
+        match result {
+            Ok(result) => Some(result.rows_affected() > 0),
+            Err(e) => {
+                warn!("Error setting the winner of tournament {id}: {e:?}");
+                None
+            }
+        }
+    }
+
+    /// Tournaments that have a winner but whose awards haven't gone out yet.
+    pub async fn get_unannounced_tournaments(&self) -> Option<Vec<TournamentBase>> {
+        let _timer = METRICS.time_query("get_unannounced_tournaments");
+        let result = sqlx::query!(
+            r#"
+            SELECT id, name, date, winner_team_id
+            FROM tournament t
+            WHERE winner_team_id > 0
+              AND NOT EXISTS (SELECT 1 FROM announced_tournament a WHERE a.tournament_id = t.id)
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&self.connection_pool)
+        .await;
+
+        match result {
+            Ok(records) => Some(
+                records
+                    .into_iter()
+                    .map(|record| TournamentBase {
+                        id: record.id as u32,
+                        name: record.name,
+                        date: record.date as u64,
+                        winner_team_id: record.winner_team_id,
+                    })
+                    .collect(),
+            ),
+            Err(e) => {
+                warn!("Error retrieving unannounced tournaments: {e:?}");
+                None
+            }
+        }
+    }
+
+    pub async fn mark_tournament_announced(
+        &self,
+        id: u32,
+        announced_at: u64,
+    ) -> Result<(), sqlx::Error> {
+        let _timer = METRICS.time_query("mark_tournament_announced");
+        sqlx::query!(
+            r#"
+            INSERT INTO announced_tournament (tournament_id, announced_at)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+            id as i32,
+            announced_at as i64
+        )
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_tournament_teams(
+        &self,
+        tournament_ids: &[u32],
+    ) -> Option<Vec<TournamentTeam>> {
+        let _timer = METRICS.time_query("get_tournament_teams");
+        let tournament_ids: Vec<i32> = tournament_ids.iter().map(|&id| id as i32).collect();
         let result = sqlx::query!(
             r#"
             SELECT tournament_id, team_id, captain_uuid
             FROM tournament_team
-            WHERE tournament_id = $1
-            ORDER BY team_id
+            WHERE tournament_id = ANY($1)
+            ORDER BY tournament_id, team_id
             "#,
-            tournament_id as i32
+            &tournament_ids
         )
         .fetch_all(&self.connection_pool)
         .await;
@@ -442,16 +1166,18 @@ impl Database {
 
     pub async fn get_tournament_team_players(
         &self,
-        tournament_id: u32,
+        tournament_ids: &[u32],
     ) -> Option<Vec<TournamentTeamPlayer>> {
+        let _timer = METRICS.time_query("get_tournament_team_players");
+        let tournament_ids: Vec<i32> = tournament_ids.iter().map(|&id| id as i32).collect();
         let result = sqlx::query!(
             r#"
             SELECT tournament_id, team_id, player_uuid
             FROM tournament_team_player
-            WHERE tournament_id = $1
-            ORDER BY team_id
+            WHERE tournament_id = ANY($1)
+            ORDER BY tournament_id, team_id
             "#,
-            tournament_id as i32
+            &tournament_ids
         )
         .fetch_all(&self.connection_pool)
         .await;
@@ -477,8 +1203,10 @@ impl Database {
 
     pub async fn get_tournament_matches(
         &self,
-        tournament_id: u32,
+        tournament_ids: &[u32],
     ) -> Option<Vec<TournamentMatchMapping>> {
+        let _timer = METRICS.time_query("get_tournament_matches");
+        let tournament_ids: Vec<i32> = tournament_ids.iter().map(|&id| id as i32).collect();
         let result = sqlx::query!(
             r#"
             SELECT
@@ -493,10 +1221,10 @@ impl Database {
                 m.team_two_score
             FROM tournament_match tm
             JOIN match_data m ON m.match = tm.match_id
-            WHERE tm.tournament_id = $1
-            ORDER BY m.start_time
+            WHERE tm.tournament_id = ANY($1)
+            ORDER BY tm.tournament_id, m.start_time
             "#,
-            tournament_id as i32
+            &tournament_ids
         )
         .fetch_all(&self.connection_pool)
         .await;
@@ -530,6 +1258,7 @@ impl Database {
         &self,
         match_ids: &[u32],
     ) -> Option<HashMap<u32, HashMap<Uuid, PlayerMatchStats>>> {
+        let _timer = METRICS.time_query("get_player_stats_for_matches");
         let match_ids_i32: Vec<i32> = match_ids.iter().map(|&id| id as i32).collect();
         let result = sqlx::query!(
             r#"
@@ -587,4 +1316,934 @@ impl Database {
             }
         }
     }
+
+    /// Connections currently open in the pool, and how many of those are idle.
+    pub fn pool_status(&self) -> (u32, usize) {
+        (self.connection_pool.size(), self.connection_pool.num_idle())
+    }
+
+    /// Matches, distinct players and tournaments on record, in that order.
+    pub async fn get_domain_counts(&self) -> Option<(i64, i64, i64)> {
+        let _timer = METRICS.time_query("get_domain_counts");
+        let result = sqlx::query!(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM match_data) as "matches!",
+                (SELECT COUNT(DISTINCT player) FROM player_match_data) as "players!",
+                (SELECT COUNT(*) FROM tournament) as "tournaments!"
+            "#
+        )
+        .fetch_one(&self.connection_pool)
+        .await;
+
+        match result {
+            Ok(record) => Some((record.matches, record.players, record.tournaments)),
+            Err(e) => {
+                warn!("Error counting matches and players: {e:?}");
+                None
+            }
+        }
+    }
+
+    pub async fn get_archive_table_counts(conn: &mut PgConnection) -> Option<Vec<ArchiveTable>> {
+        let _timer = METRICS.time_query("get_archive_table_counts");
+        let result = sqlx::query!(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM match_data) as "match_data!",
+                (SELECT COUNT(*) FROM player_match_data) as "player_match_data!",
+                (SELECT COUNT(*) FROM match_event) as "match_event!",
+                (SELECT COUNT(*) FROM player_identities) as "player_identities!",
+                (SELECT COUNT(*) FROM player_name_history) as "player_name_history!",
+                (SELECT COUNT(*) FROM tournament) as "tournament!",
+                (SELECT COUNT(*) FROM tournament_team) as "tournament_team!",
+                (SELECT COUNT(*) FROM tournament_team_player) as "tournament_team_player!",
+                (SELECT COUNT(*) FROM tournament_match) as "tournament_match!",
+                (SELECT COUNT(*) FROM announced_tournament) as "announced_tournament!"
+            "#
+        )
+        .fetch_one(&mut *conn)
+        .await;
+
+        match result {
+            Ok(record) => Some(
+                [
+                    ("match_data", record.match_data),
+                    ("player_match_data", record.player_match_data),
+                    ("match_event", record.match_event),
+                    ("player_identities", record.player_identities),
+                    ("player_name_history", record.player_name_history),
+                    ("tournament", record.tournament),
+                    ("tournament_team", record.tournament_team),
+                    ("tournament_team_player", record.tournament_team_player),
+                    ("tournament_match", record.tournament_match),
+                    ("announced_tournament", record.announced_tournament),
+                ]
+                .into_iter()
+                .map(|(name, rows)| ArchiveTable {
+                    name: String::from(name),
+                    rows: rows as u64,
+                })
+                .collect(),
+            ),
+            Err(e) => {
+                warn!("Error counting archive tables: {e:?}");
+                None
+            }
+        }
+    }
+
+    pub fn get_archived_matches(
+        conn: &mut PgConnection,
+    ) -> BoxStream<'_, Result<ArchivedMatch, sqlx::Error>> {
+        let timer = METRICS.time_query("get_archived_matches");
+        sqlx::query!(
+            r#"
+            SELECT match, server, start_time, duration, winner, team_one_score, team_two_score,
+                   map, is_tourney, team_one_name, team_two_name, team_one_color, team_two_color
+            FROM match_data
+            ORDER BY match
+            "#
+        )
+        .fetch(conn)
+        .map(move |record| {
+            // held until the whole table has been read
+            let _timer = &timer;
+            record.map(|record| ArchivedMatch {
+                id: record.r#match as u32,
+                data: PlayerlessMatchData {
+                    server: record.server,
+                    start_time: record.start_time as u64,
+                    duration: record.duration as u32,
+                    winner: record.winner,
+                    team_one_score: record.team_one_score as u32,
+                    team_two_score: record.team_two_score as u32,
+                    map: record.map,
+                    is_tourney: record.is_tourney,
+                    team_one_name: record
+                        .team_one_name
+                        .unwrap_or_else(|| String::from("Unknown")),
+                    team_two_name: record
+                        .team_two_name
+                        .unwrap_or_else(|| String::from("Unknown")),
+                    team_one_color: record.team_one_color.map(|n| n as u32),
+                    team_two_color: record.team_two_color.map(|n| n as u32),
+                },
+            })
+        })
+        .boxed()
+    }
+
+    pub fn get_archived_player_matches(
+        conn: &mut PgConnection,
+    ) -> BoxStream<'_, Result<ArchivedPlayerMatch, sqlx::Error>> {
+        let timer = METRICS.time_query("get_archived_player_matches");
+        sqlx::query!(
+            r#"
+            SELECT match, player, team, kills, deaths, assists, killstreak, dmg_dealt, dmg_taken,
+                   pickups, throws, passes, catches, strips, touchdowns, touchdown_passes,
+                   passing_blocks, receive_blocks, defensive_interceptions, pass_interceptions,
+                   damage_carrier
+            FROM player_match_data
+            ORDER BY match, player
+            "#
+        )
+        .fetch(conn)
+        .map(move |record| {
+            let _timer = &timer;
+            record.map(|record| ArchivedPlayerMatch {
+                match_id: record.r#match as u32,
+                player: Self::parse_uuid(record.player),
+                stats: PlayerMatchStats {
+                    team: record.team,
+                    kills: record.kills as u32,
+                    deaths: record.deaths as u32,
+                    assists: record.assists as u32,
+                    killstreak: record.killstreak as u32,
+                    damage_dealt: record.dmg_dealt,
+                    damage_taken: record.dmg_taken,
+                    pickups: record.pickups as u32,
+                    throws: record.throws as u32,
+                    passes: record.passes as u32,
+                    catches: record.catches as u32,
+                    strips: record.strips as u32,
+                    touchdowns: record.touchdowns as u32,
+                    touchdown_passes: record.touchdown_passes as u32,
+                    passing_blocks: record.passing_blocks.unwrap_or(0.0) as f32,
+                    receive_blocks: record.receive_blocks.unwrap_or(0.0) as f32,
+                    defensive_interceptions: record.defensive_interceptions.unwrap_or(0.0) as u32,
+                    pass_interceptions: record.pass_interceptions.unwrap_or(0.0) as u32,
+                    damage_carrier: record.damage_carrier.unwrap_or(0.0) as f32,
+                },
+            })
+        })
+        .boxed()
+    }
+
+    pub fn get_archived_match_events(
+        conn: &mut PgConnection,
+    ) -> BoxStream<'_, Result<ArchivedMatchEvent, sqlx::Error>> {
+        let timer = METRICS.time_query("get_archived_match_events");
+        sqlx::query!(
+            r#"
+            SELECT match, sequence, time_ms, kind, actor, target, team, x, y, z
+            FROM match_event
+            ORDER BY match, sequence
+            "#
+        )
+        .fetch(conn)
+        .map(move |record| {
+            let _timer = &timer;
+            let record = record?;
+            // an event the archive can't describe would be lost on restore, so it stops the export
+            let kind = MatchEventKind::parse(&record.kind).ok_or_else(|| {
+                sqlx::Error::Decode(
+                    format!(
+                        "unknown event kind {} in match {}",
+                        record.kind, record.r#match
+                    )
+                    .into(),
+                )
+            })?;
+            let location = match (record.x, record.y, record.z) {
+                (Some(x), Some(y), Some(z)) => Some(EventLocation { x, y, z }),
+                _ => None,
+            };
+            Ok(ArchivedMatchEvent {
+                match_id: record.r#match as u32,
+                event: MatchEvent {
+                    sequence: record.sequence as u32,
+                    time: record.time_ms as u64,
+                    kind,
+                    actor: record.actor.map(Self::parse_uuid),
+                    target: record.target.map(Self::parse_uuid),
+                    team: record.team,
+                    location,
+                },
+            })
+        })
+        .boxed()
+    }
+
+    pub fn get_archived_player_identities(
+        conn: &mut PgConnection,
+    ) -> BoxStream<'_, Result<ArchivedPlayerIdentity, sqlx::Error>> {
+        let timer = METRICS.time_query("get_archived_player_identities");
+        sqlx::query!(
+            r#"
+            SELECT uuid, name
+            FROM player_identities
+            ORDER BY uuid
+            "#
+        )
+        .fetch(conn)
+        .map(move |record| {
+            let _timer = &timer;
+            record.map(|record| ArchivedPlayerIdentity {
+                uuid: Self::parse_uuid(record.uuid),
+                name: record.name,
+            })
+        })
+        .boxed()
+    }
+
+    pub fn get_archived_name_history(
+        conn: &mut PgConnection,
+    ) -> BoxStream<'_, Result<PlayerNameRecord, sqlx::Error>> {
+        let timer = METRICS.time_query("get_archived_name_history");
+        sqlx::query!(
+            r#"
+            SELECT uuid, name, first_seen, last_seen
+            FROM player_name_history
+            ORDER BY uuid, name
+            "#
+        )
+        .fetch(conn)
+        .map(move |record| {
+            let _timer = &timer;
+            record.map(|record| PlayerNameRecord {
+                uuid: Self::parse_uuid(record.uuid),
+                name: record.name,
+                first_seen: record.first_seen as u64,
+                last_seen: record.last_seen as u64,
+            })
+        })
+        .boxed()
+    }
+
+    pub fn get_archived_tournaments(
+        conn: &mut PgConnection,
+    ) -> BoxStream<'_, Result<TournamentBase, sqlx::Error>> {
+        let timer = METRICS.time_query("get_archived_tournaments");
+        sqlx::query!(
+            r#"
+            SELECT id, name, date, winner_team_id
+            FROM tournament
+            ORDER BY id
+            "#
+        )
+        .fetch(conn)
+        .map(move |record| {
+            let _timer = &timer;
+            record.map(|record| TournamentBase {
+                id: record.id as u32,
+                name: record.name,
+                date: record.date as u64,
+                winner_team_id: record.winner_team_id,
+            })
+        })
+        .boxed()
+    }
+
+    pub fn get_archived_tournament_teams(
+        conn: &mut PgConnection,
+    ) -> BoxStream<'_, Result<TournamentTeam, sqlx::Error>> {
+        let timer = METRICS.time_query("get_archived_tournament_teams");
+        sqlx::query!(
+            r#"
+            SELECT tournament_id, team_id, captain_uuid
+            FROM tournament_team
+            ORDER BY tournament_id, team_id
+            "#
+        )
+        .fetch(conn)
+        .map(move |record| {
+            let _timer = &timer;
+            record.map(|record| TournamentTeam {
+                tournament_id: record.tournament_id as u32,
+                team_id: record.team_id,
+                captain_uuid: Self::parse_uuid(record.captain_uuid),
+            })
+        })
+        .boxed()
+    }
+
+    pub fn get_archived_tournament_team_players(
+        conn: &mut PgConnection,
+    ) -> BoxStream<'_, Result<TournamentTeamPlayer, sqlx::Error>> {
+        let timer = METRICS.time_query("get_archived_tournament_team_players");
+        sqlx::query!(
+            r#"
+            SELECT tournament_id, team_id, player_uuid
+            FROM tournament_team_player
+            ORDER BY tournament_id, team_id
+            "#
+        )
+        .fetch(conn)
+        .map(move |record| {
+            let _timer = &timer;
+            record.map(|record| TournamentTeamPlayer {
+                tournament_id: record.tournament_id as u32,
+                team_id: record.team_id,
+                player_uuid: Self::parse_uuid(record.player_uuid),
+            })
+        })
+        .boxed()
+    }
+
+    pub fn get_archived_tournament_matches(
+        conn: &mut PgConnection,
+    ) -> BoxStream<'_, Result<ArchivedTournamentMatch, sqlx::Error>> {
+        let timer = METRICS.time_query("get_archived_tournament_matches");
+        sqlx::query!(
+            r#"
+            SELECT tournament_id, match_id, team_one_tournament_id, team_two_tournament_id
+            FROM tournament_match
+            ORDER BY tournament_id, match_id
+            "#
+        )
+        .fetch(conn)
+        .map(move |record| {
+            let _timer = &timer;
+            record.map(|record| ArchivedTournamentMatch {
+                tournament_id: record.tournament_id as u32,
+                match_id: record.match_id as u32,
+                team_one_tournament_id: record.team_one_tournament_id,
+                team_two_tournament_id: record.team_two_tournament_id,
+            })
+        })
+        .boxed()
+    }
+
+    pub fn get_archived_announced_tournaments(
+        conn: &mut PgConnection,
+    ) -> BoxStream<'_, Result<ArchivedAnnouncedTournament, sqlx::Error>> {
+        let timer = METRICS.time_query("get_archived_announced_tournaments");
+        sqlx::query!(
+            r#"
+            SELECT tournament_id, announced_at
+            FROM announced_tournament
+            ORDER BY tournament_id
+            "#
+        )
+        .fetch(conn)
+        .map(move |record| {
+            let _timer = &timer;
+            record.map(|record| ArchivedAnnouncedTournament {
+                tournament_id: record.tournament_id as u32,
+                announced_at: record.announced_at as u64,
+            })
+        })
+        .boxed()
+    }
+
+    /// A read-only transaction that sees the database as of its first query, so that reads made
+    /// through it agree with each other whatever gets written in the meantime.
+    pub async fn begin_snapshot(&self) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        let _timer = METRICS.time_query("begin_snapshot");
+        let mut tx = self.connection_pool.begin().await?;
+        sqlx::query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+            .execute(&mut *tx)
+            .await?;
+        Ok(tx)
+    }
+
+    /// Inserts every record in a single transaction, so a failed restore leaves nothing behind.
+    pub async fn restore_archive(&self, records: &[ArchiveRecord]) -> Result<(), sqlx::Error> {
+        let _timer = METRICS.time_query("restore_archive");
+        let mut tx = self.connection_pool.begin().await?;
+        for record in records {
+            Self::insert_archive_record(&mut tx, record).await?;
+        }
+        tx.commit().await
+    }
+
+    pub async fn begin(&self) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+        let _timer = METRICS.time_query("begin");
+        self.connection_pool.begin().await
+    }
+
+    pub async fn insert_archive_record(
+        conn: &mut PgConnection,
+        record: &ArchiveRecord,
+    ) -> Result<(), sqlx::Error> {
+        let _timer = METRICS.time_query("insert_archive_record");
+        match record {
+            ArchiveRecord::MatchData(m) => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO match_data (match, server, start_time, duration, winner,
+                        team_one_score, team_two_score, map, is_tourney, team_one_name,
+                        team_two_name, team_one_color, team_two_color)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+                    "#,
+                    m.id as i32,
+                    m.data.server,
+                    m.data.start_time as i64,
+                    m.data.duration as i32,
+                    m.data.winner,
+                    m.data.team_one_score as i32,
+                    m.data.team_two_score as i32,
+                    m.data.map,
+                    m.data.is_tourney,
+                    m.data.team_one_name,
+                    m.data.team_two_name,
+                    m.data.team_one_color.map(|n| n as i32),
+                    m.data.team_two_color.map(|n| n as i32),
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+            ArchiveRecord::PlayerMatchData(p) => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO player_match_data (player, match, team, kills, deaths,
+                        assists, killstreak, dmg_dealt, dmg_taken, pickups, throws, passes,
+                        catches, strips, touchdowns, touchdown_passes, passing_blocks,
+                        receive_blocks, defensive_interceptions, pass_interceptions,
+                        damage_carrier)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15,
+                        $16, $17, $18, $19, $20, $21)
+                    "#,
+                    p.player.as_bytes().as_slice(),
+                    p.match_id as i32,
+                    p.stats.team,
+                    p.stats.kills as i32,
+                    p.stats.deaths as i32,
+                    p.stats.assists as i32,
+                    p.stats.killstreak as i32,
+                    p.stats.damage_dealt,
+                    p.stats.damage_taken,
+                    p.stats.pickups as i32,
+                    p.stats.throws as i32,
+                    p.stats.passes as i32,
+                    p.stats.catches as i32,
+                    p.stats.strips as i32,
+                    p.stats.touchdowns as i32,
+                    p.stats.touchdown_passes as i32,
+                    f64::from(p.stats.passing_blocks),
+                    f64::from(p.stats.receive_blocks),
+                    f64::from(p.stats.defensive_interceptions),
+                    f64::from(p.stats.pass_interceptions),
+                    f64::from(p.stats.damage_carrier),
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+            ArchiveRecord::PlayerIdentities(i) => {
+                sqlx::query!(
+                    r#"INSERT INTO player_identities (uuid, name) VALUES ($1, $2)"#,
+                    i.uuid.as_bytes().as_slice(),
+                    i.name,
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+            ArchiveRecord::PlayerNameHistory(n) => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO player_name_history (uuid, name, first_seen, last_seen)
+                    VALUES ($1, $2, $3, $4)
+                    "#,
+                    n.uuid.as_bytes().as_slice(),
+                    n.name,
+                    n.first_seen as i64,
+                    n.last_seen as i64,
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+            ArchiveRecord::Tournament(t) => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO tournament (id, name, date, winner_team_id)
+                    VALUES ($1, $2, $3, $4)
+                    "#,
+                    t.id as i32,
+                    t.name,
+                    t.date as i64,
+                    t.winner_team_id,
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+            ArchiveRecord::MatchEvent(e) => {
+                Self::insert_match_event(conn, e.match_id, &e.event).await?;
+            }
+            ArchiveRecord::TournamentTeam(t) => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO tournament_team (tournament_id, team_id, captain_uuid)
+                    VALUES ($1, $2, $3)
+                    "#,
+                    t.tournament_id as i32,
+                    t.team_id,
+                    t.captain_uuid.as_bytes().as_slice(),
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+            ArchiveRecord::TournamentTeamPlayer(p) => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO tournament_team_player (tournament_id, team_id, player_uuid)
+                    VALUES ($1, $2, $3)
+                    "#,
+                    p.tournament_id as i32,
+                    p.team_id,
+                    p.player_uuid.as_bytes().as_slice(),
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+            ArchiveRecord::TournamentMatch(m) => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO tournament_match (tournament_id, match_id,
+                        team_one_tournament_id, team_two_tournament_id)
+                    VALUES ($1, $2, $3, $4)
+                    "#,
+                    m.tournament_id as i32,
+                    m.match_id as i32,
+                    m.team_one_tournament_id,
+                    m.team_two_tournament_id,
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+            ArchiveRecord::AnnouncedTournament(a) => {
+                sqlx::query!(
+                    r#"
+                    INSERT INTO announced_tournament (tournament_id, announced_at)
+                    VALUES ($1, $2)
+                    "#,
+                    a.tournament_id as i32,
+                    a.announced_at as i64,
+                )
+                .execute(&mut *conn)
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Records a name learned outside of the game servers, such as from the Mojang API.
+    pub async fn store_player_identity(&self, uuid: Uuid, name: &str) -> Result<(), sqlx::Error> {
+        let _timer = METRICS.time_query("store_player_identity");
+        let mut conn = self.connection_pool.acquire().await?;
+        Self::upsert_player_identity(&mut conn, uuid, name).await
+    }
+
+    /// Renames a player under either stored uuid form, inserting a binary row if neither exists.
+    async fn upsert_player_identity(
+        conn: &mut PgConnection,
+        uuid: Uuid,
+        name: &str,
+    ) -> Result<(), sqlx::Error> {
+        let _timer = METRICS.time_query("upsert_player_identity");
+        let uuid_forms = vec![
+            uuid.as_bytes().to_vec(),
+            uuid.hyphenated().to_string().into_bytes(),
+        ];
+        let updated = sqlx::query!(
+            r#"UPDATE player_identities SET name = $2 WHERE uuid = ANY($1)"#,
+            &uuid_forms,
+            name,
+        )
+        .execute(&mut *conn)
+        .await?;
+        if updated.rows_affected() == 0 {
+            sqlx::query!(
+                r#"INSERT INTO player_identities (uuid, name) VALUES ($1, $2)"#,
+                uuid.as_bytes().as_slice(),
+                name,
+            )
+            .execute(&mut *conn)
+            .await?;
+        }
+        Self::record_player_name(conn, uuid, name, Utc::now().timestamp_millis()).await
+    }
+
+    /// Notes that a player went by `name` at `seen_at`, widening that name's first/last seen range.
+    pub async fn record_player_name(
+        conn: &mut PgConnection,
+        uuid: Uuid,
+        name: &str,
+        seen_at: i64,
+    ) -> Result<(), sqlx::Error> {
+        let _timer = METRICS.time_query("record_player_name");
+        sqlx::query!(
+            r#"
+            INSERT INTO player_name_history (uuid, name, first_seen, last_seen)
+            VALUES ($1, $2, $3, $3)
+            ON CONFLICT (uuid, name) DO UPDATE SET
+                first_seen = LEAST(player_name_history.first_seen, EXCLUDED.first_seen),
+                last_seen = GREATEST(player_name_history.last_seen, EXCLUDED.last_seen)
+            "#,
+            uuid.as_bytes().as_slice(),
+            name,
+            seen_at,
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Copies every current name into the history, so names written straight into
+    /// `player_identities` by the game servers are tracked too.
+    pub async fn sync_name_history(&self) -> Result<u64, sqlx::Error> {
+        let _timer = METRICS.time_query("sync_name_history");
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO player_name_history (uuid, name, first_seen, last_seen)
+            SELECT DISTINCT
+                CASE WHEN length(uuid) = 16 THEN uuid
+                     ELSE decode(replace(convert_from(uuid, 'UTF8'), '-', ''), 'hex')
+                END,
+                name, $1::BIGINT, $1::BIGINT
+            FROM player_identities
+            ON CONFLICT (uuid, name) DO UPDATE SET
+                last_seen = GREATEST(player_name_history.last_seen, EXCLUDED.last_seen)
+            "#,
+            Utc::now().timestamp_millis(),
+        )
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// `sync_name_history` for just these players, for after a match.
+    pub async fn sync_player_names(&self, uuids: &[Uuid]) -> Result<u64, sqlx::Error> {
+        let _timer = METRICS.time_query("sync_player_names");
+        let uuid_forms: Vec<Vec<u8>> = uuids
+            .iter()
+            .flat_map(|uuid| {
+                [
+                    uuid.as_bytes().to_vec(),
+                    uuid.hyphenated().to_string().into_bytes(),
+                ]
+            })
+            .collect();
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO player_name_history (uuid, name, first_seen, last_seen)
+            SELECT DISTINCT
+                CASE WHEN length(uuid) = 16 THEN uuid
+                     ELSE decode(replace(convert_from(uuid, 'UTF8'), '-', ''), 'hex')
+                END,
+                name, $1::BIGINT, $1::BIGINT
+            FROM player_identities
+            WHERE uuid = ANY($2)
+            ON CONFLICT (uuid, name) DO UPDATE SET
+                last_seen = GREATEST(player_name_history.last_seen, EXCLUDED.last_seen)
+            "#,
+            Utc::now().timestamp_millis(),
+            &uuid_forms
+        )
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Names containing `query`, or close to some word of it by trigram similarity, best first:
+    /// an exact match, then a prefix, then a substring, then by similarity. At most `limit` are
+    /// returned. `query` is expected lowercase.
+    pub async fn search_name_history(
+        &self,
+        query: &str,
+        limit: usize,
+    ) -> Option<Vec<PlayerNameRecord>> {
+        let _timer = METRICS.time_query("search_name_history");
+        let escaped = query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        let result = sqlx::query!(
+            r#"
+            SELECT uuid, name, first_seen, last_seen
+            FROM player_name_history
+            WHERE lower(name) LIKE $1 OR $3 <% lower(name)
+            ORDER BY lower(name) = $3 DESC,
+                     lower(name) LIKE $2 DESC,
+                     lower(name) LIKE $1 DESC,
+                     word_similarity($3, lower(name)) DESC,
+                     last_seen DESC
+            LIMIT $4
+            "#,
+            format!("%{escaped}%"),
+            format!("{escaped}%"),
+            query,
+            limit as i64
+        )
+        .fetch_all(&self.connection_pool)
+        .await;
+
+        match result {
+            Ok(records) => Some(
+                records
+                    .into_iter()
+                    .map(|record| PlayerNameRecord {
+                        uuid: Self::parse_uuid(record.uuid),
+                        name: record.name,
+                        first_seen: record.first_seen as u64,
+                        last_seen: record.last_seen as u64,
+                    })
+                    .collect(),
+            ),
+            Err(e) => {
+                warn!("Error searching name history: {e:?}");
+                None
+            }
+        }
+    }
+
+    pub async fn insert_api_key(
+        &self,
+        name: &str,
+        prefix: &str,
+        key_hash: &str,
+        scopes: &[String],
+        created_at: u64,
+    ) -> Option<ApiKeyRecord> {
+        let _timer = METRICS.time_query("insert_api_key");
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO api_key (name, prefix, key_hash, scopes, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id
+            "#,
+            name,
+            prefix,
+            key_hash,
+            scopes,
+            created_at as i64
+        )
+        .fetch_one(&self.connection_pool)
+        .await;
+
+        match result {
+            Ok(record) => Some(ApiKeyRecord {
+                id: record.id,
+                name: String::from(name),
+                prefix: String::from(prefix),
+                scopes: scopes.to_vec(),
+                created_at,
+                last_used_at: None,
+                revoked_at: None,
+            }),
+            Err(e) => {
+                warn!("Error inserting api key: {e:?}");
+                None
+            }
+        }
+    }
+
+    pub async fn get_api_keys(&self) -> Option<Vec<ApiKeyRecord>> {
+        let _timer = METRICS.time_query("get_api_keys");
+        let result = sqlx::query!(
+            r#"
+            SELECT id, name, prefix, scopes, created_at, last_used_at, revoked_at
+            FROM api_key
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&self.connection_pool)
+        .await;
+
+        match result {
+            Ok(records) => Some(
+                records
+                    .into_iter()
+                    .map(|record| ApiKeyRecord {
+                        id: record.id,
+                        name: record.name,
+                        prefix: record.prefix,
+                        scopes: record.scopes,
+                        created_at: record.created_at as u64,
+                        last_used_at: record.last_used_at.map(|t| t as u64),
+                        revoked_at: record.revoked_at.map(|t| t as u64),
+                    })
+                    .collect(),
+            ),
+            Err(e) => {
+                warn!("Error retrieving api keys: {e:?}");
+                None
+            }
+        }
+    }
+
+    /// The unrevoked key with this hash, if there is one.
+    pub async fn get_active_api_key(
+        &self,
+        key_hash: &str,
+    ) -> Result<Option<ApiKeyRecord>, sqlx::Error> {
+        let _timer = METRICS.time_query("get_active_api_key");
+        let record = sqlx::query!(
+            r#"
+            SELECT id, name, prefix, scopes, created_at, last_used_at
+            FROM api_key
+            WHERE key_hash = $1 AND revoked_at IS NULL
+            "#,
+            key_hash
+        )
+        .fetch_optional(&self.connection_pool)
+        .await?;
+
+        Ok(record.map(|record| ApiKeyRecord {
+            id: record.id,
+            name: record.name,
+            prefix: record.prefix,
+            scopes: record.scopes,
+            created_at: record.created_at as u64,
+            last_used_at: record.last_used_at.map(|t| t as u64),
+            revoked_at: None,
+        }))
+    }
+
+    /// Returns whether a key was revoked, which it isn't if it doesn't exist or already was.
+    pub async fn revoke_api_key(&self, id: i32, revoked_at: u64) -> Option<bool> {
+        let _timer = METRICS.time_query("revoke_api_key");
+        let result = sqlx::query!(
+            "UPDATE api_key SET revoked_at = $2 WHERE id = $1 AND revoked_at IS NULL",
+            id,
+            revoked_at as i64
+        )
+        .execute(&self.connection_pool)
+        .await;
+
+        match result {
+            Ok(result) => Some(result.rows_affected() > 0),
+            Err(e) => {
+                warn!("Error revoking api key {id}: {e:?}");
+                None
+            }
+        }
+    }
+
+    /// Appends to the audit log and bumps the key's `last_used_at`.
+    pub async fn record_api_key_usage(&self, usage: &ApiKeyUsage) -> Result<(), sqlx::Error> {
+        let _timer = METRICS.time_query("record_api_key_usage");
+        sqlx::query!(
+            r#"
+            WITH usage AS (
+                INSERT INTO api_key_usage (key_id, time, method, uri, scope, allowed, request_id)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+            )
+            UPDATE api_key SET last_used_at = $2 WHERE id = $1
+            "#,
+            usage.key_id,
+            usage.time as i64,
+            usage.method,
+            usage.uri,
+            usage.scope,
+            usage.allowed,
+            usage.request_id
+        )
+        .execute(&self.connection_pool)
+        .await?;
+        Ok(())
+    }
+
+    /// The most recent usage, newest first, optionally for a single key.
+    pub async fn get_api_key_usage(
+        &self,
+        key_id: Option<i32>,
+        limit: u32,
+    ) -> Option<Vec<ApiKeyUsage>> {
+        let _timer = METRICS.time_query("get_api_key_usage");
+        let result = sqlx::query!(
+            r#"
+            SELECT key_id, time, method, uri, scope, allowed, request_id
+            FROM api_key_usage
+            WHERE $1::INTEGER IS NULL OR key_id = $1
+            ORDER BY time DESC, id DESC
+            LIMIT $2
+            "#,
+            key_id,
+            i64::from(limit)
+        )
+        .fetch_all(&self.connection_pool)
+        .await;
+
+        match result {
+            Ok(records) => Some(
+                records
+                    .into_iter()
+                    .map(|record| ApiKeyUsage {
+                        key_id: record.key_id,
+                        time: record.time as u64,
+                        method: record.method,
+                        uri: record.uri,
+                        scope: record.scope,
+                        allowed: record.allowed,
+                        request_id: record.request_id,
+                    })
+                    .collect(),
+            ),
+            Err(e) => {
+                warn!("Error retrieving api key usage: {e:?}");
+                None
+            }
+        }
+    }
+}
+
+/// Whether an error means Postgres couldn't be reached (yet), rather than that it refused us.
+fn is_connection_error(e: &sqlx::Error) -> bool {
+    match e {
+        sqlx::Error::Io(_) | sqlx::Error::Tls(_) | sqlx::Error::PoolTimedOut => true,
+        // cannot_connect_now: the server is still starting up or shutting down
+        sqlx::Error::Database(e) => e.code().as_deref() == Some("57P03"),
+        _ => false,
+    }
 }
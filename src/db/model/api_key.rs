@@ -0,0 +1,27 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// An API key as stored, minus its hash. Times are epoch milliseconds.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ApiKeyRecord {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub created_at: u64,
+    pub last_used_at: Option<u64>,
+    pub revoked_at: Option<u64>,
+}
+
+/// One authenticated request made with a key, including ones refused for lacking the scope.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ApiKeyUsage {
+    /// `None` for requests made with `GHAST_ADMIN_TOKEN`.
+    pub key_id: Option<i32>,
+    pub time: u64,
+    pub method: String,
+    pub uri: String,
+    pub scope: String,
+    pub allowed: bool,
+    pub request_id: Option<String>,
+}
@@ -0,0 +1,101 @@
+use crate::db::model::match_data::PlayerlessMatchData;
+use crate::db::model::match_event::MatchEvent;
+use crate::db::model::name_history::PlayerNameRecord;
+use crate::db::model::player_match_stats::PlayerMatchStats;
+use crate::db::model::tournament::{TournamentBase, TournamentTeam, TournamentTeamPlayer};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub const ARCHIVE_FORMAT: &str = "ghast-archive";
+pub const ARCHIVE_VERSION: u32 = 2;
+
+/// First line of every archive, describing what follows it.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ArchiveManifest {
+    pub format: String,
+    pub version: u32,
+    pub created_at: i64,
+    pub tables: Vec<ArchiveTable>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ArchiveTable {
+    pub name: String,
+    pub rows: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ArchivedMatch {
+    pub id: u32,
+    #[serde(flatten)]
+    pub data: PlayerlessMatchData,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ArchivedPlayerMatch {
+    pub match_id: u32,
+    pub player: Uuid,
+    #[serde(flatten)]
+    pub stats: PlayerMatchStats,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ArchivedMatchEvent {
+    pub match_id: u32,
+    #[serde(flatten)]
+    pub event: MatchEvent,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ArchivedPlayerIdentity {
+    pub uuid: Uuid,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ArchivedTournamentMatch {
+    pub tournament_id: u32,
+    pub match_id: u32,
+    pub team_one_tournament_id: i32,
+    pub team_two_tournament_id: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ArchivedAnnouncedTournament {
+    pub tournament_id: u32,
+    pub announced_at: u64,
+}
+
+/// Every line after the manifest, tagged with the table it belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "table", content = "row", rename_all = "snake_case")]
+pub enum ArchiveRecord {
+    MatchData(ArchivedMatch),
+    PlayerMatchData(ArchivedPlayerMatch),
+    MatchEvent(ArchivedMatchEvent),
+    PlayerIdentities(ArchivedPlayerIdentity),
+    PlayerNameHistory(PlayerNameRecord),
+    Tournament(TournamentBase),
+    TournamentTeam(TournamentTeam),
+    TournamentTeamPlayer(TournamentTeamPlayer),
+    TournamentMatch(ArchivedTournamentMatch),
+    AnnouncedTournament(ArchivedAnnouncedTournament),
+}
+
+impl ArchiveRecord {
+    pub const fn table(&self) -> &'static str {
+        match self {
+            Self::MatchData(_) => "match_data",
+            Self::PlayerMatchData(_) => "player_match_data",
+            Self::MatchEvent(_) => "match_event",
+            Self::PlayerIdentities(_) => "player_identities",
+            Self::PlayerNameHistory(_) => "player_name_history",
+            Self::Tournament(_) => "tournament",
+            Self::TournamentTeam(_) => "tournament_team",
+            Self::TournamentTeamPlayer(_) => "tournament_team_player",
+            Self::TournamentMatch(_) => "tournament_match",
+            Self::AnnouncedTournament(_) => "announced_tournament",
+        }
+    }
+}
@@ -1,7 +1,9 @@
+use async_graphql::SimpleObject;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct MatchData {
     pub server: String,
     pub start_time: u64,
@@ -18,7 +20,8 @@ pub struct MatchData {
     pub players: Vec<Uuid>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema, SimpleObject)]
+#[graphql(name = "MatchData")]
 pub struct PlayerlessMatchData {
     pub server: String,
     pub start_time: u64,
@@ -0,0 +1,66 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchEventKind {
+    Pickup,
+    Throw,
+    Pass,
+    Catch,
+    Strip,
+    Interception,
+    Touchdown,
+    Kill,
+}
+
+impl MatchEventKind {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Pickup => "pickup",
+            Self::Throw => "throw",
+            Self::Pass => "pass",
+            Self::Catch => "catch",
+            Self::Strip => "strip",
+            Self::Interception => "interception",
+            Self::Touchdown => "touchdown",
+            Self::Kill => "kill",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "pickup" => Some(Self::Pickup),
+            "throw" => Some(Self::Throw),
+            "pass" => Some(Self::Pass),
+            "catch" => Some(Self::Catch),
+            "strip" => Some(Self::Strip),
+            "interception" => Some(Self::Interception),
+            "touchdown" => Some(Self::Touchdown),
+            "kill" => Some(Self::Kill),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub struct EventLocation {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+/// A single play, timed in milliseconds from the start of the match. `actor` is who did it
+/// and `target` who it was done to: the receiver of a pass, the passer on a catch, the carrier
+/// on a strip, the thrower on an interception and the victim of a kill.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct MatchEvent {
+    pub sequence: u32,
+    pub time: u64,
+    pub kind: MatchEventKind,
+    pub actor: Option<Uuid>,
+    pub target: Option<Uuid>,
+    pub team: Option<i32>,
+    pub location: Option<EventLocation>,
+}
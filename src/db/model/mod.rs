@@ -1,3 +1,7 @@
+pub mod api_key;
+pub mod archive;
 pub mod match_data;
+pub mod match_event;
+pub mod name_history;
 pub mod player_match_stats;
 pub mod tournament;
@@ -0,0 +1,12 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// One name a player has gone by, with when it was first and last seen in epoch milliseconds.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PlayerNameRecord {
+    pub uuid: Uuid,
+    pub name: String,
+    pub first_seen: u64,
+    pub last_seen: u64,
+}
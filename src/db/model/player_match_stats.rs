@@ -1,7 +1,10 @@
+use async_graphql::SimpleObject;
 use rocket::serde::Serialize;
+use schemars::JsonSchema;
 use serde::Deserialize;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema, SimpleObject)]
+#[graphql(name = "MatchStatLine")]
 pub struct PlayerMatchStats {
     pub team: i32,
     pub kills: u32,
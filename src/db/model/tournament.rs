@@ -1,7 +1,8 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TournamentBase {
     pub id: u32,
     pub name: String,
@@ -9,7 +10,7 @@ pub struct TournamentBase {
     pub winner_team_id: i32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TournamentWithCounts {
     pub id: u32,
     pub name: String,
@@ -20,21 +21,21 @@ pub struct TournamentWithCounts {
     pub captain_uuids: Vec<Uuid>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TournamentTeam {
     pub tournament_id: u32,
     pub team_id: i32,
     pub captain_uuid: Uuid,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TournamentTeamPlayer {
     pub tournament_id: u32,
     pub team_id: i32,
     pub player_uuid: Uuid,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct TournamentMatchMapping {
     pub tournament_id: u32,
     pub match_id: u32,
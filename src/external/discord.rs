@@ -0,0 +1,164 @@
+use crate::util::metrics::METRICS;
+use log::warn;
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use std::time::Duration;
+
+/// Posts messages to a single Discord webhook.
+pub struct DiscordWebhook {
+    client: Client,
+    url: String,
+}
+
+impl DiscordWebhook {
+    const MAX_ATTEMPTS: u32 = 5;
+    const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+    /// Longest we'll honour a `Retry-After` for, so one bad response can't stall notifications.
+    const MAX_RETRY_AFTER: Duration = Duration::from_mins(1);
+    /// Discord rejects messages with more embeds than this.
+    pub const MAX_EMBEDS: usize = 10;
+
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .unwrap(),
+            url: String::from(url),
+        }
+    }
+
+    /// Sends a message, retrying with exponential backoff when rate limited or when Discord is
+    /// having trouble, and waiting as long as a 429 asks to. Other rejections are not retried.
+    pub async fn post(&self, message: &WebhookMessage) -> bool {
+        let mut backoff = Self::INITIAL_BACKOFF;
+        for attempt in 1..=Self::MAX_ATTEMPTS {
+            let response = self.client.post(&self.url).json(message).send().await;
+            METRICS
+                .discord_webhook_requests
+                .with_label_values(&[match &response {
+                    Ok(response) if response.status().is_success() => "ok",
+                    Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                        "rate_limited"
+                    }
+                    Ok(response) if response.status().is_server_error() => "server_error",
+                    Ok(_) => "rejected",
+                    Err(_) => "error",
+                }])
+                .inc();
+            let mut wait = backoff;
+            match response {
+                Ok(response) if response.status().is_success() => return true,
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    if let Some(retry_after) = response
+                        .headers()
+                        .get("Retry-After")
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| value.parse::<f64>().ok())
+                    {
+                        wait = Duration::from_secs_f64(retry_after.max(0.0))
+                            .min(Self::MAX_RETRY_AFTER);
+                    }
+                    warn!("Discord webhook rate limited, retrying in {wait:?}");
+                }
+                Ok(response) if response.status().is_server_error() => {
+                    warn!("Discord webhook unavailable: {}", response.status());
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let body = response.text().await.unwrap_or_default();
+                    warn!("Discord webhook rejected a message with {status}: {body}");
+                    return false;
+                }
+                // The webhook's token is in its url, so that stays out of the log.
+                Err(e) => warn!("Error calling Discord webhook: {:?}", e.without_url()),
+            }
+            if attempt < Self::MAX_ATTEMPTS {
+                tokio::time::sleep(wait).await;
+                backoff *= 2;
+            }
+        }
+        warn!(
+            "Gave up on a Discord message after {} attempts",
+            Self::MAX_ATTEMPTS
+        );
+        false
+    }
+}
+
+#[derive(Serialize)]
+pub struct WebhookMessage {
+    pub embeds: Vec<Embed>,
+}
+
+#[derive(Serialize, Default)]
+pub struct Embed {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<EmbedField>,
+    /// RFC 3339, shown in the embed's footer in the reader's timezone.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct EmbedField {
+    pub name: String,
+    pub value: String,
+    pub inline: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external::test_server::{TestServer, response};
+
+    fn message() -> WebhookMessage {
+        WebhookMessage {
+            embeds: vec![Embed {
+                title: String::from("Match 1 on Test"),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn waits_as_long_as_a_429_asks() {
+        let server = TestServer::start(vec![
+            response("429 Too Many Requests", &[("Retry-After", "0.5")], ""),
+            response("204 No Content", &[], ""),
+        ]);
+        let webhook = DiscordWebhook::new(&server.url);
+        assert!(webhook.post(&message()).await);
+        let arrivals = server.arrivals();
+        let waited = arrivals[1] - arrivals[0];
+        assert!(waited >= Duration::from_millis(500));
+        assert!(waited < DiscordWebhook::INITIAL_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn backs_off_on_server_errors() {
+        let server = TestServer::start(vec![
+            response("500 Internal Server Error", &[], ""),
+            response("502 Bad Gateway", &[], ""),
+            response("204 No Content", &[], ""),
+        ]);
+        let webhook = DiscordWebhook::new(&server.url);
+        assert!(webhook.post(&message()).await);
+        let arrivals = server.arrivals();
+        assert!(arrivals[1] - arrivals[0] >= DiscordWebhook::INITIAL_BACKOFF);
+        assert!(arrivals[2] - arrivals[1] >= DiscordWebhook::INITIAL_BACKOFF * 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_rejected_messages() {
+        let server = TestServer::start(vec![response("400 Bad Request", &[], "")]);
+        let webhook = DiscordWebhook::new(&server.url);
+        assert!(!webhook.post(&message()).await);
+        assert_eq!(server.arrivals().len(), 1);
+    }
+}
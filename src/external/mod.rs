@@ -1 +1,4 @@
+pub mod discord;
 pub mod mojang;
+#[cfg(test)]
+pub mod test_server;
@@ -1,38 +1,100 @@
+use crate::util::metrics::METRICS;
 use log::{info, warn};
 use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::Semaphore;
 use uuid::Uuid;
 
 pub struct MojangApi {
     client: Client,
+    base_url: String,
+    permits: Semaphore,
 }
 
 impl MojangApi {
-    const API_BASE: &'static str = "https://api.minecraftservices.com";
+    pub const API_BASE: &'static str = "https://api.minecraftservices.com";
     const UUID_TO_USERNAME: &'static str = "/minecraft/profile/lookup";
+    const MAX_ATTEMPTS: u32 = 3;
+    const INITIAL_BACKOFF: Duration = Duration::from_millis(250);
+    /// Longest we'll honour a `Retry-After` for, since someone is usually waiting on the name.
+    const MAX_RETRY_AFTER: Duration = Duration::from_secs(10);
 
-    pub fn new() -> Self {
+    /// `max_concurrent` caps how many lookups are in flight at once, across all callers.
+    pub fn new(base_url: &str, max_concurrent: usize) -> Self {
         Self {
-            client: Client::builder().build().unwrap(),
+            client: Client::builder()
+                .timeout(Duration::from_secs(5))
+                .build()
+                .unwrap(),
+            base_url: String::from(base_url.trim_end_matches('/')),
+            permits: Semaphore::new(max_concurrent.max(1)),
         }
     }
 
+    /// Looks a profile up, retrying with exponential backoff when rate limited or when Mojang
+    /// is having trouble, and waiting as long as a 429 asks to. Unknown profiles are not
+    /// retried. The concurrency permit is only held while a request is in flight, so one
+    /// lookup backing off doesn't hold up the rest.
     pub async fn get_username_from_uuid(&self, uuid: Uuid) -> Option<String> {
-        info!("Calling mojang API on {uuid}");
-        let url = format!("{}{}/{}", Self::API_BASE, Self::UUID_TO_USERNAME, uuid);
-        let body = self.client.get(url).send().await;
-        if body.is_err() {
-            return None;
-        }
-        let response = body.unwrap();
-        info!("Finished {uuid}");
-        if response.status() == StatusCode::OK {
-            let response = response.json::<UsernameResolveResponse>().await.unwrap();
-            Some(response.name)
-        } else {
-            warn!("Bad response from Mojang API {response:?}");
-            None
+        let url = format!("{}{}/{}", self.base_url, Self::UUID_TO_USERNAME, uuid);
+        let mut backoff = Self::INITIAL_BACKOFF;
+        for attempt in 1..=Self::MAX_ATTEMPTS {
+            info!("Calling mojang API on {uuid} (attempt {attempt})");
+            let permit = self.permits.acquire().await.ok()?;
+            let response = self.client.get(&url).send().await;
+            drop(permit);
+            METRICS
+                .mojang_requests
+                .with_label_values(&[match &response {
+                    Ok(response) if response.status() == StatusCode::OK => "ok",
+                    Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                        "rate_limited"
+                    }
+                    Ok(response) if response.status().is_server_error() => "server_error",
+                    Ok(_) => "not_found",
+                    Err(_) => "error",
+                }])
+                .inc();
+            let mut wait = backoff;
+            match response {
+                Ok(response) if response.status() == StatusCode::OK => {
+                    return match response.json::<UsernameResolveResponse>().await {
+                        Ok(profile) => Some(profile.name),
+                        Err(e) => {
+                            warn!("Unreadable profile from Mojang API for {uuid}: {e:?}");
+                            None
+                        }
+                    };
+                }
+                Ok(response)
+                    if response.status() != StatusCode::TOO_MANY_REQUESTS
+                        && !response.status().is_server_error() =>
+                {
+                    warn!("Bad response from Mojang API {response:?}");
+                    return None;
+                }
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    if let Some(retry_after) = response
+                        .headers()
+                        .get("Retry-After")
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| value.parse::<f64>().ok())
+                    {
+                        wait = Duration::from_secs_f64(retry_after.max(0.0))
+                            .min(Self::MAX_RETRY_AFTER);
+                    }
+                    warn!("Mojang API rate limited {uuid}, retrying in {wait:?}");
+                }
+                Ok(response) => warn!("Mojang API unavailable for {uuid}: {}", response.status()),
+                Err(e) => warn!("Error calling Mojang API for {uuid}: {e:?}"),
+            }
+            if attempt < Self::MAX_ATTEMPTS {
+                tokio::time::sleep(wait).await;
+                backoff *= 2;
+            }
         }
+        None
     }
 }
 
@@ -41,3 +103,72 @@ pub struct UsernameResolveResponse {
     pub name: String,
     pub id: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external::test_server::{TestServer, response};
+
+    fn profile(name: &str) -> String {
+        response(
+            "200 OK",
+            &[("Content-Type", "application/json")],
+            &format!(r#"{{"name":"{name}","id":"00000000000000000000000000000001"}}"#),
+        )
+    }
+
+    #[tokio::test]
+    async fn waits_as_long_as_a_429_asks() {
+        let server = TestServer::start(vec![
+            response("429 Too Many Requests", &[("Retry-After", "1")], ""),
+            profile("Steve"),
+        ]);
+        let api = MojangApi::new(&server.url, 1);
+        let name = api.get_username_from_uuid(Uuid::from_u128(1)).await;
+        assert_eq!(name.as_deref(), Some("Steve"));
+        let arrivals = server.arrivals();
+        assert!(arrivals[1] - arrivals[0] >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn backs_off_on_server_errors() {
+        let server = TestServer::start(vec![
+            response("503 Service Unavailable", &[], ""),
+            response("502 Bad Gateway", &[], ""),
+            profile("Alex"),
+        ]);
+        let api = MojangApi::new(&server.url, 1);
+        let name = api.get_username_from_uuid(Uuid::from_u128(1)).await;
+        assert_eq!(name.as_deref(), Some("Alex"));
+        let arrivals = server.arrivals();
+        assert!(arrivals[1] - arrivals[0] >= MojangApi::INITIAL_BACKOFF);
+        assert!(arrivals[2] - arrivals[1] >= MojangApi::INITIAL_BACKOFF * 2);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_unknown_profiles() {
+        let server = TestServer::start(vec![response("404 Not Found", &[], "")]);
+        let api = MojangApi::new(&server.url, 1);
+        assert_eq!(api.get_username_from_uuid(Uuid::from_u128(1)).await, None);
+        assert_eq!(server.arrivals().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn other_lookups_go_ahead_while_one_backs_off() {
+        // the second connection can only be the other lookup if the first let go of the only
+        // permit while it waited
+        let server = TestServer::start(vec![
+            response("429 Too Many Requests", &[("Retry-After", "1")], ""),
+            profile("Second"),
+            profile("Third"),
+        ]);
+        let api = MojangApi::new(&server.url, 1);
+        let (backing_off, other) =
+            tokio::join!(api.get_username_from_uuid(Uuid::from_u128(1)), async {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                api.get_username_from_uuid(Uuid::from_u128(2)).await
+            },);
+        assert_eq!(other.as_deref(), Some("Second"));
+        assert_eq!(backing_off.as_deref(), Some("Third"));
+    }
+}
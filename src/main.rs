@@ -1,35 +1,180 @@
-#![feature(duration_constructors)]
-
 mod db;
 mod external;
 mod util;
 mod web;
 
 use crate::db::database::Database;
+use crate::external::discord::DiscordWebhook;
+use crate::external::mojang::MojangApi;
+use crate::util::archive::import_archive;
+use crate::util::distributions::Distributions;
+use crate::util::legacy_import::{ImportCounts, import_legacy_sqlite};
+use crate::util::logging;
+use crate::util::match_watcher::MatchWatcher;
+use crate::util::metrics::spawn_domain_gauges;
+use crate::util::name_history::spawn_name_history_sync;
+use crate::util::notifications::spawn_discord_notifications;
+use crate::util::tournament_watcher::TournamentWatcher;
 use crate::util::username_resolver::UsernameResolver;
+use crate::util::win_probability::WinProbability;
 use crate::web::api::{GhastApiState, rocket};
-use log::warn;
+use crate::web::graphql;
+use crate::web::live::LiveMatches;
+use crate::web::rate_limit::{RateLimit, RateLimiter};
+use crate::web::response_cache::ResponseCache;
+use log::{error, warn};
+use std::process::ExitCode;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
 
 #[tokio::main]
-async fn main() {
-    let db = Arc::new(
-        Database::new(
-            std::env::var("GHAST_DATABASE_PATH")
-                .unwrap_or_else(|_| String::from("postgres:secret@127.0.0.1:5432/stats"))
-                .as_str(),
-        )
-        .await,
+async fn main() -> ExitCode {
+    logging::init();
+    let db = match Database::new(
+        std::env::var("GHAST_DATABASE_PATH")
+            .unwrap_or_else(|_| String::from("postgres:secret@127.0.0.1:5432/stats"))
+            .as_str(),
+    ) {
+        Ok(db) => Arc::new(db),
+        Err(e) => {
+            eprintln!("Invalid database path: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["import-archive", path] => {
+            if let Err(e) = db.connect().await {
+                eprintln!("Error migrating database: {e}");
+                return ExitCode::FAILURE;
+            }
+            return match import_archive(&db, path).await {
+                Ok(tables) => {
+                    for table in tables {
+                        println!("{}: {} rows", table.name, table.rows);
+                    }
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("{e}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        ["import-sqlite", path] => {
+            if let Err(e) = db.connect().await {
+                eprintln!("Error migrating database: {e}");
+                return ExitCode::FAILURE;
+            }
+            return match import_legacy_sqlite(&db, path).await {
+                Ok(report) => {
+                    let print_counts = |table: &str, counts: &ImportCounts| {
+                        println!(
+                            "{table}: {} inserted, {} skipped, {} conflicted",
+                            counts.inserted, counts.skipped, counts.conflicted
+                        );
+                    };
+                    print_counts("match_data", &report.matches);
+                    print_counts("player_match_data", &report.player_matches);
+                    print_counts("player_identities", &report.player_identities);
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    eprintln!("{e}");
+                    ExitCode::FAILURE
+                }
+            };
+        }
+        [] => {
+            // the API comes up straight away and reports unready until this finishes
+            let db = db.clone();
+            tokio::spawn(async move {
+                if let Err(e) = db.connect().await {
+                    // retrying won't help and the API would sit unready forever
+                    error!("Error migrating database: {e}");
+                    std::process::exit(1);
+                }
+            });
+        }
+        _ => {
+            eprintln!("Usage: ghast [import-archive <path> | import-sqlite <path>]");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    let mojang_api = std::env::var("GHAST_MOJANG_LOOKUP")
+        .is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .then(|| {
+            MojangApi::new(
+                &std::env::var("GHAST_MOJANG_API_BASE")
+                    .unwrap_or_else(|_| String::from(MojangApi::API_BASE)),
+                4,
+            )
+        });
+    let username_resolver = Arc::new(UsernameResolver::create(db.clone(), mojang_api));
+    let graphql = graphql::schema(db.clone(), username_resolver.clone());
+    let match_watcher = MatchWatcher::spawn(db.clone(), Duration::from_secs(5));
+    let live_matches = LiveMatches::spawn(
+        Duration::from_secs(60),
+        db.clone(),
+        username_resolver.clone(),
+        match_watcher.subscribe(),
     );
-    let username_resolver = UsernameResolver::create(db.clone());
+    spawn_name_history_sync(db.clone(), match_watcher.subscribe());
+    spawn_domain_gauges(db.clone(), Duration::from_mins(5));
+    if let Ok(url) = std::env::var("GHAST_DISCORD_WEBHOOK_URL") {
+        spawn_discord_notifications(
+            DiscordWebhook::new(&url),
+            db.clone(),
+            username_resolver.clone(),
+            match_watcher.subscribe(),
+            Duration::from_mins(1),
+        );
+    }
+    let response_cache = ResponseCache::new(Duration::from_secs(60))
+        .with_ttl("/tournaments", Duration::from_secs(600));
+    response_cache.invalidate_on(match_watcher.subscribe());
+    response_cache
+        .invalidate_on(TournamentWatcher::spawn(db.clone(), Duration::from_secs(30)).subscribe());
+    // full table scans get the tightest limits, probes none at all
+    let rate_limiter = RateLimiter::new(RateLimit::new(120, 60), RateLimit::new(1200, 200))
+        .with_group("/matches/all", RateLimit::new(12, 6))
+        .with_group("/tournaments", RateLimit::new(60, 20))
+        .with_group("/players/compare", RateLimit::new(30, 10))
+        .with_group("/graphql", RateLimit::new(60, 20))
+        .without_limit("/health")
+        .without_limit("/ready")
+        .with_allowlist(
+            std::env::var("GHAST_RATE_LIMIT_ALLOWLIST")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|ip| !ip.is_empty())
+                .filter_map(|ip| {
+                    ip.parse()
+                        .inspect_err(|e| warn!("Ignoring allowlisted address {ip}: {e}"))
+                        .ok()
+                }),
+        );
     let end_result = rocket(GhastApiState {
         database: db.clone(),
-        username_resolver: Arc::new(Mutex::new(username_resolver)),
+        username_resolver,
+        admin_token: std::env::var("GHAST_ADMIN_TOKEN").ok(),
+        match_watcher,
+        live_matches,
+        win_probability: WinProbability::spawn(db.clone(), Duration::from_secs(300)),
+        distributions: Distributions::spawn(db.clone(), Duration::from_secs(300)),
+        response_cache,
+        rate_limiter,
+        graphql,
     })
     .launch()
     .await;
     if let Err(e) = end_result {
         warn!("{e}");
+        return ExitCode::FAILURE;
     }
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,66 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+const KEY_PREFIX: &str = "ghast_";
+/// How much of a key is kept in the clear, so people can tell their keys apart.
+const DISPLAY_PREFIX_LENGTH: usize = 14;
+
+/// What a key may do. `Admin` covers every other scope as well as managing keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scope {
+    Ingest,
+    TournamentAdmin,
+    ReadPrivate,
+    Admin,
+}
+
+impl Scope {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Ingest => "ingest",
+            Self::TournamentAdmin => "tournament-admin",
+            Self::ReadPrivate => "read-private",
+            Self::Admin => "admin",
+        }
+    }
+
+    /// Whether a key holding `scopes`, as stored, may act with this scope.
+    pub fn granted_by(self, scopes: &[String]) -> bool {
+        scopes
+            .iter()
+            .any(|scope| scope == self.as_str() || scope == Self::Admin.as_str())
+    }
+}
+
+/// A new random key. Only its hash is ever stored.
+pub fn generate_key() -> String {
+    format!(
+        "{KEY_PREFIX}{}{}",
+        Uuid::new_v4().simple(),
+        Uuid::new_v4().simple()
+    )
+}
+
+pub fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Whether `token` is `expected`, in time that doesn't depend on where they differ. Both are
+/// hashed first so their lengths don't leak either.
+pub fn token_matches(expected: &str, token: &str) -> bool {
+    Sha256::digest(expected.as_bytes())
+        .iter()
+        .zip(Sha256::digest(token.as_bytes()).iter())
+        .fold(0, |difference, (a, b)| difference | (a ^ b))
+        == 0
+}
+
+pub fn display_prefix(key: &str) -> String {
+    key.chars().take(DISPLAY_PREFIX_LENGTH).collect()
+}
@@ -0,0 +1,147 @@
+use crate::db::database::Database;
+use crate::db::model::archive::{
+    ARCHIVE_FORMAT, ARCHIVE_VERSION, ArchiveManifest, ArchiveRecord, ArchiveTable,
+};
+use chrono::Utc;
+use futures::stream::{BoxStream, StreamExt};
+use sqlx::PgConnection;
+use std::collections::HashMap;
+use tokio::fs::File;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// Tables in the order they are written, which is also a safe order to insert them in.
+pub const ARCHIVE_TABLES: [&str; 10] = [
+    "match_data",
+    "player_match_data",
+    "match_event",
+    "player_identities",
+    "player_name_history",
+    "tournament",
+    "tournament_team",
+    "tournament_team_player",
+    "tournament_match",
+    "announced_tournament",
+];
+
+/// Counts rows through `conn`, which should be the snapshot the tables are then read through so
+/// the counts match what gets written.
+pub async fn create_manifest(conn: &mut PgConnection) -> Option<ArchiveManifest> {
+    Some(ArchiveManifest {
+        format: String::from(ARCHIVE_FORMAT),
+        version: ARCHIVE_VERSION,
+        created_at: Utc::now().timestamp_millis(),
+        tables: Database::get_archive_table_counts(conn).await?,
+    })
+}
+
+/// A table's rows as they come off the connection, so an export never holds a whole table.
+pub fn read_archive_table<'c>(
+    conn: &'c mut PgConnection,
+    table: &str,
+) -> Option<BoxStream<'c, Result<ArchiveRecord, sqlx::Error>>> {
+    let records = match table {
+        "match_data" => Database::get_archived_matches(conn)
+            .map(|row| row.map(ArchiveRecord::MatchData))
+            .boxed(),
+        "player_match_data" => Database::get_archived_player_matches(conn)
+            .map(|row| row.map(ArchiveRecord::PlayerMatchData))
+            .boxed(),
+        "match_event" => Database::get_archived_match_events(conn)
+            .map(|row| row.map(ArchiveRecord::MatchEvent))
+            .boxed(),
+        "player_identities" => Database::get_archived_player_identities(conn)
+            .map(|row| row.map(ArchiveRecord::PlayerIdentities))
+            .boxed(),
+        "player_name_history" => Database::get_archived_name_history(conn)
+            .map(|row| row.map(ArchiveRecord::PlayerNameHistory))
+            .boxed(),
+        "tournament" => Database::get_archived_tournaments(conn)
+            .map(|row| row.map(ArchiveRecord::Tournament))
+            .boxed(),
+        "tournament_team" => Database::get_archived_tournament_teams(conn)
+            .map(|row| row.map(ArchiveRecord::TournamentTeam))
+            .boxed(),
+        "tournament_team_player" => Database::get_archived_tournament_team_players(conn)
+            .map(|row| row.map(ArchiveRecord::TournamentTeamPlayer))
+            .boxed(),
+        "tournament_match" => Database::get_archived_tournament_matches(conn)
+            .map(|row| row.map(ArchiveRecord::TournamentMatch))
+            .boxed(),
+        "announced_tournament" => Database::get_archived_announced_tournaments(conn)
+            .map(|row| row.map(ArchiveRecord::AnnouncedTournament))
+            .boxed(),
+        _ => return None,
+    };
+    Some(records)
+}
+
+/// Restores an archive written by `/admin/export` into a database whose tables are all empty.
+/// Row counts are checked against the manifest before anything is written, so a truncated
+/// download is rejected instead of half-imported.
+pub async fn import_archive(database: &Database, path: &str) -> Result<Vec<ArchiveTable>, String> {
+    let file = File::open(path)
+        .await
+        .map_err(|e| format!("Could not open {path}: {e}"))?;
+    let mut lines = BufReader::new(file).lines();
+
+    let manifest_line = lines
+        .next_line()
+        .await
+        .map_err(|e| format!("Could not read manifest: {e}"))?
+        .ok_or_else(|| String::from("Archive is empty"))?;
+    let manifest: ArchiveManifest =
+        serde_json::from_str(&manifest_line).map_err(|e| format!("Invalid manifest: {e}"))?;
+    if manifest.format != ARCHIVE_FORMAT || manifest.version != ARCHIVE_VERSION {
+        return Err(format!(
+            "Unsupported archive {} v{}, expected {ARCHIVE_FORMAT} v{ARCHIVE_VERSION}",
+            manifest.format, manifest.version
+        ));
+    }
+
+    let mut records = Vec::new();
+    let mut counts: HashMap<&'static str, u64> = HashMap::new();
+    while let Some(line) = lines
+        .next_line()
+        .await
+        .map_err(|e| format!("Could not read archive: {e}"))?
+    {
+        if line.is_empty() {
+            continue;
+        }
+        let record: ArchiveRecord = serde_json::from_str(&line)
+            .map_err(|e| format!("Invalid record on line {}: {e}", records.len() + 2))?;
+        *counts.entry(record.table()).or_default() += 1;
+        records.push(record);
+    }
+
+    for table in &manifest.tables {
+        let found = counts.get(table.name.as_str()).copied().unwrap_or(0);
+        if found != table.rows {
+            return Err(format!(
+                "Archive has {found} rows for {} but the manifest lists {}",
+                table.name, table.rows
+            ));
+        }
+    }
+
+    let mut snapshot = database
+        .begin_snapshot()
+        .await
+        .map_err(|e| format!("Could not inspect the target database: {e}"))?;
+    let existing = Database::get_archive_table_counts(&mut snapshot)
+        .await
+        .ok_or_else(|| String::from("Could not inspect the target database"))?;
+    drop(snapshot);
+    if let Some(table) = existing.iter().find(|t| t.rows > 0) {
+        return Err(format!(
+            "Target database is not empty, {} already has {} rows",
+            table.name, table.rows
+        ));
+    }
+
+    database
+        .restore_archive(&records)
+        .await
+        .map_err(|e| format!("Restore failed and was rolled back: {e}"))?;
+    Ok(manifest.tables)
+}
@@ -4,12 +4,13 @@ use std::hash::Hash;
 use std::pin::Pin;
 use std::sync::Arc;
 use tokio::task::JoinSet;
+use tracing::Instrument;
 
 // pub trait KeyedDataAccessor<K, V> {
 //     async fn get(&mut self, key: K) -> V;
 // }
 
-#[allow(dead_code, clippy::type_complexity)]
+#[allow(clippy::type_complexity)]
 pub struct LoadingCacheDataAccessor<K, V> {
     pub loader: Arc<dyn Fn(K) -> Pin<Box<dyn Future<Output = Option<V>> + Send>> + Send + Sync>,
     pub cache: Cache<K, V>,
@@ -17,7 +18,6 @@ pub struct LoadingCacheDataAccessor<K, V> {
 impl<K: Hash + Eq + Send + Sync + 'static + Clone, V: Clone + Send + Sync + 'static>
     LoadingCacheDataAccessor<K, V>
 {
-    #[allow(dead_code)]
     pub async fn get_batch(&self, keys: Vec<K>) -> HashMap<K, Option<V>> {
         let mut set = JoinSet::new();
 
@@ -39,11 +39,22 @@ impl<K: Hash + Eq + Send + Sync + 'static + Clone, V: Clone + Send + Sync + 'sta
                 };
                 (key.clone(), value)
             };
-            set.spawn(future);
+            set.spawn(future.in_current_span());
         }
         let results = set.join_all().await;
         results.into_iter().collect()
     }
+
+    /// Gets one value, producing it with `load` rather than the accessor's loader when it's
+    /// missing, for values that take more than the key to produce. Concurrent misses share one
+    /// load, and a failed load is handed back instead of being cached.
+    pub async fn try_get_with<E: Send + Sync + 'static>(
+        &self,
+        key: K,
+        load: impl Future<Output = Result<V, E>>,
+    ) -> Result<V, Arc<E>> {
+        self.cache.try_get_with(key, load).await
+    }
 }
 
 // impl<
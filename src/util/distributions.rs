@@ -0,0 +1,218 @@
+use crate::db::database::Database;
+use crate::db::model::player_match_stats::PlayerMatchStats;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+pub struct StatField {
+    pub name: &'static str,
+    pub value: fn(&PlayerMatchStats) -> f64,
+}
+
+/// Every numeric field of `PlayerMatchStats`, leaving out `team`.
+pub const STAT_FIELDS: [StatField; 18] = [
+    StatField {
+        name: "kills",
+        value: |stats| f64::from(stats.kills),
+    },
+    StatField {
+        name: "deaths",
+        value: |stats| f64::from(stats.deaths),
+    },
+    StatField {
+        name: "assists",
+        value: |stats| f64::from(stats.assists),
+    },
+    StatField {
+        name: "killstreak",
+        value: |stats| f64::from(stats.killstreak),
+    },
+    StatField {
+        name: "damage_dealt",
+        value: |stats| stats.damage_dealt,
+    },
+    StatField {
+        name: "damage_taken",
+        value: |stats| stats.damage_taken,
+    },
+    StatField {
+        name: "pickups",
+        value: |stats| f64::from(stats.pickups),
+    },
+    StatField {
+        name: "throws",
+        value: |stats| f64::from(stats.throws),
+    },
+    StatField {
+        name: "passes",
+        value: |stats| f64::from(stats.passes),
+    },
+    StatField {
+        name: "catches",
+        value: |stats| f64::from(stats.catches),
+    },
+    StatField {
+        name: "strips",
+        value: |stats| f64::from(stats.strips),
+    },
+    StatField {
+        name: "touchdowns",
+        value: |stats| f64::from(stats.touchdowns),
+    },
+    StatField {
+        name: "touchdown_passes",
+        value: |stats| f64::from(stats.touchdown_passes),
+    },
+    StatField {
+        name: "passing_blocks",
+        value: |stats| f64::from(stats.passing_blocks),
+    },
+    StatField {
+        name: "receive_blocks",
+        value: |stats| f64::from(stats.receive_blocks),
+    },
+    StatField {
+        name: "defensive_interceptions",
+        value: |stats| f64::from(stats.defensive_interceptions),
+    },
+    StatField {
+        name: "pass_interceptions",
+        value: |stats| f64::from(stats.pass_interceptions),
+    },
+    StatField {
+        name: "damage_carrier",
+        value: |stats| f64::from(stats.damage_carrier),
+    },
+];
+
+/// Sorted values of one stat over every player line in a pub match.
+pub struct StatPopulation {
+    pub name: &'static str,
+    pub per_game: Vec<f64>,
+    pub per_minute: Vec<f64>,
+}
+
+/// Share of `sorted` below `value`, with ties counted as half, as a percentage.
+pub fn percentile_rank(sorted: &[f64], value: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let below = sorted.partition_point(|v| *v < value);
+    let at_or_below = sorted.partition_point(|v| *v <= value);
+    (below as f64 + (at_or_below - below) as f64 / 2.0) * 100.0 / sorted.len() as f64
+}
+
+pub struct StatDistributions {
+    pub populations: Vec<StatPopulation>,
+}
+
+impl StatDistributions {
+    /// Tournament matches are left out, since their players and pace aren't representative.
+    pub async fn load(database: &Database) -> Option<Self> {
+        let matches = database.get_matches_all().await?;
+        let durations: BTreeMap<u32, u32> = matches
+            .into_iter()
+            .filter(|(_, data)| !data.is_tourney)
+            .map(|(id, data)| (id, data.duration))
+            .collect();
+        let match_ids: Vec<u32> = durations.keys().copied().collect();
+        let stats_by_match = database.get_player_stats_for_matches(&match_ids).await?;
+
+        let mut populations: Vec<StatPopulation> = STAT_FIELDS
+            .iter()
+            .map(|field| StatPopulation {
+                name: field.name,
+                per_game: Vec::new(),
+                per_minute: Vec::new(),
+            })
+            .collect();
+        for (match_id, match_stats) in &stats_by_match {
+            let minutes = f64::from(durations.get(match_id).copied().unwrap_or(0)) / 60.0;
+            for stats in match_stats.values() {
+                for (field, population) in STAT_FIELDS.iter().zip(&mut populations) {
+                    let value = (field.value)(stats);
+                    population.per_game.push(value);
+                    if minutes > 0.0 {
+                        population.per_minute.push(value / minutes);
+                    }
+                }
+            }
+        }
+        for population in &mut populations {
+            population.per_game.sort_by(f64::total_cmp);
+            population.per_minute.sort_by(f64::total_cmp);
+        }
+        Some(Self { populations })
+    }
+
+    pub fn population(&self, name: &str) -> Option<&StatPopulation> {
+        self.populations
+            .iter()
+            .find(|population| population.name == name)
+    }
+
+    /// Where each of a single game's stats falls among all pub games.
+    pub fn percentile_ranks(&self, stats: &PlayerMatchStats) -> BTreeMap<String, f64> {
+        STAT_FIELDS
+            .iter()
+            .zip(&self.populations)
+            .map(|(field, population)| {
+                (
+                    String::from(field.name),
+                    percentile_rank(&population.per_game, (field.value)(stats)),
+                )
+            })
+            .collect()
+    }
+
+    /// Where each of a single game's stats, per minute played, falls among all pub games.
+    pub fn per_minute_percentile_ranks(
+        &self,
+        stats: &PlayerMatchStats,
+        duration: u32,
+    ) -> BTreeMap<String, f64> {
+        if duration == 0 {
+            return BTreeMap::new();
+        }
+        let minutes = f64::from(duration) / 60.0;
+        STAT_FIELDS
+            .iter()
+            .zip(&self.populations)
+            .map(|(field, population)| {
+                (
+                    String::from(field.name),
+                    percentile_rank(&population.per_minute, (field.value)(stats) / minutes),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Keeps the population loaded, reloading it in the background so no request ever waits on the
+/// scan. There is nothing to rank against until the first load has finished.
+pub struct Distributions {
+    current: watch::Sender<Option<Arc<StatDistributions>>>,
+}
+
+impl Distributions {
+    pub fn spawn(database: Arc<Database>, refresh_interval: Duration) -> Self {
+        let current = watch::Sender::new(None);
+        let task_current = current.clone();
+        tokio::spawn(async move {
+            database.wait_until_ready().await;
+            let mut interval = tokio::time::interval(refresh_interval);
+            loop {
+                interval.tick().await;
+                if let Some(distributions) = StatDistributions::load(&database).await {
+                    task_current.send_replace(Some(Arc::new(distributions)));
+                }
+            }
+        });
+        Self { current }
+    }
+
+    pub fn get(&self) -> Option<Arc<StatDistributions>> {
+        self.current.borrow().clone()
+    }
+}
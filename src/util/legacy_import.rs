@@ -0,0 +1,336 @@
+use crate::db::database::Database;
+use crate::db::model::archive::{
+    ArchiveRecord, ArchivedMatch, ArchivedPlayerIdentity, ArchivedPlayerMatch,
+};
+use crate::db::model::match_data::PlayerlessMatchData;
+use crate::db::model::player_match_stats::PlayerMatchStats;
+use sqlx::Row;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqliteRow};
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+#[derive(Clone, Copy)]
+enum ColumnKind {
+    Integer,
+    Real,
+    Text,
+    Blob,
+}
+
+impl ColumnKind {
+    const fn sql_type(self) -> &'static str {
+        match self {
+            Self::Integer => "INTEGER",
+            Self::Real => "REAL",
+            Self::Text => "TEXT",
+            Self::Blob => "BLOB",
+        }
+    }
+}
+
+/// A column we expect in a legacy table. Columns without a default must be present.
+struct LegacyColumn {
+    name: &'static str,
+    kind: ColumnKind,
+    default: Option<&'static str>,
+}
+
+const fn required(name: &'static str, kind: ColumnKind) -> LegacyColumn {
+    LegacyColumn {
+        name,
+        kind,
+        default: None,
+    }
+}
+
+const fn optional(name: &'static str, kind: ColumnKind, default: &'static str) -> LegacyColumn {
+    LegacyColumn {
+        name,
+        kind,
+        default: Some(default),
+    }
+}
+
+const MATCH_COLUMNS: [LegacyColumn; 13] = [
+    required("match", ColumnKind::Integer),
+    optional("server", ColumnKind::Text, "'Unknown'"),
+    optional("start_time", ColumnKind::Integer, "0"),
+    optional("duration", ColumnKind::Integer, "0"),
+    optional("winner", ColumnKind::Integer, "0"),
+    optional("team_one_score", ColumnKind::Integer, "0"),
+    optional("team_two_score", ColumnKind::Integer, "0"),
+    optional("map", ColumnKind::Text, "'Unknown'"),
+    optional("is_tourney", ColumnKind::Integer, "0"),
+    optional("team_one_name", ColumnKind::Text, "'Unknown'"),
+    optional("team_two_name", ColumnKind::Text, "'Unknown'"),
+    optional("team_one_color", ColumnKind::Integer, "NULL"),
+    optional("team_two_color", ColumnKind::Integer, "NULL"),
+];
+
+const PLAYER_MATCH_COLUMNS: [LegacyColumn; 21] = [
+    required("player", ColumnKind::Blob),
+    required("match", ColumnKind::Integer),
+    optional("team", ColumnKind::Integer, "0"),
+    optional("kills", ColumnKind::Integer, "0"),
+    optional("deaths", ColumnKind::Integer, "0"),
+    optional("assists", ColumnKind::Integer, "0"),
+    optional("killstreak", ColumnKind::Integer, "0"),
+    optional("dmg_dealt", ColumnKind::Real, "0.0"),
+    optional("dmg_taken", ColumnKind::Real, "0.0"),
+    optional("pickups", ColumnKind::Integer, "0"),
+    optional("throws", ColumnKind::Integer, "0"),
+    optional("passes", ColumnKind::Integer, "0"),
+    optional("catches", ColumnKind::Integer, "0"),
+    optional("strips", ColumnKind::Integer, "0"),
+    optional("touchdowns", ColumnKind::Integer, "0"),
+    optional("touchdown_passes", ColumnKind::Integer, "0"),
+    optional("passing_blocks", ColumnKind::Real, "0.0"),
+    optional("receive_blocks", ColumnKind::Real, "0.0"),
+    optional("defensive_interceptions", ColumnKind::Real, "0.0"),
+    optional("pass_interceptions", ColumnKind::Real, "0.0"),
+    optional("damage_carrier", ColumnKind::Real, "0.0"),
+];
+
+const PLAYER_IDENTITY_COLUMNS: [LegacyColumn; 2] = [
+    required("uuid", ColumnKind::Blob),
+    required("name", ColumnKind::Text),
+];
+
+#[derive(Default)]
+pub struct ImportCounts {
+    pub inserted: u32,
+    pub skipped: u32,
+    pub conflicted: u32,
+}
+
+#[derive(Default)]
+pub struct LegacyImportReport {
+    pub matches: ImportCounts,
+    pub player_matches: ImportCounts,
+    pub player_identities: ImportCounts,
+}
+
+async fn table_columns(pool: &SqlitePool, table: &str) -> Result<HashSet<String>, String> {
+    let rows = sqlx::query("SELECT name FROM pragma_table_info(?)")
+        .bind(table)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| format!("Could not inspect {table}: {e}"))?;
+    if rows.is_empty() {
+        return Err(format!("Legacy database has no {table} table"));
+    }
+    rows.iter()
+        .map(|row| row.try_get::<String, _>("name"))
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Could not inspect {table}: {e}"))
+}
+
+/// Selects every expected column, substituting the default for any the file predates.
+async fn select_legacy_rows(
+    pool: &SqlitePool,
+    table: &str,
+    columns: &[LegacyColumn],
+) -> Result<Vec<SqliteRow>, String> {
+    let available = table_columns(pool, table).await?;
+    let select_list = columns
+        .iter()
+        .map(|column| {
+            let source = match (available.contains(column.name), column.default) {
+                (true, Some(default)) => format!("COALESCE(\"{}\", {default})", column.name),
+                (true, None) => format!("\"{}\"", column.name),
+                (false, Some(default)) => String::from(default),
+                (false, None) => {
+                    return Err(format!("Legacy {table} is missing column {}", column.name));
+                }
+            };
+            Ok(format!(
+                "CAST({source} AS {}) AS \"{}\"",
+                column.kind.sql_type(),
+                column.name
+            ))
+        })
+        .collect::<Result<Vec<_>, _>>()?
+        .join(", ");
+
+    sqlx::query(&format!("SELECT {select_list} FROM {table}"))
+        .fetch_all(pool)
+        .await
+        .map_err(|e| format!("Could not read {table}: {e}"))
+}
+
+fn read_match(row: &SqliteRow) -> Result<ArchivedMatch, sqlx::Error> {
+    Ok(ArchivedMatch {
+        id: row.try_get::<i64, _>("match")? as u32,
+        data: PlayerlessMatchData {
+            server: row.try_get("server")?,
+            start_time: row.try_get::<i64, _>("start_time")? as u64,
+            duration: row.try_get::<i64, _>("duration")? as u32,
+            winner: row.try_get::<i64, _>("winner")? as i32,
+            team_one_score: row.try_get::<i64, _>("team_one_score")? as u32,
+            team_two_score: row.try_get::<i64, _>("team_two_score")? as u32,
+            map: row.try_get("map")?,
+            is_tourney: row.try_get::<i64, _>("is_tourney")? != 0,
+            team_one_name: row.try_get("team_one_name")?,
+            team_two_name: row.try_get("team_two_name")?,
+            team_one_color: row
+                .try_get::<Option<i64>, _>("team_one_color")?
+                .map(|n| n as u32),
+            team_two_color: row
+                .try_get::<Option<i64>, _>("team_two_color")?
+                .map(|n| n as u32),
+        },
+    })
+}
+
+fn read_player_match(row: &SqliteRow) -> Result<ArchivedPlayerMatch, sqlx::Error> {
+    Ok(ArchivedPlayerMatch {
+        match_id: row.try_get::<i64, _>("match")? as u32,
+        player: Database::parse_uuid(row.try_get("player")?),
+        stats: PlayerMatchStats {
+            team: row.try_get::<i64, _>("team")? as i32,
+            kills: row.try_get::<i64, _>("kills")? as u32,
+            deaths: row.try_get::<i64, _>("deaths")? as u32,
+            assists: row.try_get::<i64, _>("assists")? as u32,
+            killstreak: row.try_get::<i64, _>("killstreak")? as u32,
+            damage_dealt: row.try_get("dmg_dealt")?,
+            damage_taken: row.try_get("dmg_taken")?,
+            pickups: row.try_get::<i64, _>("pickups")? as u32,
+            throws: row.try_get::<i64, _>("throws")? as u32,
+            passes: row.try_get::<i64, _>("passes")? as u32,
+            catches: row.try_get::<i64, _>("catches")? as u32,
+            strips: row.try_get::<i64, _>("strips")? as u32,
+            touchdowns: row.try_get::<i64, _>("touchdowns")? as u32,
+            touchdown_passes: row.try_get::<i64, _>("touchdown_passes")? as u32,
+            passing_blocks: row.try_get::<f64, _>("passing_blocks")? as f32,
+            receive_blocks: row.try_get::<f64, _>("receive_blocks")? as f32,
+            defensive_interceptions: row.try_get::<f64, _>("defensive_interceptions")? as u32,
+            pass_interceptions: row.try_get::<f64, _>("pass_interceptions")? as u32,
+            damage_carrier: row.try_get::<f64, _>("damage_carrier")? as f32,
+        },
+    })
+}
+
+fn read_player_identity(row: &SqliteRow) -> Result<ArchivedPlayerIdentity, sqlx::Error> {
+    Ok(ArchivedPlayerIdentity {
+        uuid: Database::parse_uuid(row.try_get("uuid")?),
+        name: row.try_get("name")?,
+    })
+}
+
+/// Copies a legacy SQLite stats file into Postgres. Rows already present with identical data
+/// are skipped, and rows that differ are left alone and reported as conflicts. That includes
+/// names in `player_identities`, since the file's can only be older than ours.
+pub async fn import_legacy_sqlite(
+    database: &Database,
+    path: &str,
+) -> Result<LegacyImportReport, String> {
+    let pool = SqlitePool::connect_with(SqliteConnectOptions::new().filename(path).read_only(true))
+        .await
+        .map_err(|e| format!("Could not open {path}: {e}"))?;
+
+    let matches = select_legacy_rows(&pool, "match_data", &MATCH_COLUMNS)
+        .await?
+        .iter()
+        .map(read_match)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid match_data row: {e}"))?;
+    let player_matches = select_legacy_rows(&pool, "player_match_data", &PLAYER_MATCH_COLUMNS)
+        .await?
+        .iter()
+        .map(read_player_match)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid player_match_data row: {e}"))?;
+    let player_identities =
+        select_legacy_rows(&pool, "player_identities", &PLAYER_IDENTITY_COLUMNS)
+            .await?
+            .iter()
+            .map(read_player_identity)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Invalid player_identities row: {e}"))?;
+    pool.close().await;
+
+    let mut report = LegacyImportReport::default();
+    let mut tx = database
+        .begin()
+        .await
+        .map_err(|e| format!("Could not start transaction: {e}"))?;
+
+    let mut known_matches = HashSet::new();
+    for m in matches {
+        match database.get_match_by_id(m.id).await {
+            Some(existing) if existing == m.data => report.matches.skipped += 1,
+            Some(_) => report.matches.conflicted += 1,
+            None => {
+                Database::insert_archive_record(&mut tx, &ArchiveRecord::MatchData(m.clone()))
+                    .await
+                    .map_err(|e| format!("Could not insert match {}: {e}", m.id))?;
+                report.matches.inserted += 1;
+            }
+        }
+        known_matches.insert(m.id);
+    }
+
+    let mut seen_player_matches = HashSet::new();
+    let mut existing_by_match: HashMap<u32, HashMap<Uuid, PlayerMatchStats>> = HashMap::new();
+    for p in player_matches {
+        if !seen_player_matches.insert((p.player, p.match_id)) {
+            report.player_matches.skipped += 1;
+            continue;
+        }
+        if !known_matches.contains(&p.match_id) {
+            report.player_matches.conflicted += 1;
+            continue;
+        }
+        let existing = match existing_by_match.entry(p.match_id) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(
+                database
+                    .get_player_match_stats(p.match_id)
+                    .await
+                    .unwrap_or_default(),
+            ),
+        };
+        match existing.get(&p.player) {
+            Some(existing) if *existing == p.stats => report.player_matches.skipped += 1,
+            Some(_) => report.player_matches.conflicted += 1,
+            None => {
+                Database::insert_archive_record(
+                    &mut tx,
+                    &ArchiveRecord::PlayerMatchData(p.clone()),
+                )
+                .await
+                .map_err(|e| {
+                    format!("Could not insert {} in match {}: {e}", p.player, p.match_id)
+                })?;
+                report.player_matches.inserted += 1;
+            }
+        }
+    }
+
+    let mut seen_identities = HashSet::new();
+    for identity in player_identities {
+        if !seen_identities.insert(identity.uuid) {
+            report.player_identities.skipped += 1;
+            continue;
+        }
+        match database.get_username_from_uuid(identity.uuid).await {
+            Some(name) if name == identity.name => report.player_identities.skipped += 1,
+            Some(_) => report.player_identities.conflicted += 1,
+            None => {
+                Database::insert_archive_record(
+                    &mut tx,
+                    &ArchiveRecord::PlayerIdentities(identity.clone()),
+                )
+                .await
+                .map_err(|e| format!("Could not insert {}: {e}", identity.uuid))?;
+                report.player_identities.inserted += 1;
+            }
+        }
+    }
+
+    tx.commit()
+        .await
+        .map_err(|e| format!("Could not commit import: {e}"))?;
+    Ok(report)
+}
@@ -0,0 +1,46 @@
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_SLOW_QUERY_THRESHOLD: Duration = Duration::from_millis(250);
+
+static SLOW_QUERY_THRESHOLD: OnceLock<Duration> = OnceLock::new();
+
+/// Installs the global subscriber, which also picks up everything logged through `log`.
+/// `GHAST_LOG` takes `RUST_LOG`-style directives and defaults to `info`, without Rocket's
+/// per-request chatter. `GHAST_LOG_FORMAT=json` writes one JSON object per line, for production.
+/// `GHAST_SLOW_QUERY_MS` sets how long a `Database` call may take before it is logged as slow.
+pub fn init() {
+    let filter = EnvFilter::try_from_env("GHAST_LOG")
+        .unwrap_or_else(|_| EnvFilter::new("info,rocket::server=warn"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_ansi(std::io::stdout().is_terminal());
+    let result = if std::env::var("GHAST_LOG_FORMAT").is_ok_and(|v| v.eq_ignore_ascii_case("json"))
+    {
+        builder
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .try_init()
+    } else {
+        builder.try_init()
+    };
+    if let Err(e) = result {
+        eprintln!("Error initialising logging: {e}");
+    }
+
+    let threshold = std::env::var("GHAST_SLOW_QUERY_MS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .map_or(DEFAULT_SLOW_QUERY_THRESHOLD, Duration::from_millis);
+    let _ = SLOW_QUERY_THRESHOLD.set(threshold);
+}
+
+pub fn slow_query_threshold() -> Duration {
+    SLOW_QUERY_THRESHOLD
+        .get()
+        .copied()
+        .unwrap_or(DEFAULT_SLOW_QUERY_THRESHOLD)
+}
@@ -0,0 +1,74 @@
+use crate::db::database::Database;
+use crate::db::model::match_data::MatchData;
+use log::warn;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Polls for matches with player rows and broadcasts each new one once. Matches are finished in
+/// whatever order their servers write them, so a match below the highest id seen that still had
+/// no player rows is kept pending and broadcast when it completes, rather than skipped.
+pub struct MatchWatcher {
+    sender: broadcast::Sender<(u32, MatchData)>,
+}
+
+impl MatchWatcher {
+    /// How long a match may stay without player rows before it's given up on, so a header that
+    /// will never be completed doesn't hold the polled range open forever.
+    const PENDING_TIMEOUT: Duration = Duration::from_mins(30);
+
+    pub fn spawn(database: Arc<Database>, poll_interval: Duration) -> Self {
+        let (sender, _) = broadcast::channel(64);
+        let task_sender = sender.clone();
+        tokio::spawn(async move {
+            database.wait_until_ready().await;
+            let mut last_seen = database.get_latest_match_id().await.unwrap_or(0);
+            // matches below `last_seen` still waiting on their player rows, and since when
+            let mut pending: BTreeMap<u32, Instant> = BTreeMap::new();
+            let mut interval = tokio::time::interval(poll_interval);
+            loop {
+                interval.tick().await;
+                pending.retain(|id, since| {
+                    let waiting = since.elapsed() < Self::PENDING_TIMEOUT;
+                    if !waiting {
+                        warn!("Match {id} never got player rows, no longer watching for it");
+                    }
+                    waiting
+                });
+                let floor = pending
+                    .keys()
+                    .next()
+                    .map_or(last_seen, |id| last_seen.min(id - 1));
+                let Some(matches) = database.get_matches_after(floor).await else {
+                    continue;
+                };
+                let mut matches: Vec<_> = matches.into_iter().collect();
+                matches.sort_unstable_by_key(|(id, _)| *id);
+                let previous = last_seen;
+                for (id, data) in matches {
+                    if id <= previous && pending.remove(&id).is_none() {
+                        continue;
+                    }
+                    last_seen = last_seen.max(id);
+                    // no subscribers is fine, the match is simply not pushed anywhere
+                    let _ = task_sender.send((id, data));
+                }
+                if last_seen > previous
+                    && let Some(unfinished) =
+                        database.get_unfinished_match_ids(previous, last_seen).await
+                {
+                    let now = Instant::now();
+                    for id in unfinished {
+                        pending.entry(id).or_insert(now);
+                    }
+                }
+            }
+        });
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<(u32, MatchData)> {
+        self.sender.subscribe()
+    }
+}
@@ -0,0 +1,176 @@
+use crate::db::database::Database;
+use crate::util::logging::slow_query_threshold;
+use log::warn;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+
+/// Everything exported on `/metrics`. Kept global so the database, resolver and Mojang client
+/// can record without every constructor having to carry a handle.
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_duration: HistogramVec,
+    pub query_duration: HistogramVec,
+    pub pool_connections: IntGaugeVec,
+    pub username_lookups: IntCounterVec,
+    pub mojang_requests: IntCounterVec,
+    pub discord_webhook_requests: IntCounterVec,
+    pub rate_limited_requests: IntCounterVec,
+    pub matches: IntGauge,
+    pub players: IntGauge,
+    pub tournaments: IntGauge,
+}
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some(String::from("ghast")), None).unwrap();
+        let metrics = Self {
+            http_requests: IntCounterVec::new(
+                Opts::new(
+                    "http_requests_total",
+                    "Requests served, by route and status",
+                ),
+                &["method", "route", "status"],
+            )
+            .unwrap(),
+            http_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "http_request_duration_seconds",
+                    "Time spent serving requests",
+                ),
+                &["method", "route"],
+            )
+            .unwrap(),
+            query_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "db_query_duration_seconds",
+                    "Time spent in each Database method",
+                ),
+                &["method"],
+            )
+            .unwrap(),
+            pool_connections: IntGaugeVec::new(
+                Opts::new("db_pool_connections", "Pooled connections, by state"),
+                &["state"],
+            )
+            .unwrap(),
+            username_lookups: IntCounterVec::new(
+                Opts::new(
+                    "username_lookups_total",
+                    "Username resolver lookups, by whether the name cache had them",
+                ),
+                &["result"],
+            )
+            .unwrap(),
+            mojang_requests: IntCounterVec::new(
+                Opts::new(
+                    "mojang_requests_total",
+                    "Calls to the Mojang API, by outcome",
+                ),
+                &["outcome"],
+            )
+            .unwrap(),
+            discord_webhook_requests: IntCounterVec::new(
+                Opts::new(
+                    "discord_webhook_requests_total",
+                    "Posts to the Discord webhook, by outcome",
+                ),
+                &["outcome"],
+            )
+            .unwrap(),
+            rate_limited_requests: IntCounterVec::new(
+                Opts::new(
+                    "rate_limited_requests_total",
+                    "Requests turned away with a 429, by route group",
+                ),
+                &["group"],
+            )
+            .unwrap(),
+            matches: IntGauge::new("matches", "Recorded matches").unwrap(),
+            players: IntGauge::new("players", "Distinct players with a recorded match").unwrap(),
+            tournaments: IntGauge::new("tournaments", "Recorded tournaments").unwrap(),
+            registry,
+        };
+        let collectors: [Box<dyn prometheus::core::Collector>; 11] = [
+            Box::new(metrics.http_requests.clone()),
+            Box::new(metrics.http_duration.clone()),
+            Box::new(metrics.query_duration.clone()),
+            Box::new(metrics.pool_connections.clone()),
+            Box::new(metrics.username_lookups.clone()),
+            Box::new(metrics.mojang_requests.clone()),
+            Box::new(metrics.discord_webhook_requests.clone()),
+            Box::new(metrics.rate_limited_requests.clone()),
+            Box::new(metrics.matches.clone()),
+            Box::new(metrics.players.clone()),
+            Box::new(metrics.tournaments.clone()),
+        ];
+        for collector in collectors {
+            metrics.registry.register(collector).unwrap();
+        }
+        metrics
+    }
+
+    /// Observes how long a `Database` method runs once the returned timer is dropped.
+    pub fn time_query(&self, method: &'static str) -> QueryTimer {
+        QueryTimer {
+            method,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn render(&self) -> Option<String> {
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            warn!("Error encoding metrics: {e:?}");
+            return None;
+        }
+        String::from_utf8(buffer).ok()
+    }
+}
+
+/// Refreshes the match, player and tournament gauges every `interval`. Counting players scans
+/// all of `player_match_data`, far too much to do on every scrape.
+pub fn spawn_domain_gauges(database: Arc<Database>, interval: Duration) {
+    tokio::spawn(async move {
+        database.wait_until_ready().await;
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            if let Some((matches, players, tournaments)) = database.get_domain_counts().await {
+                METRICS.matches.set(matches);
+                METRICS.players.set(players);
+                METRICS.tournaments.set(tournaments);
+            }
+        }
+    });
+}
+
+/// Records a query's duration when dropped, and logs it if it took longer than the slow query
+/// threshold.
+pub struct QueryTimer {
+    method: &'static str,
+    started: Instant,
+}
+
+impl Drop for QueryTimer {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed();
+        METRICS
+            .query_duration
+            .with_label_values(&[self.method])
+            .observe(elapsed.as_secs_f64());
+        if elapsed >= slow_query_threshold() {
+            tracing::warn!(
+                method = self.method,
+                elapsed_ms = elapsed.as_millis() as u64,
+                "slow query"
+            );
+        }
+    }
+}
@@ -1,2 +1,13 @@
+pub mod api_keys;
+pub mod archive;
 pub mod cached_accessor;
+pub mod distributions;
+pub mod legacy_import;
+pub mod logging;
+pub mod match_watcher;
+pub mod metrics;
+pub mod name_history;
+pub mod notifications;
+pub mod tournament_watcher;
 pub mod username_resolver;
+pub mod win_probability;
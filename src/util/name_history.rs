@@ -0,0 +1,40 @@
+use crate::db::database::Database;
+use crate::db::model::match_data::MatchData;
+use log::{info, warn};
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Keeps `player_name_history` in step with `player_identities`: all of it once at startup, then
+/// the players of each new match as it lands, since that is when the game servers write names.
+/// Falling behind on matches, or failing to sync, means a full sync again.
+pub fn spawn_name_history_sync(
+    database: Arc<Database>,
+    mut new_matches: broadcast::Receiver<(u32, MatchData)>,
+) {
+    tokio::spawn(async move {
+        database.wait_until_ready().await;
+        let mut players: Option<Vec<Uuid>> = None;
+        loop {
+            let result = match &players {
+                Some(players) => database.sync_player_names(players).await,
+                None => database.sync_name_history().await,
+            };
+            let synced = match result {
+                Ok(rows) => {
+                    info!("Synced {rows} names into name history");
+                    true
+                }
+                Err(e) => {
+                    warn!("Error syncing name history: {e:?}");
+                    false
+                }
+            };
+            players = match new_matches.recv().await {
+                Ok((_, data)) if synced => Some(data.players),
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => None,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+        }
+    });
+}
@@ -0,0 +1,322 @@
+use crate::db::database::Database;
+use crate::db::model::match_data::MatchData;
+use crate::db::model::player_match_stats::PlayerMatchStats;
+use crate::external::discord::{DiscordWebhook, Embed, EmbedField, WebhookMessage};
+use crate::util::username_resolver::UsernameResolver;
+use crate::web::routes::indexes::{aggregate_player_stats, calculate_player_indexes};
+use crate::web::routes::tournament::{determine_awards, score_players};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Discord's blurple, for embeds with no team colour to borrow.
+const DEFAULT_COLOR: u32 = 0x5865F2;
+const TOP_PERFORMERS: usize = 3;
+
+/// A career total worth announcing, each time it passes another multiple of `step`.
+struct Milestone {
+    stat: &'static str,
+    step: u32,
+    /// Read off a number of matches and the lines summed over them.
+    value: fn(u32, &PlayerMatchStats) -> u32,
+}
+
+const MILESTONES: [Milestone; 4] = [
+    Milestone {
+        stat: "matches",
+        step: 100,
+        value: |matches, _| matches,
+    },
+    Milestone {
+        stat: "kills",
+        step: 1000,
+        value: |_, stats| stats.kills,
+    },
+    Milestone {
+        stat: "touchdowns",
+        step: 100,
+        value: |_, stats| stats.touchdowns,
+    },
+    Milestone {
+        stat: "touchdown passes",
+        step: 100,
+        value: |_, stats| stats.touchdown_passes,
+    },
+];
+
+struct Notifier {
+    webhook: DiscordWebhook,
+    database: Arc<Database>,
+    username_resolver: Arc<UsernameResolver>,
+}
+
+/// Posts each finished match, and the milestones players reached in it, to the webhook. Checks
+/// for tournaments that have been given a winner every `tournament_interval`, and after each
+/// match, announcing their awards once. Which tournaments have been announced is kept in the
+/// database, so one decided while we were down still goes out, and one whose post failed is
+/// tried again on the next check.
+pub fn spawn_discord_notifications(
+    webhook: DiscordWebhook,
+    database: Arc<Database>,
+    username_resolver: Arc<UsernameResolver>,
+    mut new_matches: broadcast::Receiver<(u32, MatchData)>,
+    tournament_interval: Duration,
+) {
+    tokio::spawn(async move {
+        database.wait_until_ready().await;
+        let notifier = Notifier {
+            webhook,
+            database,
+            username_resolver,
+        };
+        let mut interval = tokio::time::interval(tournament_interval);
+        loop {
+            tokio::select! {
+                received = new_matches.recv() => match received {
+                    Ok((match_id, data)) => notifier.match_finished(match_id, data).await,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("Skipped notifying {skipped} matches");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = interval.tick() => {}
+            }
+            notifier.check_tournaments().await;
+        }
+    });
+}
+
+impl Notifier {
+    /// Posts the embeds over as many messages as it takes, saying whether they all went out.
+    async fn send(&self, embeds: Vec<Embed>) -> bool {
+        let mut embeds = embeds.into_iter().peekable();
+        let mut sent = true;
+        while embeds.peek().is_some() {
+            let message = WebhookMessage {
+                embeds: embeds.by_ref().take(DiscordWebhook::MAX_EMBEDS).collect(),
+            };
+            sent &= self.webhook.post(&message).await;
+        }
+        sent
+    }
+
+    async fn match_finished(&self, match_id: u32, data: MatchData) {
+        let Some(stats) = self.database.get_player_match_stats(match_id).await else {
+            return;
+        };
+        let uuids: Vec<Uuid> = stats.keys().copied().collect();
+        let (names, career_totals) = tokio::join!(
+            self.username_resolver.resolve_batch(uuids.clone()),
+            self.database.get_player_career_totals(&uuids),
+        );
+        let name = |uuid: &Uuid| {
+            names
+                .get(uuid)
+                .cloned()
+                .flatten()
+                .unwrap_or_else(|| String::from("Unknown"))
+        };
+
+        let team_map: HashMap<Uuid, i32> = stats.iter().map(|(uuid, s)| (*uuid, s.team)).collect();
+        let aggregates = aggregate_player_stats(
+            &HashMap::from([(match_id, stats.clone())]),
+            &HashMap::from([(match_id, data.duration)]),
+            &team_map,
+        );
+        let mut indexes = calculate_player_indexes(&aggregates);
+        indexes.sort_by(|a, b| b.1.total.total_cmp(&a.1.total));
+        let top_performers = indexes
+            .iter()
+            .take(TOP_PERFORMERS)
+            .filter_map(|(uuid, index)| {
+                let line = stats.get(uuid)?;
+                Some(format!(
+                    "**{}** ({:.1}): {} kills, {} deaths, {} touchdowns, {} passes",
+                    name(uuid),
+                    index.total,
+                    line.kills,
+                    line.deaths,
+                    line.touchdowns,
+                    line.passes
+                ))
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let (headline, color) = match data.winner {
+            1 => (format!("{} wins", data.team_one_name), data.team_one_color),
+            2 => (format!("{} wins", data.team_two_name), data.team_two_color),
+            _ => (String::from("Draw"), None),
+        };
+        let mut embeds = vec![Embed {
+            title: format!("Match {match_id} on {}", data.map),
+            description: Some(format!(
+                "{headline}\n**{}** {} - {} **{}**",
+                data.team_one_name, data.team_one_score, data.team_two_score, data.team_two_name
+            )),
+            color: Some(color.unwrap_or(DEFAULT_COLOR)),
+            fields: vec![
+                EmbedField {
+                    name: String::from("Duration"),
+                    value: format!("{}:{:02}", data.duration / 60, data.duration % 60),
+                    inline: true,
+                },
+                EmbedField {
+                    name: String::from("Server"),
+                    value: data.server.clone(),
+                    inline: true,
+                },
+                EmbedField {
+                    name: String::from("Top performers"),
+                    value: if top_performers.is_empty() {
+                        String::from("Nobody")
+                    } else {
+                        top_performers
+                    },
+                    inline: false,
+                },
+            ],
+            timestamp: DateTime::from_timestamp_millis(
+                (data.start_time + u64::from(data.duration) * 1000) as i64,
+            )
+            .map(|time| time.to_rfc3339()),
+        }];
+
+        for (uuid, (matches, totals)) in career_totals.unwrap_or_default() {
+            let Some(line) = stats.get(&uuid) else {
+                continue;
+            };
+            for (stat, total) in milestones_reached(line, matches, &totals) {
+                embeds.push(Embed {
+                    title: format!("{} reached {total} career {stat}", name(&uuid)),
+                    color: Some(DEFAULT_COLOR),
+                    ..Default::default()
+                });
+            }
+        }
+        self.send(embeds).await;
+    }
+
+    async fn check_tournaments(&self) {
+        let Some(tournaments) = self.database.get_unannounced_tournaments().await else {
+            return;
+        };
+        for tournament in tournaments {
+            if !self
+                .tournament_decided(tournament.id, &tournament.name)
+                .await
+            {
+                continue;
+            }
+            let announced_at = Utc::now().timestamp_millis() as u64;
+            if let Err(e) = self
+                .database
+                .mark_tournament_announced(tournament.id, announced_at)
+                .await
+            {
+                warn!(
+                    "Error marking tournament {} announced, it will be announced again: {e:?}",
+                    tournament.id
+                );
+            }
+        }
+    }
+
+    /// Announces a tournament's awards, saying whether they worked out and were posted.
+    async fn tournament_decided(&self, tournament_id: u32, tournament_name: &str) -> bool {
+        let tournament_ids = [tournament_id];
+        let (matches, team_players) = tokio::join!(
+            self.database.get_tournament_matches(&tournament_ids),
+            self.database.get_tournament_team_players(&tournament_ids),
+        );
+        let (Some(matches), Some(team_players)) = (matches, team_players) else {
+            return false;
+        };
+        let match_ids: Vec<u32> = matches.iter().map(|m| m.match_id).collect();
+        let Some(stats_by_match) = self.database.get_player_stats_for_matches(&match_ids).await
+        else {
+            return false;
+        };
+        let (_, indexes) = score_players(&matches, &team_players, &stats_by_match);
+        let awards = determine_awards(&indexes);
+
+        let winners: Vec<(&str, Option<Uuid>)> = vec![
+            ("Overall MVP", awards.mvp),
+            ("Best Offensive Player", awards.opot),
+            ("Best Defensive Player", awards.dpot),
+            ("Best PvPer", awards.oldl),
+            ("Best Passer", awards.passer),
+            ("Best Receiver", awards.receiver),
+        ];
+        let names = self
+            .username_resolver
+            .resolve_batch(
+                winners
+                    .iter()
+                    .filter_map(|(_, uuid)| *uuid)
+                    .chain(awards.all_tournament.iter().copied())
+                    .collect(),
+            )
+            .await;
+        let name = |uuid: &Uuid| {
+            names
+                .get(uuid)
+                .cloned()
+                .flatten()
+                .unwrap_or_else(|| String::from("Unknown"))
+        };
+
+        let mut fields: Vec<EmbedField> = winners
+            .into_iter()
+            .filter_map(|(award, uuid)| {
+                Some(EmbedField {
+                    name: String::from(award),
+                    value: name(&uuid?),
+                    inline: true,
+                })
+            })
+            .collect();
+        if !awards.all_tournament.is_empty() {
+            fields.push(EmbedField {
+                name: String::from("All-Tournament"),
+                value: awards
+                    .all_tournament
+                    .iter()
+                    .map(name)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                inline: false,
+            });
+        }
+        info!("Announcing awards for tournament {tournament_id}");
+        self.send(vec![Embed {
+            title: format!("{tournament_name} awards"),
+            description: Some(format!("{} matches played", matches.len())),
+            color: Some(DEFAULT_COLOR),
+            fields,
+            ..Default::default()
+        }])
+        .await
+    }
+}
+
+/// The milestones a player's `line` from one match took them past, given their `totals` over all
+/// `matches` they have played, that one included.
+fn milestones_reached(
+    line: &PlayerMatchStats,
+    matches: u32,
+    totals: &PlayerMatchStats,
+) -> Vec<(&'static str, u32)> {
+    MILESTONES
+        .iter()
+        .filter_map(|&Milestone { stat, step, value }| {
+            let total = value(matches, totals);
+            let before = total.saturating_sub(value(1, line));
+            (before / step < total / step).then_some((stat, total / step * step))
+        })
+        .collect()
+}
@@ -0,0 +1,38 @@
+use crate::db::database::Database;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Polls the tournament tables, which are edited outside ghast, and broadcasts whenever any of
+/// them has changed since the last poll.
+pub struct TournamentWatcher {
+    sender: broadcast::Sender<()>,
+}
+
+impl TournamentWatcher {
+    pub fn spawn(database: Arc<Database>, poll_interval: Duration) -> Self {
+        let (sender, _) = broadcast::channel(16);
+        let task_sender = sender.clone();
+        tokio::spawn(async move {
+            database.wait_until_ready().await;
+            let mut last_seen = database.get_tournament_fingerprint().await;
+            let mut interval = tokio::time::interval(poll_interval);
+            loop {
+                interval.tick().await;
+                let Some(fingerprint) = database.get_tournament_fingerprint().await else {
+                    continue;
+                };
+                if last_seen.as_ref() != Some(&fingerprint) {
+                    last_seen = Some(fingerprint);
+                    // no subscribers is fine, nobody needed to know
+                    let _ = task_sender.send(());
+                }
+            }
+        });
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<()> {
+        self.sender.subscribe()
+    }
+}
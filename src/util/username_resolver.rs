@@ -1,24 +1,34 @@
 use crate::db::database::Database;
 use crate::external::mojang::MojangApi;
 use crate::util::cached_accessor::LoadingCacheDataAccessor;
+use crate::util::metrics::METRICS;
+use log::warn;
 use moka::future::Cache;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
 use uuid::Uuid;
 
+/// Resolves player names from the database, falling back to Mojang when enabled. Every field is a
+/// concurrent cache, so a single resolver is shared across requests without locking.
 pub struct UsernameResolver {
-    #[allow(dead_code)]
-    loading_cache: LoadingCacheDataAccessor<Uuid, String>,
+    /// Only present when Mojang lookups are enabled.
+    loading_cache: Option<LoadingCacheDataAccessor<Uuid, String>>,
+    /// Players Mojang recently had no name for, so they aren't looked up on every request.
+    misses: Cache<Uuid, ()>,
+    /// Recently resolved names, including players nobody has a name for yet.
+    names: Cache<Uuid, Option<String>>,
+    /// Batches being loaded right now. Concurrent requests for the same batch wait on the first
+    /// one's load instead of querying the database again.
+    in_flight: Cache<Vec<Uuid>, Arc<HashMap<Uuid, Option<String>>>>,
     database: Arc<Database>,
 }
 
 impl UsernameResolver {
-    pub fn create(database: Arc<Database>) -> Self {
-        let mojang_api = Arc::new(MojangApi::new());
-        Self {
-            loading_cache: LoadingCacheDataAccessor {
-                // loader: Arc::new(move |x| Box::pin(mojang_api.get_username_from_uuid(x))),
+    pub fn create(database: Arc<Database>, mojang_api: Option<MojangApi>) -> Self {
+        let loading_cache = mojang_api.map(|mojang_api| {
+            let mojang_api = Arc::new(mojang_api);
+            LoadingCacheDataAccessor {
                 loader: Arc::new(move |x| {
                     let mojang_api = mojang_api.clone();
                     Box::pin(async move { mojang_api.get_username_from_uuid(x).await })
@@ -28,32 +38,105 @@ impl UsernameResolver {
                     .time_to_live(Duration::from_hours(2))
                     .max_capacity(2048)
                     .build(),
-            },
+            }
+        });
+        Self {
+            loading_cache,
+            misses: Cache::builder()
+                .time_to_live(Duration::from_mins(10))
+                .max_capacity(4096)
+                .build(),
+            // Short enough that a rename picked up by the match watcher shows up quickly.
+            names: Cache::builder()
+                .time_to_live(Duration::from_secs(60))
+                .max_capacity(16384)
+                .build(),
+            in_flight: Cache::builder()
+                .time_to_live(Duration::from_secs(1))
+                .max_capacity(256)
+                .build(),
             database,
         }
     }
 
+    /// Entry counts of the name and Mojang miss caches, and whether Mojang lookups are enabled.
+    pub fn cache_status(&self) -> (u64, u64, bool) {
+        (
+            self.names.entry_count(),
+            self.misses.entry_count(),
+            self.loading_cache.is_some(),
+        )
+    }
+
     pub async fn resolve_batch(&self, uuids: Vec<Uuid>) -> HashMap<Uuid, Option<String>> {
         let mut names: HashMap<Uuid, Option<String>> = HashMap::new();
+        let mut missing = Vec::new();
+        for uuid in uuids {
+            match self.names.get(&uuid).await {
+                Some(name) => {
+                    names.insert(uuid, name);
+                }
+                None => missing.push(uuid),
+            }
+        }
+        METRICS
+            .username_lookups
+            .with_label_values(&["hit"])
+            .inc_by(names.len() as u64);
+        METRICS
+            .username_lookups
+            .with_label_values(&["miss"])
+            .inc_by(missing.len() as u64);
+        if missing.is_empty() {
+            return names;
+        }
 
-        let mut results = {
-            let db = self.database.clone();
-            db.get_usernames_from_uuids(&uuids).await
+        missing.sort_unstable();
+        missing.dedup();
+        let loaded = self
+            .in_flight
+            .get_with_by_ref(&missing, self.load_batch(&missing))
+            .await;
+        for uuid in missing {
+            names.insert(uuid, loaded.get(&uuid).cloned().flatten());
+        }
+        names
+    }
+
+    async fn load_batch(&self, uuids: &[Uuid]) -> Arc<HashMap<Uuid, Option<String>>> {
+        let Some(mut results) = self.database.get_usernames_from_uuids(uuids).await else {
+            // Nothing is cached, so the next request tries the database again.
+            return Arc::new(uuids.iter().map(|uuid| (*uuid, None)).collect());
         };
+        let mut names: HashMap<Uuid, Option<String>> = uuids
+            .iter()
+            .map(|uuid| (*uuid, results.remove(uuid)))
+            .collect();
 
-        for uuid in uuids {
-            names.insert(uuid, results.remove(&uuid));
+        if let Some(loading_cache) = &self.loading_cache {
+            let mut unknown = Vec::new();
+            for (uuid, name) in &names {
+                if name.is_none() && !self.misses.contains_key(uuid) {
+                    unknown.push(*uuid);
+                }
+            }
+            if !unknown.is_empty() {
+                for (uuid, name) in loading_cache.get_batch(unknown).await {
+                    let Some(name) = name else {
+                        self.misses.insert(uuid, ()).await;
+                        continue;
+                    };
+                    if let Err(e) = self.database.store_player_identity(uuid, &name).await {
+                        warn!("Error storing resolved name for {uuid}: {e:?}");
+                    }
+                    names.insert(uuid, Some(name));
+                }
+            }
         }
 
-        // no one wants this
-        // let api_names = self.loading_cache.get_batch(
-        //     Vec::from_iter(missing.iter().cloned())
-        // ).await;
-        // for (key, value) in api_names {
-        //     if let Some(v) = value {
-        //         names.insert(key, Some(v.clone()));
-        //     }
-        // }
-        names
+        for (uuid, name) in &names {
+            self.names.insert(*uuid, name.clone()).await;
+        }
+        Arc::new(names)
     }
 }
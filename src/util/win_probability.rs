@@ -0,0 +1,363 @@
+use crate::db::database::Database;
+use crate::db::model::match_data::MatchData;
+use crate::db::model::match_event::MatchEvent;
+use crate::db::model::player_match_stats::PlayerMatchStats;
+use log::warn;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use uuid::Uuid;
+
+const BASE_RATING: f64 = 1500.0;
+const RATING_K: f64 = 24.0;
+/// Below this many samples a fit is too noisy to trust, so the starting weights are kept.
+const MIN_SAMPLES: usize = 30;
+const ITERATIONS: usize = 2000;
+const LEARNING_RATE: f64 = 0.1;
+const REGULARIZATION: f64 = 0.001;
+
+/// Team one's chances at one moment of a match, with times in milliseconds.
+#[derive(Clone)]
+pub struct WinPoint {
+    pub time: u64,
+    pub team_one_score: u32,
+    pub team_two_score: u32,
+    pub team_one_probability: f64,
+}
+
+/// How a finished match swung. `excitement` is the total movement in win probability, and
+/// `comeback` grows as the eventual winner's lowest chance falls below a coin flip.
+#[derive(Clone)]
+pub struct MatchSwing {
+    pub pre_match: f64,
+    pub points: Vec<WinPoint>,
+    pub excitement: f64,
+    pub comeback: f64,
+    pub from_events: bool,
+}
+
+pub struct LineupPrediction {
+    pub team_one_probability: f64,
+    pub team_one_rating: f64,
+    pub team_two_rating: f64,
+}
+
+/// Two logistic models fitted over the whole match history. The pre-match one works from the gap
+/// between the lineups' average player ratings, which are updated Elo-style match by match. The
+/// in-game one adds the score differential, weighted more heavily as the clock runs down.
+pub struct WinModel {
+    ratings: HashMap<Uuid, f64>,
+    pre_match: [f64; 2],
+    in_game: [f64; 3],
+    swings: HashMap<u32, MatchSwing>,
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+fn dot<const N: usize>(weights: &[f64; N], features: &[f64; N]) -> f64 {
+    weights.iter().zip(features).map(|(w, x)| w * x).sum()
+}
+
+/// Plain batch gradient descent on log loss. Labels may be fractional so draws count as 0.5.
+fn fit<const N: usize>(samples: &[([f64; N], f64)], mut weights: [f64; N]) -> [f64; N] {
+    if samples.len() < MIN_SAMPLES {
+        return weights;
+    }
+    let n = samples.len() as f64;
+    for _ in 0..ITERATIONS {
+        let mut gradient = [0.0; N];
+        for (features, label) in samples {
+            let error = sigmoid(dot(&weights, features)) - label;
+            for (g, x) in gradient.iter_mut().zip(features) {
+                *g += error * x;
+            }
+        }
+        for (w, g) in weights.iter_mut().zip(gradient) {
+            *w -= LEARNING_RATE * (g / n + REGULARIZATION * *w);
+        }
+    }
+    weights
+}
+
+fn team_one_result(winner: i32) -> f64 {
+    match winner {
+        1 => 1.0,
+        2 => 0.0,
+        _ => 0.5,
+    }
+}
+
+fn average_rating(ratings: &HashMap<Uuid, f64>, players: &[Uuid]) -> f64 {
+    if players.is_empty() {
+        return BASE_RATING;
+    }
+    players
+        .iter()
+        .map(|uuid| ratings.get(uuid).copied().unwrap_or(BASE_RATING))
+        .sum::<f64>()
+        / players.len() as f64
+}
+
+/// Rating difference in Elo units, so that a gap of 1.0 is worth 10:1 odds.
+fn rating_gap(team_one_rating: f64, team_two_rating: f64) -> f64 {
+    (team_one_rating - team_two_rating) / 400.0
+}
+
+fn team_players(players: &HashMap<Uuid, PlayerMatchStats>, team: i32) -> Vec<Uuid> {
+    players
+        .iter()
+        .filter(|(_, stats)| stats.team == team)
+        .map(|(uuid, _)| *uuid)
+        .collect()
+}
+
+fn in_game_features(prior: f64, diff: f64, progress: f64) -> [f64; 3] {
+    [prior, diff, diff * progress]
+}
+
+/// Replays a match's touchdowns into score states, crediting each to the scorer's team.
+fn score_states(
+    touchdowns: &[MatchEvent],
+    players: Option<&HashMap<Uuid, PlayerMatchStats>>,
+) -> Vec<(u64, u32, u32)> {
+    let mut team_one_score = 0;
+    let mut team_two_score = 0;
+    touchdowns
+        .iter()
+        .filter_map(|touchdown| {
+            let team = touchdown
+                .actor
+                .and_then(|uuid| players.and_then(|p| p.get(&uuid)).map(|stats| stats.team))
+                .or(touchdown.team)?;
+            match team {
+                1 => team_one_score += 1,
+                2 => team_two_score += 1,
+                _ => return None,
+            }
+            Some((touchdown.time, team_one_score, team_two_score))
+        })
+        .collect()
+}
+
+fn progress(time: u64, data: &MatchData) -> f64 {
+    if data.duration == 0 {
+        return 1.0;
+    }
+    (time as f64 / (f64::from(data.duration) * 1000.0)).min(1.0)
+}
+
+impl WinModel {
+    /// Loads every match and fits the model on a blocking thread, the fit being far too long
+    /// to run on a runtime worker.
+    pub async fn train(database: &Database) -> Option<Self> {
+        let matches = database.get_matches_all().await?;
+        let match_ids: Vec<u32> = matches.keys().copied().collect();
+        let (stats, touchdowns) = tokio::join!(
+            database.get_player_stats_for_matches(&match_ids),
+            database.get_touchdown_events(),
+        );
+        let stats = stats.unwrap_or_default();
+        let touchdowns = touchdowns.unwrap_or_default();
+        tokio::task::spawn_blocking(move || Self::fit_all(&matches, &stats, &touchdowns))
+            .await
+            .inspect_err(|e| warn!("Error training the win probability model: {e:?}"))
+            .ok()
+    }
+
+    fn fit_all(
+        matches: &HashMap<u32, MatchData>,
+        stats: &HashMap<u32, HashMap<Uuid, PlayerMatchStats>>,
+        touchdowns: &HashMap<u32, Vec<MatchEvent>>,
+    ) -> Self {
+        let mut ordered: Vec<(u32, &MatchData)> =
+            matches.iter().map(|(id, data)| (*id, data)).collect();
+        ordered.sort_by_key(|(id, data)| (data.start_time, *id));
+
+        // Ratings only ever reflect earlier matches, so each gap is a genuine pre-match signal.
+        let mut ratings: HashMap<Uuid, f64> = HashMap::new();
+        let mut gaps: HashMap<u32, f64> = HashMap::new();
+        for (match_id, data) in &ordered {
+            let Some(players) = stats.get(match_id) else {
+                continue;
+            };
+            let team_one = team_players(players, 1);
+            let team_two = team_players(players, 2);
+            let gap = rating_gap(
+                average_rating(&ratings, &team_one),
+                average_rating(&ratings, &team_two),
+            );
+            gaps.insert(*match_id, gap);
+
+            let expected = 1.0 / (1.0 + 10f64.powf(-gap));
+            let delta = RATING_K * (team_one_result(data.winner) - expected);
+            for uuid in team_one {
+                *ratings.entry(uuid).or_insert(BASE_RATING) += delta;
+            }
+            for uuid in team_two {
+                *ratings.entry(uuid).or_insert(BASE_RATING) -= delta;
+            }
+        }
+
+        let pre_match_samples: Vec<([f64; 2], f64)> = ordered
+            .iter()
+            .map(|(match_id, data)| {
+                let gap = gaps.get(match_id).copied().unwrap_or(0.0);
+                ([1.0, gap], team_one_result(data.winner))
+            })
+            .collect();
+        let pre_match = fit(&pre_match_samples, [0.0, std::f64::consts::LN_10]);
+        let prior = |match_id: &u32| {
+            dot(
+                &pre_match,
+                &[1.0, gaps.get(match_id).copied().unwrap_or(0.0)],
+            )
+        };
+
+        let in_game_samples: Vec<([f64; 3], f64)> = ordered
+            .iter()
+            .filter_map(|(match_id, data)| {
+                let states = score_states(touchdowns.get(match_id)?, stats.get(match_id));
+                let prior = prior(match_id);
+                let label = team_one_result(data.winner);
+                Some(states.into_iter().map(move |(time, one, two)| {
+                    let diff = f64::from(one) - f64::from(two);
+                    (in_game_features(prior, diff, progress(time, data)), label)
+                }))
+            })
+            .flatten()
+            .collect();
+        let in_game = fit(&in_game_samples, [1.0, 0.5, 1.5]);
+
+        let mut model = Self {
+            ratings,
+            pre_match,
+            in_game,
+            swings: HashMap::new(),
+        };
+        model.swings = ordered
+            .iter()
+            .map(|(match_id, data)| {
+                let states = touchdowns
+                    .get(match_id)
+                    .map(|events| score_states(events, stats.get(match_id)));
+                (*match_id, model.swing(prior(match_id), data, states))
+            })
+            .collect();
+        model
+    }
+
+    fn swing(
+        &self,
+        prior: f64,
+        data: &MatchData,
+        states: Option<Vec<(u64, u32, u32)>>,
+    ) -> MatchSwing {
+        let pre_match = sigmoid(prior);
+        let from_events = states.is_some();
+        let mut points = vec![WinPoint {
+            time: 0,
+            team_one_score: 0,
+            team_two_score: 0,
+            team_one_probability: pre_match,
+        }];
+        for (time, one, two) in states.unwrap_or_default() {
+            let diff = f64::from(one) - f64::from(two);
+            points.push(WinPoint {
+                time,
+                team_one_score: one,
+                team_two_score: two,
+                team_one_probability: sigmoid(dot(
+                    &self.in_game,
+                    &in_game_features(prior, diff, progress(time, data)),
+                )),
+            });
+        }
+        points.push(WinPoint {
+            time: u64::from(data.duration) * 1000,
+            team_one_score: data.team_one_score,
+            team_two_score: data.team_two_score,
+            team_one_probability: team_one_result(data.winner),
+        });
+
+        let excitement = points
+            .windows(2)
+            .map(|pair| (pair[1].team_one_probability - pair[0].team_one_probability).abs())
+            .sum();
+        let lowest_winner_chance = match data.winner {
+            1 => points
+                .iter()
+                .map(|p| p.team_one_probability)
+                .fold(1.0, f64::min),
+            2 => points
+                .iter()
+                .map(|p| 1.0 - p.team_one_probability)
+                .fold(1.0, f64::min),
+            _ => 0.5,
+        };
+
+        MatchSwing {
+            pre_match,
+            points,
+            excitement,
+            comeback: (1.0 - 2.0 * lowest_winner_chance).max(0.0),
+            from_events,
+        }
+    }
+
+    pub fn predict(&self, team_one: &[Uuid], team_two: &[Uuid]) -> LineupPrediction {
+        let team_one_rating = average_rating(&self.ratings, team_one);
+        let team_two_rating = average_rating(&self.ratings, team_two);
+        LineupPrediction {
+            team_one_probability: sigmoid(dot(
+                &self.pre_match,
+                &[1.0, rating_gap(team_one_rating, team_two_rating)],
+            )),
+            team_one_rating,
+            team_two_rating,
+        }
+    }
+
+    pub fn swing_for(&self, match_id: u32) -> Option<&MatchSwing> {
+        self.swings.get(&match_id)
+    }
+
+    /// A player's current rating, or `None` if they have never played a rated match.
+    pub fn rating(&self, uuid: &Uuid) -> Option<f64> {
+        self.ratings.get(uuid).copied()
+    }
+
+    pub fn swings(&self) -> impl Iterator<Item = (u32, &MatchSwing)> {
+        self.swings.iter().map(|(id, swing)| (*id, swing))
+    }
+}
+
+/// Keeps the most recently trained model around, retraining it in the background so no request
+/// ever waits on training. There is nothing to predict with until the first fit has finished.
+pub struct WinProbability {
+    current: watch::Sender<Option<Arc<WinModel>>>,
+}
+
+impl WinProbability {
+    pub fn spawn(database: Arc<Database>, retrain_interval: Duration) -> Self {
+        let current = watch::Sender::new(None);
+        let task_current = current.clone();
+        tokio::spawn(async move {
+            database.wait_until_ready().await;
+            let mut interval = tokio::time::interval(retrain_interval);
+            loop {
+                interval.tick().await;
+                if let Some(model) = WinModel::train(&database).await {
+                    task_current.send_replace(Some(Arc::new(model)));
+                }
+            }
+        });
+        Self { current }
+    }
+
+    pub fn model(&self) -> Option<Arc<WinModel>> {
+        self.current.borrow().clone()
+    }
+}
@@ -1,5 +1,15 @@
 use crate::db::database::Database;
+use crate::util::distributions::Distributions;
+use crate::util::match_watcher::MatchWatcher;
 use crate::util::username_resolver::UsernameResolver;
+use crate::util::win_probability::WinProbability;
+use crate::web::fairings::RequestMetrics;
+use crate::web::graphql::GhastSchema;
+use crate::web::live::LiveMatches;
+use crate::web::openapi;
+use crate::web::rate_limit::{RateLimiter, RateLimiting};
+use crate::web::request_id::RequestTracing;
+use crate::web::response_cache::ResponseCache;
 use crate::web::routes;
 use rocket::figment::Figment;
 use rocket::http::Method;
@@ -7,40 +17,72 @@ use rocket::{Build, Config, Rocket};
 use rocket_cors::{AllowedOrigins, CorsOptions};
 use std::net::{IpAddr, Ipv4Addr};
 use std::sync::Arc;
-use tokio::sync::Mutex;
 
 pub struct GhastApiState {
     pub database: Arc<Database>,
-    pub username_resolver: Arc<Mutex<UsernameResolver>>,
+    pub username_resolver: Arc<UsernameResolver>,
+    pub admin_token: Option<String>,
+    pub match_watcher: MatchWatcher,
+    pub live_matches: LiveMatches,
+    pub win_probability: WinProbability,
+    pub distributions: Distributions,
+    pub response_cache: ResponseCache,
+    pub rate_limiter: RateLimiter,
+    pub graphql: GhastSchema,
 }
 
 fn get_cors_configuration() -> CorsOptions {
     CorsOptions::default()
         .allowed_origins(AllowedOrigins::all())
         .allowed_methods(
-            vec![Method::Get, Method::Post]
+            vec![Method::Get, Method::Post, Method::Put, Method::Delete]
                 .into_iter()
                 .map(From::from)
                 .collect(),
         )
-        .allow_credentials(true)
+        // keys travel in the Authorization header, so there are no cookies to share cross-origin
+        .allow_credentials(false)
 }
 
 pub fn rocket(state: GhastApiState) -> Rocket<Build> {
     let is_debug = false;
     let http_port = 8000;
-    let config: Config = Figment::from(if is_debug {
+    let figment = Figment::from(if is_debug {
         Config::debug_default()
     } else {
         Config::release_default()
     })
     .merge::<(&str, IpAddr)>(("address", Ipv4Addr::UNSPECIFIED.into()))
-    .merge(("port", http_port))
-    .extract()
-    .unwrap();
+    .merge(("port", http_port));
+    // Rocket trusts X-Real-IP by default, which any client can set when nothing sits in front of
+    // us to overwrite it. Only a header named for a trusted proxy is believed.
+    let figment = match std::env::var("GHAST_TRUSTED_IP_HEADER") {
+        Ok(header) => figment.merge(("ip_header", header)),
+        Err(_) => figment.merge(("ip_header", false)),
+    };
+    let config: Config = figment.extract().unwrap();
     let cors = get_cors_configuration().to_cors().unwrap();
-    let mut build = rocket::custom(config).attach(cors).manage(state);
-    build = routes::r#match::mount(build);
-    build = routes::tournament::mount(build);
+    let mut build = rocket::custom(config)
+        .attach(cors)
+        .attach(RequestTracing)
+        .attach(RequestMetrics)
+        .attach(RateLimiting)
+        .manage(state);
+    let mut spec = openapi::spec();
+    build = routes::r#match::mount(build, &mut spec);
+    build = routes::events::mount(build, &mut spec);
+    build = routes::tournament::mount(build, &mut spec);
+    build = routes::admin::mount(build, &mut spec);
+    build = routes::live::mount(build, &mut spec);
+    build = routes::violations::mount(build, &mut spec);
+    build = routes::pass_network::mount(build, &mut spec);
+    build = routes::win_probability::mount(build, &mut spec);
+    build = routes::players::mount(build, &mut spec);
+    build = routes::stats::mount(build, &mut spec);
+    build = routes::metrics::mount(build, &mut spec);
+    build = routes::health::mount(build, &mut spec);
+    build = routes::graphql::mount(build);
+    build = routes::rate_limit::mount(build);
+    build = openapi::mount_docs(build, spec);
     build
 }
@@ -0,0 +1,38 @@
+use crate::util::metrics::METRICS;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Data, Request, Response, async_trait};
+use std::time::Instant;
+
+/// Counts and times every request under its route template, so `/matches/66` and `/matches/67`
+/// share a series. Requests no route matched are grouped together.
+pub struct RequestMetrics;
+
+#[async_trait]
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request metrics",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        request.local_cache(Instant::now);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let started = request.local_cache(Instant::now);
+        let route = request
+            .route()
+            .map_or_else(|| String::from("unmatched"), |route| route.uri.to_string());
+        let method = request.method().as_str();
+        METRICS
+            .http_requests
+            .with_label_values(&[method, &route, response.status().code.to_string().as_str()])
+            .inc();
+        METRICS
+            .http_duration
+            .with_label_values(&[method, &route])
+            .observe(started.elapsed().as_secs_f64());
+    }
+}
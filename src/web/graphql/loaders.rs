@@ -0,0 +1,140 @@
+use crate::db::database::Database;
+use crate::db::model::match_data::PlayerlessMatchData;
+use crate::db::model::player_match_stats::PlayerMatchStats;
+use crate::db::model::tournament::{TournamentMatchMapping, TournamentTeam, TournamentTeamPlayer};
+use crate::util::username_resolver::UsernameResolver;
+use crate::web::graphql::MAX_PAGE_SIZE;
+use async_graphql::Error;
+use async_graphql::dataloader::Loader;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+// Every loader batches the keys its resolvers ask for within a tick into one query. None of them
+// cache, so the schema can share them across requests without serving stale stats. The database
+// has already logged the cause whenever one of them fails.
+
+pub struct MatchLoader(pub Arc<Database>);
+
+impl Loader<u32> for MatchLoader {
+    type Value = PlayerlessMatchData;
+    type Error = Error;
+
+    async fn load(&self, ids: &[u32]) -> Result<HashMap<u32, Self::Value>, Self::Error> {
+        self.0
+            .get_matches_by_ids(ids)
+            .await
+            .ok_or_else(|| Error::new("Error loading matches"))
+    }
+}
+
+/// Stat lines by match id.
+pub struct MatchStatsLoader(pub Arc<Database>);
+
+impl Loader<u32> for MatchStatsLoader {
+    type Value = HashMap<Uuid, PlayerMatchStats>;
+    type Error = Error;
+
+    async fn load(&self, ids: &[u32]) -> Result<HashMap<u32, Self::Value>, Self::Error> {
+        self.0
+            .get_player_stats_for_matches(ids)
+            .await
+            .ok_or_else(|| Error::new("Error loading match stats"))
+    }
+}
+
+/// Each player's latest lines, as many as `Player.matches` can hand back, rather than their whole
+/// history.
+pub struct PlayerHistoryLoader(pub Arc<Database>);
+
+impl Loader<Uuid> for PlayerHistoryLoader {
+    type Value = Vec<(u32, PlayerMatchStats)>;
+    type Error = Error;
+
+    async fn load(&self, uuids: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
+        self.0
+            .get_player_match_history(uuids, Some(MAX_PAGE_SIZE as u32))
+            .await
+            .ok_or_else(|| Error::new("Error loading player history"))
+    }
+}
+
+/// Goes through the resolver like every other route, so GraphQL shares its name cache.
+pub struct UsernameLoader(pub Arc<UsernameResolver>);
+
+impl Loader<Uuid> for UsernameLoader {
+    type Value = String;
+    type Error = Error;
+
+    async fn load(&self, uuids: &[Uuid]) -> Result<HashMap<Uuid, Self::Value>, Self::Error> {
+        Ok(self
+            .0
+            .resolve_batch(uuids.to_vec())
+            .await
+            .into_iter()
+            .map(|(uuid, name)| (uuid, name.unwrap_or_else(|| String::from("Unknown"))))
+            .collect())
+    }
+}
+
+/// Groups rows of a per-tournament table by their tournament, so tournaments without any rows
+/// still load as empty.
+fn by_tournament<T>(
+    ids: &[u32],
+    rows: Vec<T>,
+    tournament_id: impl Fn(&T) -> u32,
+) -> HashMap<u32, Vec<T>> {
+    let mut grouped: HashMap<u32, Vec<T>> = ids.iter().map(|id| (*id, Vec::new())).collect();
+    for row in rows {
+        grouped.entry(tournament_id(&row)).or_default().push(row);
+    }
+    grouped
+}
+
+pub struct TournamentTeamsLoader(pub Arc<Database>);
+
+impl Loader<u32> for TournamentTeamsLoader {
+    type Value = Vec<TournamentTeam>;
+    type Error = Error;
+
+    async fn load(&self, ids: &[u32]) -> Result<HashMap<u32, Self::Value>, Self::Error> {
+        let teams = self
+            .0
+            .get_tournament_teams(ids)
+            .await
+            .ok_or_else(|| Error::new("Error loading tournament teams"))?;
+        Ok(by_tournament(ids, teams, |team| team.tournament_id))
+    }
+}
+
+pub struct TournamentPlayersLoader(pub Arc<Database>);
+
+impl Loader<u32> for TournamentPlayersLoader {
+    type Value = Vec<TournamentTeamPlayer>;
+    type Error = Error;
+
+    async fn load(&self, ids: &[u32]) -> Result<HashMap<u32, Self::Value>, Self::Error> {
+        let players = self
+            .0
+            .get_tournament_team_players(ids)
+            .await
+            .ok_or_else(|| Error::new("Error loading tournament players"))?;
+        Ok(by_tournament(ids, players, |player| player.tournament_id))
+    }
+}
+
+pub struct TournamentMatchesLoader(pub Arc<Database>);
+
+impl Loader<u32> for TournamentMatchesLoader {
+    type Value = Vec<TournamentMatchMapping>;
+    type Error = Error;
+
+    async fn load(&self, ids: &[u32]) -> Result<HashMap<u32, Self::Value>, Self::Error> {
+        let matches = self
+            .0
+            .get_tournament_matches(ids)
+            .await
+            .ok_or_else(|| Error::new("Error loading tournament matches"))?;
+        Ok(by_tournament(ids, matches, |m| m.tournament_id))
+    }
+}
@@ -0,0 +1,124 @@
+mod loaders;
+mod types;
+
+use crate::db::database::Database;
+use crate::util::username_resolver::UsernameResolver;
+use crate::web::graphql::loaders::{
+    MatchLoader, MatchStatsLoader, PlayerHistoryLoader, TournamentMatchesLoader,
+    TournamentPlayersLoader, TournamentTeamsLoader, UsernameLoader,
+};
+use crate::web::graphql::types::{Match, Player, Tournament};
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{Context, EmptyMutation, EmptySubscription, Error, Object, Result, Schema};
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub type GhastSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// The most a single list field hands back, whatever `first` asks for.
+const MAX_PAGE_SIZE: usize = 100;
+
+// Lists that aren't paginated are weighted by roughly how long they get, so nesting them still
+// counts towards the complexity limit.
+const TOURNAMENTS_ESTIMATE: usize = 20;
+const TOURNAMENT_MATCHES_ESTIMATE: usize = 16;
+const TOURNAMENT_TEAMS_ESTIMATE: usize = 8;
+const TEAM_PLAYERS_ESTIMATE: usize = 6;
+const MATCH_PLAYERS_ESTIMATE: usize = 12;
+/// Six awards plus the all-tournament team.
+const AWARDS_ESTIMATE: usize = 11;
+/// Awards rescore the whole tournament, however few fields are asked of them.
+const AWARDS_SCORING_COST: usize = 50;
+
+fn loader<'a, T: Send + Sync + 'static>(ctx: &Context<'a>) -> &'a DataLoader<T> {
+    ctx.data_unchecked::<DataLoader<T>>()
+}
+
+fn database<'a>(ctx: &Context<'a>) -> &'a Database {
+    ctx.data_unchecked::<Arc<Database>>()
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    #[graphql(name = "match")]
+    async fn match_by_id(&self, ctx: &Context<'_>, id: u32) -> Result<Option<Match>> {
+        let data = loader::<MatchLoader>(ctx).load_one(id).await?;
+        Ok(data.map(|data| Match { id, data }))
+    }
+
+    /// The latest matches, newest first. Pass the last id of a page as `before` for the next.
+    #[graphql(complexity = "first.min(MAX_PAGE_SIZE).saturating_mul(child_complexity)")]
+    async fn matches(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 20)] first: usize,
+        before: Option<u32>,
+    ) -> Result<Vec<Match>> {
+        let ids = database(ctx)
+            .get_recent_match_ids(first.min(MAX_PAGE_SIZE) as u32, before)
+            .await
+            .ok_or_else(|| Error::new("Error loading matches"))?;
+        let mut matches = loader::<MatchLoader>(ctx).load_many(ids.clone()).await?;
+        Ok(ids
+            .into_iter()
+            .filter_map(|id| matches.remove(&id).map(|data| Match { id, data }))
+            .collect())
+    }
+
+    async fn player(&self, uuid: Uuid) -> Player {
+        Player { uuid }
+    }
+
+    async fn tournament(&self, ctx: &Context<'_>, id: u32) -> Option<Tournament> {
+        database(ctx).get_tournament_by_id(id).await.map(From::from)
+    }
+
+    /// Newest first.
+    #[graphql(complexity = "TOURNAMENTS_ESTIMATE * child_complexity")]
+    async fn tournaments(&self, ctx: &Context<'_>) -> Result<Vec<Tournament>> {
+        let tournaments = database(ctx)
+            .get_tournaments_all()
+            .await
+            .ok_or_else(|| Error::new("Error loading tournaments"))?;
+        Ok(tournaments.into_iter().map(From::from).collect())
+    }
+}
+
+/// Builds the schema once for the whole API. Its loaders only batch, so they are shared by every
+/// request.
+pub fn schema(database: Arc<Database>, username_resolver: Arc<UsernameResolver>) -> GhastSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .limit_depth(10)
+        // list fields count once per item they can return, or are expected to, so this bounds rows
+        // rather than fields
+        .limit_complexity(500)
+        .data(DataLoader::new(MatchLoader(database.clone()), tokio::spawn))
+        .data(DataLoader::new(
+            MatchStatsLoader(database.clone()),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            PlayerHistoryLoader(database.clone()),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            TournamentTeamsLoader(database.clone()),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            TournamentPlayersLoader(database.clone()),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            TournamentMatchesLoader(database.clone()),
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            UsernameLoader(username_resolver),
+            tokio::spawn,
+        ))
+        .data(database)
+        .finish()
+}
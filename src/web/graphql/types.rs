@@ -0,0 +1,278 @@
+use crate::db::model::match_data::PlayerlessMatchData;
+use crate::db::model::player_match_stats;
+use crate::db::model::tournament::{TournamentBase, TournamentWithCounts};
+use crate::web::graphql::loaders::{
+    MatchLoader, MatchStatsLoader, PlayerHistoryLoader, TournamentMatchesLoader,
+    TournamentPlayersLoader, TournamentTeamsLoader, UsernameLoader,
+};
+use crate::web::graphql::{
+    AWARDS_ESTIMATE, AWARDS_SCORING_COST, MATCH_PLAYERS_ESTIMATE, MAX_PAGE_SIZE,
+    TEAM_PLAYERS_ESTIMATE, TOURNAMENT_MATCHES_ESTIMATE, TOURNAMENT_TEAMS_ESTIMATE, loader,
+};
+use crate::web::routes::tournament::{determine_awards, score_players};
+use async_graphql::{ComplexObject, Context, Enum, Result, SimpleObject};
+use std::cmp::Reverse;
+use uuid::Uuid;
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Match {
+    pub id: u32,
+    #[graphql(flatten)]
+    pub data: PlayerlessMatchData,
+}
+
+#[ComplexObject]
+impl Match {
+    /// Every player's stat line, grouped by team.
+    #[graphql(complexity = "MATCH_PLAYERS_ESTIMATE * child_complexity")]
+    async fn players(&self, ctx: &Context<'_>) -> Result<Vec<PlayerMatchStats>> {
+        let stats = loader::<MatchStatsLoader>(ctx)
+            .load_one(self.id)
+            .await?
+            .unwrap_or_default();
+        let mut players: Vec<PlayerMatchStats> = stats
+            .into_iter()
+            .map(|(uuid, stats)| PlayerMatchStats {
+                match_id: self.id,
+                uuid,
+                stats,
+            })
+            .collect();
+        players.sort_by_key(|p| (p.stats.team, p.uuid));
+        Ok(players)
+    }
+}
+
+/// One player's line in one match.
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct PlayerMatchStats {
+    #[graphql(skip)]
+    pub match_id: u32,
+    #[graphql(skip)]
+    pub uuid: Uuid,
+    #[graphql(flatten)]
+    pub stats: player_match_stats::PlayerMatchStats,
+}
+
+#[ComplexObject]
+impl PlayerMatchStats {
+    async fn player(&self) -> Player {
+        Player { uuid: self.uuid }
+    }
+
+    #[graphql(name = "match")]
+    async fn match_played(&self, ctx: &Context<'_>) -> Result<Option<Match>> {
+        let data = loader::<MatchLoader>(ctx).load_one(self.match_id).await?;
+        Ok(data.map(|data| Match {
+            id: self.match_id,
+            data,
+        }))
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Player {
+    pub uuid: Uuid,
+}
+
+#[ComplexObject]
+impl Player {
+    async fn username(&self, ctx: &Context<'_>) -> Result<String> {
+        let username = loader::<UsernameLoader>(ctx).load_one(self.uuid).await?;
+        Ok(username.unwrap_or_else(|| String::from("Unknown")))
+    }
+
+    /// The player's most recent stat lines, newest first.
+    #[graphql(complexity = "first.min(MAX_PAGE_SIZE).saturating_mul(child_complexity)")]
+    async fn matches(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 20)] first: usize,
+    ) -> Result<Vec<PlayerMatchStats>> {
+        let mut history = loader::<PlayerHistoryLoader>(ctx)
+            .load_one(self.uuid)
+            .await?
+            .unwrap_or_default();
+        history.sort_unstable_by_key(|(match_id, _)| Reverse(*match_id));
+        Ok(history
+            .into_iter()
+            .take(first.min(MAX_PAGE_SIZE))
+            .map(|(match_id, stats)| PlayerMatchStats {
+                match_id,
+                uuid: self.uuid,
+                stats,
+            })
+            .collect())
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Tournament {
+    pub id: u32,
+    pub name: String,
+    pub date: u64,
+    pub winner_team_id: i32,
+}
+
+impl From<TournamentBase> for Tournament {
+    fn from(tournament: TournamentBase) -> Self {
+        Self {
+            id: tournament.id,
+            name: tournament.name,
+            date: tournament.date,
+            winner_team_id: tournament.winner_team_id,
+        }
+    }
+}
+
+impl From<TournamentWithCounts> for Tournament {
+    fn from(tournament: TournamentWithCounts) -> Self {
+        Self {
+            id: tournament.id,
+            name: tournament.name,
+            date: tournament.date,
+            winner_team_id: tournament.winner_team_id,
+        }
+    }
+}
+
+#[ComplexObject]
+impl Tournament {
+    #[graphql(complexity = "TOURNAMENT_TEAMS_ESTIMATE * child_complexity")]
+    async fn teams(&self, ctx: &Context<'_>) -> Result<Vec<Team>> {
+        let teams = loader::<TournamentTeamsLoader>(ctx)
+            .load_one(self.id)
+            .await?
+            .unwrap_or_default();
+        Ok(teams
+            .into_iter()
+            .map(|team| Team {
+                id: team.team_id,
+                tournament_id: self.id,
+                captain_uuid: team.captain_uuid,
+            })
+            .collect())
+    }
+
+    /// Matches in the order they were played.
+    #[graphql(complexity = "TOURNAMENT_MATCHES_ESTIMATE * child_complexity")]
+    async fn matches(&self, ctx: &Context<'_>) -> Result<Vec<Match>> {
+        let ids: Vec<u32> = loader::<TournamentMatchesLoader>(ctx)
+            .load_one(self.id)
+            .await?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|m| m.match_id)
+            .collect();
+        let mut matches = loader::<MatchLoader>(ctx).load_many(ids.clone()).await?;
+        Ok(ids
+            .into_iter()
+            .filter_map(|id| matches.remove(&id).map(|data| Match { id, data }))
+            .collect())
+    }
+
+    /// Computed from the tournament's stats the same way as on `/tournaments/<id>`.
+    #[graphql(complexity = "AWARDS_SCORING_COST + AWARDS_ESTIMATE * child_complexity")]
+    async fn awards(&self, ctx: &Context<'_>) -> Result<Vec<Award>> {
+        let (matches, players) = tokio::try_join!(
+            loader::<TournamentMatchesLoader>(ctx).load_one(self.id),
+            loader::<TournamentPlayersLoader>(ctx).load_one(self.id),
+        )?;
+        let (matches, players) = (matches.unwrap_or_default(), players.unwrap_or_default());
+        let stats_by_match = loader::<MatchStatsLoader>(ctx)
+            .load_many(matches.iter().map(|m| m.match_id))
+            .await?;
+        let (_, indexes) = score_players(&matches, &players, &stats_by_match);
+        let winners = determine_awards(&indexes);
+
+        let awards = [
+            (AwardKind::Mvp, winners.mvp),
+            (AwardKind::Opot, winners.opot),
+            (AwardKind::Dpot, winners.dpot),
+            (AwardKind::Oldl, winners.oldl),
+            (AwardKind::Passer, winners.passer),
+            (AwardKind::Receiver, winners.receiver),
+        ]
+        .into_iter()
+        .filter_map(|(kind, uuid)| uuid.map(|uuid| Award { kind, uuid }))
+        .chain(winners.all_tournament.into_iter().map(|uuid| Award {
+            kind: AwardKind::AllTournament,
+            uuid,
+        }))
+        .collect();
+        Ok(awards)
+    }
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Team {
+    pub id: i32,
+    #[graphql(skip)]
+    pub tournament_id: u32,
+    #[graphql(skip)]
+    pub captain_uuid: Uuid,
+}
+
+#[ComplexObject]
+impl Team {
+    /// Named after the captain, as on the site.
+    async fn name(&self, ctx: &Context<'_>) -> Result<String> {
+        let captain = loader::<UsernameLoader>(ctx)
+            .load_one(self.captain_uuid)
+            .await?;
+        Ok(format!("Team {}", captain.as_deref().unwrap_or("Unknown")))
+    }
+
+    async fn captain(&self) -> Player {
+        Player {
+            uuid: self.captain_uuid,
+        }
+    }
+
+    #[graphql(complexity = "TEAM_PLAYERS_ESTIMATE * child_complexity")]
+    async fn players(&self, ctx: &Context<'_>) -> Result<Vec<Player>> {
+        let players = loader::<TournamentPlayersLoader>(ctx)
+            .load_one(self.tournament_id)
+            .await?
+            .unwrap_or_default();
+        Ok(players
+            .into_iter()
+            .filter(|p| p.team_id == self.id)
+            .map(|p| Player {
+                uuid: p.player_uuid,
+            })
+            .collect())
+    }
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+pub enum AwardKind {
+    Mvp,
+    Opot,
+    Dpot,
+    Oldl,
+    Passer,
+    Receiver,
+    /// One of the five best players by total index, whatever else they won.
+    AllTournament,
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct Award {
+    pub kind: AwardKind,
+    #[graphql(skip)]
+    pub uuid: Uuid,
+}
+
+#[ComplexObject]
+impl Award {
+    async fn player(&self) -> Player {
+        Player { uuid: self.uuid }
+    }
+}
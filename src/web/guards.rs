@@ -0,0 +1,174 @@
+use crate::db::model::api_key::ApiKeyUsage;
+use crate::util::api_keys::{Scope, hash_key, token_matches};
+use crate::web::api::GhastApiState;
+use crate::web::request_id::RequestId;
+use chrono::Utc;
+use log::warn;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Request, async_trait};
+use rocket_okapi::r#gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::{
+    Object, RefOr, Response, Responses, SecurityRequirement, SecurityScheme, SecuritySchemeData,
+};
+use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
+use std::marker::PhantomData;
+
+pub trait RequiredScope: Send + Sync + 'static {
+    const SCOPE: Scope;
+    /// Whether admitted requests are written to the audit log. Refused ones always are.
+    const AUDITED: bool = true;
+}
+
+pub struct IngestScope;
+pub struct TournamentAdminScope;
+pub struct ReadPrivateScope;
+pub struct AdminScope;
+/// `ReadPrivateScope` for `/metrics`, which is scraped every few seconds and would otherwise
+/// bury the rest of the audit log.
+pub struct MetricsScope;
+
+impl RequiredScope for IngestScope {
+    const SCOPE: Scope = Scope::Ingest;
+}
+
+impl RequiredScope for TournamentAdminScope {
+    const SCOPE: Scope = Scope::TournamentAdmin;
+}
+
+impl RequiredScope for ReadPrivateScope {
+    const SCOPE: Scope = Scope::ReadPrivate;
+}
+
+impl RequiredScope for AdminScope {
+    const SCOPE: Scope = Scope::Admin;
+}
+
+impl RequiredScope for MetricsScope {
+    const SCOPE: Scope = Scope::ReadPrivate;
+    const AUDITED: bool = false;
+}
+
+/// Admits requests carrying `Authorization: Bearer <key>` for an unrevoked key holding `S`'s
+/// scope, recording every attempt in the key's audit log unless `S` opts out. `GHAST_ADMIN_TOKEN`,
+/// when configured, is accepted as a root key with every scope so the first real keys can be
+/// minted with it, and its use is logged without a key id.
+pub struct ApiKey<S: RequiredScope> {
+    /// `None` when authenticated by `GHAST_ADMIN_TOKEN`.
+    pub key_id: Option<i32>,
+    scope: PhantomData<S>,
+}
+
+#[async_trait]
+impl<'r, S: RequiredScope> FromRequest<'r> for ApiKey<S> {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(state) = request.rocket().state::<GhastApiState>() else {
+            return Outcome::Error((Status::InternalServerError, ()));
+        };
+        let Some(token) = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "))
+        else {
+            return Outcome::Error((Status::Unauthorized, ()));
+        };
+
+        let root = state
+            .admin_token
+            .as_deref()
+            .is_some_and(|admin_token| token_matches(admin_token, token));
+        let key = if root {
+            None
+        } else {
+            match state.database.get_active_api_key(&hash_key(token)).await {
+                Ok(Some(key)) => Some(key),
+                Ok(None) => return Outcome::Error((Status::Unauthorized, ())),
+                Err(e) => {
+                    warn!("Error looking up api key: {e:?}");
+                    return Outcome::Error((Status::ServiceUnavailable, ()));
+                }
+            }
+        };
+        let key_id = key.as_ref().map(|key| key.id);
+        let allowed = key
+            .as_ref()
+            .is_none_or(|key| S::SCOPE.granted_by(&key.scopes));
+        if S::AUDITED || !allowed {
+            let usage = ApiKeyUsage {
+                key_id,
+                time: Utc::now().timestamp_millis() as u64,
+                method: request.method().to_string(),
+                uri: request.uri().to_string(),
+                scope: String::from(S::SCOPE.as_str()),
+                allowed,
+                request_id: Some(RequestId::of(request).0.clone()),
+            };
+            if let Err(e) = state.database.record_api_key_usage(&usage).await {
+                match key_id {
+                    Some(id) => warn!("Error recording usage of api key {id}: {e:?}"),
+                    None => warn!("Error recording usage of the root token: {e:?}"),
+                }
+            }
+        }
+
+        if allowed {
+            Outcome::Success(Self {
+                key_id,
+                scope: PhantomData,
+            })
+        } else {
+            Outcome::Error((Status::Forbidden, ()))
+        }
+    }
+}
+
+impl<S: RequiredScope> OpenApiFromRequest<'_> for ApiKey<S> {
+    fn from_request_input(
+        _: &mut OpenApiGenerator,
+        _: String,
+        _: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        let scheme = SecurityScheme {
+            description: Some(String::from(
+                "An API key, or `GHAST_ADMIN_TOKEN` which holds every scope.",
+            )),
+            data: SecuritySchemeData::Http {
+                scheme: String::from("bearer"),
+                bearer_format: None,
+            },
+            extensions: Object::default(),
+        };
+        let mut requirement = SecurityRequirement::new();
+        requirement.insert(String::from("ApiKey"), Vec::new());
+        Ok(RequestHeaderInput::Security(
+            String::from("ApiKey"),
+            scheme,
+            requirement,
+        ))
+    }
+
+    fn get_responses(_: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let mut responses = Responses::default();
+        for (status, description) in [
+            (
+                "401",
+                String::from("No key was given, or it is unknown or revoked."),
+            ),
+            (
+                "403",
+                format!("The key doesn't hold the `{}` scope.", S::SCOPE.as_str()),
+            ),
+        ] {
+            responses.responses.insert(
+                String::from(status),
+                RefOr::Object(Response {
+                    description,
+                    ..Default::default()
+                }),
+            );
+        }
+        Ok(responses)
+    }
+}
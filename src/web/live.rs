@@ -0,0 +1,195 @@
+use crate::db::database::Database;
+use crate::db::model::match_data::MatchData;
+use crate::db::model::player_match_stats::PlayerMatchStats;
+use crate::util::username_resolver::UsernameResolver;
+use crate::web::types::{LiveMatchEnded, LiveMatchState, LiveStatMismatch, PlayerData};
+use log::warn;
+use moka::future::Cache;
+use moka::ops::compute;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+#[derive(Clone)]
+pub enum LiveMatchEvent {
+    Update(LiveMatchState),
+    Ended(LiveMatchEnded),
+}
+
+type StatValue = fn(&PlayerMatchStats) -> u32;
+
+/// The counting stats a live snapshot should agree with the recorded match on.
+const LIVE_STATS: [(&str, StatValue); 12] = [
+    ("kills", |stats| stats.kills),
+    ("deaths", |stats| stats.deaths),
+    ("assists", |stats| stats.assists),
+    ("pickups", |stats| stats.pickups),
+    ("throws", |stats| stats.throws),
+    ("passes", |stats| stats.passes),
+    ("catches", |stats| stats.catches),
+    ("strips", |stats| stats.strips),
+    ("touchdowns", |stats| stats.touchdowns),
+    ("touchdown_passes", |stats| stats.touchdown_passes),
+    ("defensive_interceptions", |stats| {
+        stats.defensive_interceptions
+    }),
+    ("pass_interceptions", |stats| stats.pass_interceptions),
+];
+
+/// In-progress matches keyed by server. A server that stops reporting falls out after the TTL.
+/// When a server's finished match shows up in the database, and the server's state is still that
+/// of the same match, the state is checked against the recorded match and dropped, and the
+/// `Ended` event carries whatever didn't line up. A match that shows up late, once the server has
+/// moved on to its next one, leaves the newer state alone.
+pub struct LiveMatches {
+    cache: Cache<String, LiveMatchState>,
+    sender: broadcast::Sender<LiveMatchEvent>,
+}
+
+impl LiveMatches {
+    pub fn spawn(
+        ttl: Duration,
+        database: Arc<Database>,
+        username_resolver: Arc<UsernameResolver>,
+        mut finished: broadcast::Receiver<(u32, MatchData)>,
+    ) -> Self {
+        let cache: Cache<String, LiveMatchState> =
+            Cache::builder().time_to_live(ttl).max_capacity(64).build();
+        let (sender, _) = broadcast::channel(256);
+
+        let task_cache = cache.clone();
+        let task_sender = sender.clone();
+        tokio::spawn(async move {
+            loop {
+                let (match_id, data) = match finished.recv().await {
+                    Ok(message) => message,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let ended = task_cache
+                    .entry_by_ref(&data.server)
+                    .and_compute_with(|entry| {
+                        let belongs = entry.is_some_and(|entry| {
+                            let live = entry.value();
+                            live.start_time == data.start_time && live.map == data.map
+                        });
+                        async move {
+                            if belongs {
+                                compute::Op::Remove
+                            } else {
+                                compute::Op::Nop
+                            }
+                        }
+                    })
+                    .await;
+                let compute::CompResult::Removed(entry) = ended else {
+                    continue;
+                };
+                let live = entry.into_value();
+                let recorded = database
+                    .get_player_match_stats(match_id)
+                    .await
+                    .unwrap_or_default();
+                let mismatches = reconcile_live(&live, &data, &recorded, &username_resolver).await;
+                if !mismatches.is_empty() {
+                    warn!(
+                        "Live state from {} disagrees with match {match_id} on {} stats",
+                        data.server,
+                        mismatches.len()
+                    );
+                }
+                let _ = task_sender.send(LiveMatchEvent::Ended(LiveMatchEnded {
+                    server: data.server,
+                    match_id,
+                    mismatches,
+                }));
+            }
+        });
+
+        Self { cache, sender }
+    }
+
+    pub async fn update(&self, state: LiveMatchState) {
+        self.cache.insert(state.server.clone(), state.clone()).await;
+        let _ = self.sender.send(LiveMatchEvent::Update(state));
+    }
+
+    pub async fn get(&self, server: &str) -> Option<LiveMatchState> {
+        self.cache.get(server).await
+    }
+
+    pub fn all(&self) -> Vec<LiveMatchState> {
+        let mut states: Vec<_> = self.cache.iter().map(|(_, state)| state).collect();
+        states.sort_unstable_by(|a, b| a.server.cmp(&b.server));
+        states
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<LiveMatchEvent> {
+        self.sender.subscribe()
+    }
+}
+
+/// Compares the team scores and every player's counting stats. A player missing from one side
+/// counts as zero there, so they show up against each stat they have on the other.
+async fn reconcile_live(
+    live: &LiveMatchState,
+    data: &MatchData,
+    recorded: &HashMap<Uuid, PlayerMatchStats>,
+    username_resolver: &UsernameResolver,
+) -> Vec<LiveStatMismatch> {
+    let mut mismatches: Vec<LiveStatMismatch> = [
+        ("team_one_score", live.team_one_score, data.team_one_score),
+        ("team_two_score", live.team_two_score, data.team_two_score),
+    ]
+    .into_iter()
+    .filter(|(_, live, recorded)| live != recorded)
+    .map(|(stat, live, recorded)| LiveStatMismatch {
+        player: None,
+        stat: String::from(stat),
+        live,
+        recorded,
+    })
+    .collect();
+
+    let live_stats: HashMap<Uuid, &PlayerMatchStats> = live
+        .players
+        .iter()
+        .map(|player| (player.uuid, &player.stats))
+        .collect();
+    let players: BTreeSet<Uuid> = live_stats.keys().chain(recorded.keys()).copied().collect();
+    let unnamed: Vec<Uuid> = players
+        .iter()
+        .filter(|uuid| !live_stats.contains_key(uuid))
+        .copied()
+        .collect();
+    let username_map = username_resolver.resolve_batch(unnamed).await;
+    let username = |uuid: Uuid| {
+        live.players
+            .iter()
+            .find(|player| player.uuid == uuid)
+            .map(|player| player.username.clone())
+            .or_else(|| username_map.get(&uuid).cloned().flatten())
+            .unwrap_or_else(|| String::from("Unknown"))
+    };
+
+    for uuid in players {
+        for (stat, value) in LIVE_STATS {
+            let live = live_stats.get(&uuid).map_or(0, |stats| value(stats));
+            let recorded = recorded.get(&uuid).map_or(0, value);
+            if live != recorded {
+                mismatches.push(LiveStatMismatch {
+                    player: Some(PlayerData {
+                        uuid,
+                        username: username(uuid),
+                    }),
+                    stat: String::from(stat),
+                    live,
+                    recorded,
+                });
+            }
+        }
+    }
+    mismatches
+}
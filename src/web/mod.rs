@@ -1,3 +1,14 @@
 pub mod api;
-mod routes;
+mod fairings;
+pub mod graphql;
+mod guards;
+pub mod live;
+mod openapi;
+pub mod rate_limit;
+mod request_id;
+mod responders;
+pub mod response_cache;
+pub mod routes;
 pub mod types;
+pub mod types_v0;
+mod versions;
@@ -0,0 +1,52 @@
+use crate::web::request_id::traced;
+use log::warn;
+use rocket::{Build, Rocket, Route};
+use rocket_okapi::get_openapi_route;
+use rocket_okapi::okapi::merge::merge_specs;
+use rocket_okapi::okapi::openapi3::{Info, OpenApi};
+use rocket_okapi::settings::OpenApiSettings;
+use rocket_okapi::swagger_ui::{SwaggerUIConfig, make_swagger_ui};
+
+/// The document every route module's docs are merged into.
+pub fn spec() -> OpenApi {
+    OpenApi {
+        openapi: OpenApi::default_version(),
+        info: Info {
+            title: String::from("Ghast"),
+            description: Some(String::from(
+                "Match, player and tournament stats. Routes that change data take an API key as \
+                 a bearer token. Every route under `/api/v1` is also served without the prefix as \
+                 the deprecated v0, whose tournament responses are camelCase.",
+            )),
+            version: String::from(env!("CARGO_PKG_VERSION")),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+/// Mounts routes made by `openapi_get_routes_spec!` at `base`, adding their docs to `spec` under
+/// the same base.
+pub fn mount_documented(
+    rocket_build: Rocket<Build>,
+    spec: &mut OpenApi,
+    base: &str,
+    (routes, docs): (Vec<Route>, OpenApi),
+) -> Rocket<Build> {
+    if let Err(e) = merge_specs(spec, &base, &docs) {
+        warn!("Error merging OpenAPI docs for {base}: {e}");
+    }
+    rocket_build.mount(base, traced(routes))
+}
+
+/// Serves the finished document at `/openapi.json`, and a Swagger UI for it under `/docs`.
+pub fn mount_docs(rocket_build: Rocket<Build>, spec: OpenApi) -> Rocket<Build> {
+    let settings = OpenApiSettings::default();
+    let swagger_ui = SwaggerUIConfig {
+        url: settings.json_path.clone(),
+        ..Default::default()
+    };
+    rocket_build
+        .mount("/", traced(vec![get_openapi_route(spec, &settings)]))
+        .mount("/docs", traced(make_swagger_ui(&swagger_ui).into()))
+}
@@ -0,0 +1,253 @@
+use crate::util::api_keys::{hash_key, token_matches};
+use crate::util::metrics::METRICS;
+use crate::web::api::GhastApiState;
+use crate::web::versions::unversioned;
+use log::warn;
+use moka::future::Cache;
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::http::{Method, Status};
+use rocket::request::{FromRequest, Outcome};
+use rocket::{Data, Request, async_trait};
+use std::collections::HashSet;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Where limited requests are rerouted to, since a fairing can't answer a request itself.
+pub const LIMITED_PATH: &str = "/__rate_limited";
+
+/// A token bucket: up to `burst` requests at once, refilled at `per_minute`.
+#[derive(Clone, Copy)]
+pub struct RateLimit {
+    pub per_minute: u32,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub const fn new(per_minute: u32, burst: u32) -> Self {
+        Self { per_minute, burst }
+    }
+
+    fn refill_per_second(self) -> f64 {
+        f64::from(self.per_minute) / 60.0
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    /// Takes a token if there is one, otherwise says how many seconds until there will be.
+    fn take(&mut self, limit: RateLimit) -> Result<(), u64> {
+        let now = Instant::now();
+        let rate = limit.refill_per_second();
+        self.tokens = (self.tokens + now.duration_since(self.updated).as_secs_f64() * rate)
+            .min(f64::from(limit.burst));
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        if rate <= 0.0 {
+            return Err(60);
+        }
+        Err(((1.0 - self.tokens) / rate).ceil() as u64)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Client {
+    Ip(IpAddr),
+    Key(String),
+}
+
+/// Per-IP and per-API-key token buckets. Anonymous clients get a bucket per route group, whose
+/// limit is that of the longest matching prefix. Requests made with a valid key are charged to
+/// the key instead, under a single limit of its own. Allowlisted IPs are never limited.
+pub struct RateLimiter {
+    default_limit: RateLimit,
+    groups: Vec<(&'static str, Option<RateLimit>)>,
+    key_limit: RateLimit,
+    allowlist: HashSet<IpAddr>,
+    buckets: Cache<(Client, &'static str), Arc<Mutex<Bucket>>>,
+    /// Whether a bearer token (by hash) belongs to an active key, so keys aren't looked up on
+    /// every request. Only consulted for limiting, the `ApiKey` guard still checks every time.
+    known_keys: Cache<String, bool>,
+}
+
+impl RateLimiter {
+    pub fn new(default_limit: RateLimit, key_limit: RateLimit) -> Self {
+        Self {
+            default_limit,
+            groups: Vec::new(),
+            key_limit,
+            allowlist: HashSet::new(),
+            buckets: Cache::builder()
+                .time_to_idle(Duration::from_mins(10))
+                .max_capacity(65536)
+                .build(),
+            known_keys: Cache::builder()
+                .time_to_live(Duration::from_secs(60))
+                .max_capacity(4096)
+                .build(),
+        }
+    }
+
+    pub fn with_group(mut self, route_prefix: &'static str, limit: RateLimit) -> Self {
+        self.groups.push((route_prefix, Some(limit)));
+        self
+    }
+
+    pub fn without_limit(mut self, route_prefix: &'static str) -> Self {
+        self.groups.push((route_prefix, None));
+        self
+    }
+
+    pub fn with_allowlist(mut self, ips: impl IntoIterator<Item = IpAddr>) -> Self {
+        self.allowlist.extend(ips);
+        self
+    }
+
+    fn group_for(&self, path: &str) -> (&'static str, Option<RateLimit>) {
+        let path = unversioned(path);
+        self.groups
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .copied()
+            .unwrap_or(("", Some(self.default_limit)))
+    }
+
+    async fn take(&self, client: Client, group: &'static str, limit: RateLimit) -> Result<(), u64> {
+        let bucket = self
+            .buckets
+            .get_with((client, group), async {
+                Arc::new(Mutex::new(Bucket {
+                    tokens: f64::from(limit.burst),
+                    updated: Instant::now(),
+                }))
+            })
+            .await;
+        let mut bucket = bucket.lock().unwrap_or_else(|e| e.into_inner());
+        bucket.take(limit)
+    }
+
+    async fn is_known_key(&self, state: &GhastApiState, key_hash: &str) -> bool {
+        if let Some(known) = self.known_keys.get(key_hash).await {
+            return known;
+        }
+        let known = match state.database.get_active_api_key(key_hash).await {
+            Ok(key) => key.is_some(),
+            Err(e) => {
+                warn!("Error looking up api key for rate limiting: {e:?}");
+                return false;
+            }
+        };
+        self.known_keys.insert(String::from(key_hash), known).await;
+        known
+    }
+
+    /// Decides whether a request may go ahead, answering with seconds to wait if not.
+    async fn check(&self, request: &Request<'_>, state: &GhastApiState) -> Result<(), u64> {
+        let ip = if request.rocket().config().ip_header.is_some() {
+            request.client_ip()
+        } else {
+            request.remote().map(|remote| remote.ip())
+        };
+        if ip.is_some_and(|ip| self.allowlist.contains(&ip)) {
+            return Ok(());
+        }
+        let (group, limit) = self.group_for(request.uri().path().as_str());
+        let Some(limit) = limit else {
+            return Ok(());
+        };
+        let ip_client = ip.map(Client::Ip);
+
+        let mut ip_charged = false;
+        if let Some(token) = request
+            .headers()
+            .get_one("Authorization")
+            .and_then(|header| header.strip_prefix("Bearer "))
+        {
+            let key_hash = hash_key(token);
+            if state
+                .admin_token
+                .as_deref()
+                .is_some_and(|admin_token| token_matches(admin_token, token))
+            {
+                return self.take(Client::Key(key_hash), "", self.key_limit).await;
+            }
+            if !self.known_keys.contains_key(&key_hash)
+                && let Some(client) = ip_client.clone()
+            {
+                // looking a key up costs a query, so unfamiliar tokens pay for it from the IP's
+                // bucket. Otherwise random tokens would be a way around the limit.
+                self.take(client, group, limit).await?;
+                ip_charged = true;
+            }
+            if self.is_known_key(state, &key_hash).await {
+                return self.take(Client::Key(key_hash), "", self.key_limit).await;
+            }
+        }
+
+        match ip_client {
+            Some(client) if !ip_charged => self.take(client, group, limit).await,
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Set on requests that were rerouted to `LIMITED_PATH`, with the seconds to wait. Requests for
+/// `LIMITED_PATH` that weren't rerouted don't have one, and are forwarded on to a 404.
+#[derive(Clone, Copy)]
+pub struct RetryAfter(pub u64);
+
+#[async_trait]
+impl<'r> FromRequest<'r> for RetryAfter {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.local_cache(|| None::<Self>) {
+            Some(retry_after) => Outcome::Success(*retry_after),
+            None => Outcome::Forward(Status::NotFound),
+        }
+    }
+}
+
+pub struct RateLimiting;
+
+#[async_trait]
+impl Fairing for RateLimiting {
+    fn info(&self) -> Info {
+        Info {
+            name: "Rate limiting",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        if request.method() == Method::Options {
+            return;
+        }
+        let Some(state) = request.rocket().state::<GhastApiState>() else {
+            return;
+        };
+        let Err(retry_after) = state.rate_limiter.check(request, state).await else {
+            return;
+        };
+        let group = state
+            .rate_limiter
+            .group_for(request.uri().path().as_str())
+            .0;
+        METRICS
+            .rate_limited_requests
+            .with_label_values(&[if group.is_empty() { "default" } else { group }])
+            .inc();
+        request.local_cache(|| Some(RetryAfter(retry_after)));
+        request.set_method(Method::Get);
+        request.set_uri(Origin::parse(LIMITED_PATH).unwrap());
+    }
+}
@@ -0,0 +1,86 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::Header;
+use rocket::route::{Handler, Outcome, Route};
+use rocket::{Data, Request, Response, async_trait};
+use std::time::Instant;
+use tracing::{Instrument, info, info_span};
+use uuid::Uuid;
+
+const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Ties together everything logged while serving one request. A sane `X-Request-Id` from the
+/// client (or a proxy in front of us) is kept, otherwise a fresh one is made up.
+pub struct RequestId(pub String);
+
+impl RequestId {
+    pub fn of<'r>(request: &'r Request<'_>) -> &'r Self {
+        request.local_cache(|| {
+            let incoming = request.headers().get_one(REQUEST_ID_HEADER).filter(|id| {
+                (1..=64).contains(&id.len())
+                    && id
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            });
+            Self(incoming.map_or_else(|| Uuid::new_v4().to_string(), String::from))
+        })
+    }
+}
+
+/// Assigns request ids, echoes them back in `X-Request-Id` and writes an access log line.
+pub struct RequestTracing;
+
+#[async_trait]
+impl Fairing for RequestTracing {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request tracing",
+            kind: Kind::Request | Kind::Response,
+        }
+    }
+
+    async fn on_request(&self, request: &mut Request<'_>, _: &mut Data<'_>) {
+        RequestId::of(request);
+        request.local_cache(Instant::now);
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let request_id = &RequestId::of(request).0;
+        response.set_header(Header::new(REQUEST_ID_HEADER, request_id.clone()));
+        info!(
+            request_id = %request_id,
+            method = %request.method(),
+            uri = %request.uri(),
+            status = response.status().code,
+            elapsed_ms = request.local_cache(Instant::now).elapsed().as_millis() as u64,
+            "request served"
+        );
+    }
+}
+
+/// Runs a route's handler inside a span carrying the request id, so database and Mojang calls
+/// made on the request's behalf are logged with it.
+#[derive(Clone)]
+struct Traced(Box<dyn Handler>);
+
+#[async_trait]
+impl Handler for Traced {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        let span = info_span!(
+            "request",
+            request_id = %RequestId::of(request).0,
+            method = %request.method(),
+            uri = %request.uri(),
+        );
+        self.0.handle(request, data).instrument(span).await
+    }
+}
+
+pub fn traced(routes: Vec<Route>) -> Vec<Route> {
+    routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(Traced(route.handler.clone()));
+            route
+        })
+        .collect()
+}
@@ -0,0 +1,120 @@
+use crate::web::response_cache::CachedResponse;
+use log::warn;
+use rocket::Request;
+use rocket::http::{ContentType, Header, Status};
+use rocket::response::{self, Responder, Response};
+use rocket_okapi::r#gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::Responses;
+use rocket_okapi::response::OpenApiResponderInner;
+use rocket_okapi::util::{add_schema_response, ensure_status_code_exists};
+use serde::Serialize;
+use std::io::Cursor;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// Serializes a list of flat rows as a CSV document, header first.
+pub struct Csv<T>(pub Vec<T>);
+
+impl<T: Serialize> Csv<T> {
+    pub fn into_bytes(self) -> Result<Vec<u8>, Status> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for row in self.0 {
+            writer.serialize(row).map_err(|e| {
+                warn!("Error serializing csv row {e:?}");
+                Status::InternalServerError
+            })?;
+        }
+        writer.into_inner().map_err(|e| {
+            warn!("Error flushing csv writer {e:?}");
+            Status::InternalServerError
+        })
+    }
+}
+
+impl<'r, T: Serialize> Responder<'r, 'static> for Csv<T> {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let body = self.into_bytes()?;
+        Response::build()
+            .header(ContentType::CSV)
+            .sized_body(body.len(), Cursor::new(body))
+            .ok()
+    }
+}
+
+impl<T> OpenApiResponderInner for Csv<T> {
+    fn responses(generator: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let mut responses = Responses::default();
+        let schema = generator.json_schema::<String>();
+        add_schema_response(&mut responses, 200, "text/csv", schema)?;
+        Ok(responses)
+    }
+}
+
+/// Serves a cached body with its `ETag` and `Last-Modified`, or a bare 304 when the request's
+/// `If-None-Match` (or, failing that, `If-Modified-Since`) shows the client already has it.
+/// `R` is the responder the body was rendered as, which is what the route is documented as.
+pub struct Cached<R>(pub Arc<CachedResponse>, PhantomData<R>);
+
+impl<R> From<Arc<CachedResponse>> for Cached<R> {
+    fn from(response: Arc<CachedResponse>) -> Self {
+        Self(response, PhantomData)
+    }
+}
+
+const HTTP_DATE: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+impl<R> Cached<R> {
+    fn is_fresh(&self, request: &Request<'_>) -> bool {
+        if let Some(if_none_match) = request.headers().get_one("If-None-Match") {
+            return if_none_match
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == self.0.etag);
+        }
+        request
+            .headers()
+            .get_one("If-Modified-Since")
+            .and_then(|since| chrono::DateTime::parse_from_rfc2822(since).ok())
+            .is_some_and(|since| self.0.last_modified.timestamp() <= since.timestamp())
+    }
+}
+
+impl<'r, R> Responder<'r, 'static> for Cached<R> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .header(Header::new("ETag", self.0.etag.clone()))
+            .header(Header::new(
+                "Last-Modified",
+                self.0.last_modified.format(HTTP_DATE).to_string(),
+            ))
+            .header(Header::new("Cache-Control", "no-cache"));
+        if self.is_fresh(request) {
+            return response.status(Status::NotModified).ok();
+        }
+        response
+            .header(self.0.content_type.clone())
+            .sized_body(self.0.body.len(), Cursor::new(self.0.body.clone()))
+            .ok()
+    }
+}
+
+impl<R: OpenApiResponderInner> OpenApiResponderInner for Cached<R> {
+    fn responses(generator: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let mut responses = R::responses(generator)?;
+        ensure_status_code_exists(&mut responses, 304);
+        Ok(responses)
+    }
+}
+
+/// A 429 telling the client how many seconds to wait before trying again.
+pub struct TooManyRequests(pub u64);
+
+impl<'r> Responder<'r, 'static> for TooManyRequests {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .status(Status::TooManyRequests)
+            .header(Header::new("Retry-After", self.0.to_string()))
+            .ok()
+    }
+}
@@ -0,0 +1,152 @@
+use crate::util::cached_accessor::LoadingCacheDataAccessor;
+use crate::web::responders::Csv;
+use crate::web::versions::unversioned;
+use chrono::{DateTime, Utc};
+use log::warn;
+use moka::Expiry;
+use moka::future::Cache;
+use rocket::http::uri::Origin;
+use rocket::http::{ContentType, Status};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// A fully rendered response body along with the validators clients revalidate it by.
+pub struct CachedResponse {
+    pub body: Vec<u8>,
+    pub content_type: ContentType,
+    pub etag: String,
+    pub last_modified: DateTime<Utc>,
+    ttl: Duration,
+}
+
+struct PerRouteExpiry;
+
+impl Expiry<String, Arc<CachedResponse>> for PerRouteExpiry {
+    fn expire_after_create(
+        &self,
+        _: &String,
+        response: &Arc<CachedResponse>,
+        _: Instant,
+    ) -> Option<Duration> {
+        Some(response.ttl)
+    }
+}
+
+/// Rendered responses of expensive routes, keyed by path and query. Everything is dropped as
+/// soon as a match is ingested or a tournament changes, since any of it may now be out of date.
+/// Renders that fail aren't cached.
+pub struct ResponseCache {
+    entries: LoadingCacheDataAccessor<String, Arc<CachedResponse>>,
+    default_ttl: Duration,
+    /// Route prefixes with their own TTL. The longest matching prefix wins.
+    ttls: HashMap<&'static str, Duration>,
+    /// Bumped on every invalidation so renders that straddle one are never served.
+    generation: Arc<AtomicU64>,
+    /// When the data last changed, in epoch seconds.
+    last_modified: Arc<AtomicI64>,
+}
+
+impl ResponseCache {
+    pub fn new(default_ttl: Duration) -> Self {
+        Self {
+            entries: LoadingCacheDataAccessor {
+                // every response is rendered by its route, there is nothing to load by key
+                loader: Arc::new(|_| Box::pin(async { None })),
+                cache: Cache::builder()
+                    .expire_after(PerRouteExpiry)
+                    .max_capacity(512)
+                    .build(),
+            },
+            default_ttl,
+            ttls: HashMap::new(),
+            generation: Arc::new(AtomicU64::new(0)),
+            last_modified: Arc::new(AtomicI64::new(Utc::now().timestamp())),
+        }
+    }
+
+    pub fn with_ttl(mut self, route_prefix: &'static str, ttl: Duration) -> Self {
+        self.ttls.insert(route_prefix, ttl);
+        self
+    }
+
+    /// Clears the cache whenever `changes` reports something, like an ingested match.
+    pub fn invalidate_on<T: Clone + Send + 'static>(&self, mut changes: broadcast::Receiver<T>) {
+        let entries = self.entries.cache.clone();
+        let generation = self.generation.clone();
+        let last_modified = self.last_modified.clone();
+        tokio::spawn(async move {
+            while let Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) = changes.recv().await {
+                generation.fetch_add(1, Ordering::SeqCst);
+                last_modified.store(Utc::now().timestamp(), Ordering::SeqCst);
+                entries.invalidate_all();
+            }
+        });
+    }
+
+    fn ttl_for(&self, path: &str) -> Duration {
+        let path = unversioned(path);
+        self.ttls
+            .iter()
+            .filter(|(prefix, _)| path.starts_with(*prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or(self.default_ttl, |(_, ttl)| *ttl)
+    }
+
+    async fn get_or_render(
+        &self,
+        uri: &Origin<'_>,
+        content_type: ContentType,
+        render: impl Future<Output = Result<Vec<u8>, Status>>,
+    ) -> Result<Arc<CachedResponse>, Status> {
+        let generation = self.generation.load(Ordering::SeqCst);
+        let key = format!("{generation}:{uri}");
+        let ttl = self.ttl_for(uri.path().as_str());
+        let last_modified = DateTime::from_timestamp(self.last_modified.load(Ordering::SeqCst), 0)
+            .unwrap_or_default();
+        self.entries
+            .try_get_with(key, async move {
+                let body = render.await?;
+                let mut hasher = DefaultHasher::new();
+                body.hash(&mut hasher);
+                Ok(Arc::new(CachedResponse {
+                    etag: format!("\"{:016x}\"", hasher.finish()),
+                    body,
+                    content_type,
+                    last_modified,
+                    ttl,
+                }))
+            })
+            .await
+            .map_err(|status: Arc<Status>| *status)
+    }
+
+    /// A render that fails is answered with its status and not cached, so an error isn't served
+    /// as an empty result for the rest of the TTL.
+    pub async fn json<T: Serialize>(
+        &self,
+        uri: &Origin<'_>,
+        render: impl Future<Output = Result<T, Status>>,
+    ) -> Result<Arc<CachedResponse>, Status> {
+        self.get_or_render(uri, ContentType::JSON, async {
+            serde_json::to_vec(&render.await?).map_err(|e| {
+                warn!("Error serializing cached response for {uri}: {e:?}");
+                Status::InternalServerError
+            })
+        })
+        .await
+    }
+
+    pub async fn csv<T: Serialize>(
+        &self,
+        uri: &Origin<'_>,
+        render: impl Future<Output = Result<Csv<T>, Status>>,
+    ) -> Result<Arc<CachedResponse>, Status> {
+        self.get_or_render(uri, ContentType::CSV, async { render.await?.into_bytes() })
+            .await
+    }
+}
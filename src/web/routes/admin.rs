@@ -0,0 +1,172 @@
+use crate::db::model::api_key::{ApiKeyRecord, ApiKeyUsage};
+use crate::util::api_keys::{display_prefix, generate_key, hash_key};
+use crate::util::archive::{ARCHIVE_TABLES, create_manifest, read_archive_table};
+use crate::web::api::GhastApiState;
+use crate::web::guards::{AdminScope, ApiKey, ReadPrivateScope};
+use crate::web::types::{CreateApiKeyRequest, CreatedApiKeyApi};
+use crate::web::versions::mount_versioned;
+use chrono::Utc;
+use futures::StreamExt;
+use log::{info, warn};
+use rocket::http::{ContentType, Status};
+use rocket::response::stream::TextStream;
+use rocket::serde::json::Json;
+use rocket::{Build, Rocket, State, delete, get, post};
+use rocket_okapi::okapi::openapi3::OpenApi;
+use rocket_okapi::{openapi, openapi_get_routes_spec};
+
+const DEFAULT_USAGE_ENTRIES: u32 = 100;
+const MAX_USAGE_ENTRIES: u32 = 1000;
+
+/// Streams the whole dataset as newline-delimited JSON, manifest first, one table at a time.
+/// Everything is read from one snapshot, so matches landing mid-export can't make the tables
+/// disagree with the manifest. Should anything fail the stream stops short, which the importer's
+/// count check then rejects.
+#[openapi(skip)]
+#[get("/export")]
+pub async fn export_archive(
+    _key: ApiKey<ReadPrivateScope>,
+    state: &State<GhastApiState>,
+) -> Option<(ContentType, TextStream![String])> {
+    let mut snapshot = state
+        .database
+        .begin_snapshot()
+        .await
+        .inspect_err(|e| warn!("Error starting archive export: {e:?}"))
+        .ok()?;
+    let manifest = create_manifest(&mut snapshot).await?;
+    let manifest = serde_json::to_string(&manifest)
+        .inspect_err(|e| warn!("Error serializing archive manifest: {e:?}"))
+        .ok()?;
+    let stream = TextStream! {
+        yield format!("{manifest}\n");
+        'tables: for table in ARCHIVE_TABLES {
+            let Some(mut records) = read_archive_table(&mut snapshot, table) else {
+                warn!("Archive export stopped at {table}");
+                break;
+            };
+            while let Some(record) = records.next().await {
+                let record = match record {
+                    Ok(record) => record,
+                    Err(e) => {
+                        warn!("Archive export stopped reading {table}: {e:?}");
+                        break 'tables;
+                    }
+                };
+                match serde_json::to_string(&record) {
+                    Ok(line) => yield format!("{line}\n"),
+                    Err(e) => {
+                        warn!("Archive export stopped at a {table} row that failed to serialize: {e:?}");
+                        break 'tables;
+                    }
+                }
+            }
+        }
+    };
+
+    Some((ContentType::new("application", "x-ndjson"), stream))
+}
+
+#[openapi(tag = "Admin")]
+#[post("/keys", data = "<request>")]
+pub async fn create_api_key(
+    request: Json<CreateApiKeyRequest>,
+    admin: ApiKey<AdminScope>,
+    state: &State<GhastApiState>,
+) -> Result<Json<CreatedApiKeyApi>, Status> {
+    let name = request.name.trim();
+    if name.is_empty() || request.scopes.is_empty() {
+        return Err(Status::BadRequest);
+    }
+    let mut scopes: Vec<String> = request
+        .scopes
+        .iter()
+        .map(|scope| String::from(scope.as_str()))
+        .collect();
+    scopes.sort_unstable();
+    scopes.dedup();
+
+    let key = generate_key();
+    let record = state
+        .database
+        .insert_api_key(
+            name,
+            &display_prefix(&key),
+            &hash_key(&key),
+            &scopes,
+            Utc::now().timestamp_millis() as u64,
+        )
+        .await
+        .ok_or(Status::InternalServerError)?;
+    info!(
+        "Api key {} ({name}) minted with scopes {scopes:?} by {:?}",
+        record.id, admin.key_id
+    );
+    Ok(Json(CreatedApiKeyApi { record, key }))
+}
+
+#[openapi(tag = "Admin")]
+#[get("/keys")]
+pub async fn list_api_keys(
+    _key: ApiKey<AdminScope>,
+    state: &State<GhastApiState>,
+) -> Option<Json<Vec<ApiKeyRecord>>> {
+    state.database.get_api_keys().await.map(Json)
+}
+
+#[openapi(tag = "Admin")]
+#[delete("/keys/<id>")]
+pub async fn revoke_api_key(
+    id: i32,
+    admin: ApiKey<AdminScope>,
+    state: &State<GhastApiState>,
+) -> Status {
+    match state
+        .database
+        .revoke_api_key(id, Utc::now().timestamp_millis() as u64)
+        .await
+    {
+        Some(true) => {
+            info!("Api key {id} revoked by {:?}", admin.key_id);
+            Status::NoContent
+        }
+        Some(false) => Status::NotFound,
+        None => Status::InternalServerError,
+    }
+}
+
+/// The audit log of key usage, newest first, for one key or all of them.
+#[openapi(tag = "Admin")]
+#[get("/keys/usage?<key_id>&<limit>")]
+pub async fn get_api_key_usage(
+    key_id: Option<i32>,
+    limit: Option<u32>,
+    _key: ApiKey<AdminScope>,
+    state: &State<GhastApiState>,
+) -> Option<Json<Vec<ApiKeyUsage>>> {
+    state
+        .database
+        .get_api_key_usage(
+            key_id,
+            limit
+                .unwrap_or(DEFAULT_USAGE_ENTRIES)
+                .min(MAX_USAGE_ENTRIES),
+        )
+        .await
+        .map(Json)
+}
+
+pub fn mount(rocket_build: Rocket<Build>, spec: &mut OpenApi) -> Rocket<Build> {
+    mount_versioned(
+        rocket_build,
+        spec,
+        "/admin",
+        openapi_get_routes_spec![
+            export_archive,
+            create_api_key,
+            list_api_keys,
+            revoke_api_key,
+            get_api_key_usage
+        ],
+    )
+}
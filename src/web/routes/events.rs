@@ -0,0 +1,208 @@
+use crate::db::model::match_event::{MatchEvent, MatchEventKind};
+use crate::db::model::player_match_stats::PlayerMatchStats;
+use crate::web::api::GhastApiState;
+use crate::web::guards::{ApiKey, IngestScope};
+use crate::web::routes::utils::{make_event_response, resolve_event_usernames};
+use crate::web::types::{EventStatMismatch, MatchEventsApi};
+use crate::web::versions::mount_versioned;
+use log::warn;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::{Build, Rocket, State, get, post};
+use rocket_okapi::okapi::openapi3::OpenApi;
+use rocket_okapi::{openapi, openapi_get_routes_spec};
+use std::collections::{BTreeSet, HashMap};
+use uuid::Uuid;
+
+#[derive(Clone, Copy)]
+enum EventRole {
+    Actor,
+    Target,
+}
+
+/// Which player an event is credited to and the `PlayerMatchStats` field it should add up to.
+struct EventStat {
+    kind: MatchEventKind,
+    role: EventRole,
+    stat: &'static str,
+    recorded: fn(&PlayerMatchStats) -> u32,
+}
+
+const EVENT_STATS: [EventStat; 9] = [
+    EventStat {
+        kind: MatchEventKind::Pickup,
+        role: EventRole::Actor,
+        stat: "pickups",
+        recorded: |stats| stats.pickups,
+    },
+    EventStat {
+        kind: MatchEventKind::Throw,
+        role: EventRole::Actor,
+        stat: "throws",
+        recorded: |stats| stats.throws,
+    },
+    EventStat {
+        kind: MatchEventKind::Pass,
+        role: EventRole::Actor,
+        stat: "passes",
+        recorded: |stats| stats.passes,
+    },
+    EventStat {
+        kind: MatchEventKind::Catch,
+        role: EventRole::Actor,
+        stat: "catches",
+        recorded: |stats| stats.catches,
+    },
+    EventStat {
+        kind: MatchEventKind::Strip,
+        role: EventRole::Actor,
+        stat: "strips",
+        recorded: |stats| stats.strips,
+    },
+    EventStat {
+        kind: MatchEventKind::Interception,
+        role: EventRole::Actor,
+        stat: "defensive_interceptions",
+        recorded: |stats| stats.defensive_interceptions,
+    },
+    EventStat {
+        kind: MatchEventKind::Interception,
+        role: EventRole::Target,
+        stat: "pass_interceptions",
+        recorded: |stats| stats.pass_interceptions,
+    },
+    EventStat {
+        kind: MatchEventKind::Touchdown,
+        role: EventRole::Actor,
+        stat: "touchdowns",
+        recorded: |stats| stats.touchdowns,
+    },
+    EventStat {
+        kind: MatchEventKind::Kill,
+        role: EventRole::Actor,
+        stat: "kills",
+        recorded: |stats| stats.kills,
+    },
+];
+
+fn reconcile_events(
+    events: &[MatchEvent],
+    player_stats: &HashMap<Uuid, PlayerMatchStats>,
+) -> Vec<(Uuid, &'static str, u32, u32)> {
+    let mut counts: HashMap<(Uuid, &'static str), u32> = HashMap::new();
+    for event in events {
+        for event_stat in EVENT_STATS.iter().filter(|s| s.kind == event.kind) {
+            let player = match event_stat.role {
+                EventRole::Actor => event.actor,
+                EventRole::Target => event.target,
+            };
+            if let Some(player) = player {
+                *counts.entry((player, event_stat.stat)).or_default() += 1;
+            }
+        }
+    }
+
+    let players: BTreeSet<Uuid> = player_stats
+        .keys()
+        .copied()
+        .chain(counts.keys().map(|(uuid, _)| *uuid))
+        .collect();
+    players
+        .into_iter()
+        .flat_map(|uuid| EVENT_STATS.iter().map(move |event_stat| (uuid, event_stat)))
+        .filter_map(|(uuid, event_stat)| {
+            let from_events = counts.get(&(uuid, event_stat.stat)).copied().unwrap_or(0);
+            let recorded = player_stats
+                .get(&uuid)
+                .map_or(0, |stats| (event_stat.recorded)(stats));
+            (from_events != recorded).then_some((uuid, event_stat.stat, from_events, recorded))
+        })
+        .collect()
+}
+
+async fn get_mismatches(
+    match_id: u32,
+    events: &[MatchEvent],
+    state: &State<GhastApiState>,
+) -> Vec<EventStatMismatch> {
+    if events.is_empty() {
+        return Vec::new();
+    }
+    let player_stats = state
+        .database
+        .get_player_match_stats(match_id)
+        .await
+        .unwrap_or_default();
+    let mismatches = reconcile_events(events, &player_stats);
+    let username_map = state
+        .username_resolver
+        .resolve_batch(mismatches.iter().map(|(uuid, ..)| *uuid).collect())
+        .await;
+
+    mismatches
+        .into_iter()
+        .map(|(uuid, stat, events, recorded)| EventStatMismatch {
+            uuid,
+            username: username_map
+                .get(&uuid)
+                .cloned()
+                .flatten()
+                .unwrap_or_else(|| String::from("Unknown")),
+            stat: String::from(stat),
+            events,
+            recorded,
+        })
+        .collect()
+}
+
+#[openapi(tag = "Events")]
+#[get("/<match_id>/events")]
+pub async fn get_match_events(
+    match_id: u32,
+    state: &State<GhastApiState>,
+) -> Result<Json<MatchEventsApi>, Status> {
+    if state.database.get_match_by_id(match_id).await.is_none() {
+        return Err(Status::NotFound);
+    }
+    let Some(events) = state.database.get_match_events(match_id).await else {
+        return Err(Status::InternalServerError);
+    };
+    let username_map = resolve_event_usernames(&events, state).await;
+    let mismatches = get_mismatches(match_id, &events, state).await;
+    Ok(Json(MatchEventsApi {
+        events: events
+            .iter()
+            .map(|event| make_event_response(event, &username_map))
+            .collect(),
+        mismatches,
+    }))
+}
+
+/// Stores a match's event log and answers with any stats it fails to reconcile with.
+#[openapi(tag = "Events")]
+#[post("/<match_id>/events", data = "<events>")]
+pub async fn post_match_events(
+    match_id: u32,
+    events: Json<Vec<MatchEvent>>,
+    _key: ApiKey<IngestScope>,
+    state: &State<GhastApiState>,
+) -> Result<Json<Vec<EventStatMismatch>>, Status> {
+    if state.database.get_match_by_id(match_id).await.is_none() {
+        return Err(Status::NotFound);
+    }
+    if let Err(e) = state.database.replace_match_events(match_id, &events).await {
+        warn!("Error storing events for match {match_id}: {e:?}");
+        return Err(Status::UnprocessableEntity);
+    }
+
+    Ok(Json(get_mismatches(match_id, &events, state).await))
+}
+
+pub fn mount(rocket_build: Rocket<Build>, spec: &mut OpenApi) -> Rocket<Build> {
+    mount_versioned(
+        rocket_build,
+        spec,
+        "/matches",
+        openapi_get_routes_spec![get_match_events, post_match_events],
+    )
+}
@@ -0,0 +1,32 @@
+use crate::web::api::GhastApiState;
+use crate::web::request_id::traced;
+use async_graphql::http::GraphiQLSource;
+use async_graphql_rocket::{GraphQLQuery, GraphQLRequest, GraphQLResponse};
+use rocket::response::content::RawHtml;
+use rocket::{Build, Rocket, State, get, post, routes};
+
+#[post("/", data = "<request>")]
+pub async fn graphql_request(
+    request: GraphQLRequest,
+    state: &State<GhastApiState>,
+) -> GraphQLResponse {
+    request.execute(&state.graphql).await
+}
+
+#[get("/?<query..>")]
+pub async fn graphql_query(query: GraphQLQuery, state: &State<GhastApiState>) -> GraphQLResponse {
+    query.execute(&state.graphql).await
+}
+
+#[get("/graphiql")]
+pub fn graphiql() -> RawHtml<String> {
+    RawHtml(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+/// Unversioned and left out of the OpenAPI document, the schema documents itself.
+pub fn mount(rocket_build: Rocket<Build>) -> Rocket<Build> {
+    rocket_build.mount(
+        "/graphql",
+        traced(routes![graphql_request, graphql_query, graphiql]),
+    )
+}
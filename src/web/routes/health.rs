@@ -0,0 +1,107 @@
+use crate::db::database::MIGRATOR;
+use crate::web::api::GhastApiState;
+use crate::web::openapi::mount_documented;
+use crate::web::types::{
+    DatabaseCheck, HealthApi, MigrationCheck, ReadinessApi, ResolverCacheStatus,
+};
+use log::warn;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::{Build, Rocket, State, get};
+use rocket_okapi::okapi::openapi3::OpenApi;
+use rocket_okapi::{openapi, openapi_get_routes_spec};
+use std::time::{Duration, Instant};
+
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Liveness only: answers as long as the process is serving requests.
+#[openapi(tag = "Health")]
+#[get("/health")]
+pub fn get_health() -> Json<HealthApi> {
+    Json(HealthApi {
+        status: String::from("ok"),
+    })
+}
+
+async fn check_database(state: &GhastApiState) -> DatabaseCheck {
+    if !state.database.is_ready() {
+        return DatabaseCheck {
+            ok: false,
+            latency_ms: None,
+            error: Some(String::from("still connecting")),
+        };
+    }
+    let started = Instant::now();
+    let error = match tokio::time::timeout(PING_TIMEOUT, state.database.ping()).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => {
+            // the detail can name hosts and roles, so it stays in the log
+            warn!("Readiness ping failed: {e:?}");
+            Some(String::from("database unavailable"))
+        }
+        Err(_) => Some(format!("no response within {PING_TIMEOUT:?}")),
+    };
+    DatabaseCheck {
+        ok: error.is_none(),
+        latency_ms: error
+            .is_none()
+            .then(|| started.elapsed().as_millis() as u64),
+        error,
+    }
+}
+
+/// Readiness: 200 when the database answers and every migration is applied, otherwise 503. The
+/// body breaks the checks down either way.
+#[openapi(tag = "Health")]
+#[get("/ready")]
+pub async fn get_ready(state: &State<GhastApiState>) -> (Status, Json<ReadinessApi>) {
+    let database = check_database(state).await;
+    let applied = if database.ok {
+        state
+            .database
+            .get_applied_migrations()
+            .await
+            .unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+    let pending: Vec<i64> = MIGRATOR
+        .iter()
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains(version))
+        .collect();
+    let migrations = MigrationCheck {
+        ok: database.ok && pending.is_empty(),
+        applied,
+        pending,
+    };
+    let (cached_names, cached_misses, mojang_lookups) = state.username_resolver.cache_status();
+
+    let ready = database.ok && migrations.ok;
+    (
+        if ready {
+            Status::Ok
+        } else {
+            Status::ServiceUnavailable
+        },
+        Json(ReadinessApi {
+            status: String::from(if ready { "ready" } else { "degraded" }),
+            database,
+            migrations,
+            username_resolver: ResolverCacheStatus {
+                cached_names,
+                cached_misses,
+                mojang_lookups,
+            },
+        }),
+    )
+}
+
+pub fn mount(rocket_build: Rocket<Build>, spec: &mut OpenApi) -> Rocket<Build> {
+    mount_documented(
+        rocket_build,
+        spec,
+        "/",
+        openapi_get_routes_spec![get_health, get_ready],
+    )
+}
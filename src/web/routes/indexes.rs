@@ -0,0 +1,209 @@
+use crate::db::model::player_match_stats::PlayerMatchStats;
+use crate::web::types::{PlayerIndexScores, TournamentAggregateStats};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+pub fn add_stats(
+    agg: &TournamentAggregateStats,
+    stats: &PlayerMatchStats,
+) -> TournamentAggregateStats {
+    TournamentAggregateStats {
+        assists: agg.assists + stats.assists,
+        catches: agg.catches + stats.catches,
+        damage_carrier: agg.damage_carrier + stats.damage_carrier,
+        damage_dealt: agg.damage_dealt + stats.damage_dealt,
+        damage_taken: agg.damage_taken + stats.damage_taken,
+        deaths: agg.deaths + stats.deaths,
+        defensive_interceptions: agg.defensive_interceptions + stats.defensive_interceptions,
+        kills: agg.kills + stats.kills,
+        killstreak: agg.killstreak.max(stats.killstreak),
+        pass_interceptions: agg.pass_interceptions + stats.pass_interceptions,
+        passes: agg.passes + stats.passes,
+        passing_blocks: agg.passing_blocks + stats.passing_blocks,
+        pickups: agg.pickups + stats.pickups,
+        receive_blocks: agg.receive_blocks + stats.receive_blocks,
+        strips: agg.strips + stats.strips,
+        throws: agg.throws + stats.throws,
+        touchdown_passes: agg.touchdown_passes + stats.touchdown_passes,
+        touchdowns: agg.touchdowns + stats.touchdowns,
+        ..*agg
+    }
+}
+
+struct WeightedScores {
+    passing: f64,
+    receiving: f64,
+    defense: f64,
+    pvp: f64,
+}
+
+impl WeightedScores {
+    fn from_aggregate(agg: &TournamentAggregateStats) -> Self {
+        Self {
+            passing: 0.03f64.mul_add(
+                f64::from(agg.passing_blocks),
+                11.0f64.mul_add(
+                    f64::from(agg.touchdown_passes),
+                    -1.4 * f64::from(agg.pass_interceptions),
+                ),
+            ),
+            receiving: 0.09f64.mul_add(
+                f64::from(agg.catches),
+                0.03f64.mul_add(
+                    f64::from(agg.receive_blocks),
+                    11.0 * f64::from(agg.touchdowns),
+                ),
+            ),
+            defense: 6.0f64.mul_add(
+                f64::from(agg.defensive_interceptions),
+                6.0f64.mul_add(f64::from(agg.strips), 0.055 * f64::from(agg.damage_carrier)),
+            ),
+            pvp: 0.085f64.mul_add(f64::from(agg.kills), 0.015 * agg.damage_dealt),
+        }
+    }
+
+    fn zero() -> Self {
+        Self {
+            passing: 0.0,
+            receiving: 0.0,
+            defense: 0.0,
+            pvp: 0.0,
+        }
+    }
+
+    #[inline]
+    fn offense(&self) -> f64 {
+        self.passing + self.receiving
+    }
+
+    #[inline]
+    fn total(&self) -> f64 {
+        self.offense() + self.defense + self.pvp
+    }
+}
+
+struct RatingScores {
+    offense: f64,
+    passing: f64,
+    receiving: f64,
+    defense: f64,
+    pvp: f64,
+    total: f64,
+}
+
+impl RatingScores {
+    fn from_weighted(weighted: &WeightedScores, games: u32, avg: &WeightedScores) -> Self {
+        let gp = f64::from(games);
+        let bayesian = |w: f64, avg_w: f64| w.mul_add(gp, avg_w * 2.0) / (gp + 2.0);
+
+        Self {
+            offense: bayesian(weighted.offense(), avg.offense()),
+            passing: bayesian(weighted.passing, avg.passing),
+            receiving: bayesian(weighted.receiving, avg.receiving),
+            defense: bayesian(weighted.defense, avg.defense),
+            pvp: bayesian(weighted.pvp, avg.pvp),
+            total: bayesian(weighted.total(), avg.total()),
+        }
+    }
+}
+
+pub struct IndexScores {
+    pub offense: f64,
+    pub passing: f64,
+    pub receiving: f64,
+    pub defense: f64,
+    pub pvp: f64,
+    pub total: f64,
+}
+
+impl IndexScores {
+    fn from_rating(rating: &RatingScores, avg_weighted: &WeightedScores) -> Self {
+        let safe_div = |val: f64, divisor: f64| {
+            if divisor.abs() > 0.001 {
+                val / divisor
+            } else {
+                0.0
+            }
+        };
+
+        Self {
+            offense: safe_div(rating.offense, avg_weighted.offense()),
+            passing: safe_div(rating.passing, avg_weighted.passing),
+            receiving: safe_div(rating.receiving, avg_weighted.receiving),
+            defense: safe_div(rating.defense, avg_weighted.defense),
+            pvp: safe_div(rating.pvp, avg_weighted.pvp),
+            total: safe_div(rating.total, avg_weighted.total()),
+        }
+    }
+
+    pub fn to_player_index_scores(&self) -> PlayerIndexScores {
+        PlayerIndexScores {
+            offense: self.offense,
+            passing: self.passing,
+            receiving: self.receiving,
+            defense: self.defense,
+            pvp: self.pvp,
+            total: self.total,
+        }
+    }
+}
+
+pub fn aggregate_player_stats(
+    stats_by_match: &HashMap<u32, HashMap<Uuid, PlayerMatchStats>>,
+    match_duration_map: &HashMap<u32, u32>,
+    player_team_map: &HashMap<Uuid, i32>,
+) -> HashMap<Uuid, (TournamentAggregateStats, u32, u32)> {
+    stats_by_match
+        .iter()
+        .flat_map(|(match_id, match_stats)| {
+            let duration = match_duration_map.get(match_id).copied().unwrap_or(0);
+            match_stats
+                .iter()
+                .map(move |(uuid, stats)| (uuid, stats, duration))
+        })
+        .fold(HashMap::new(), |mut acc, (uuid, stats, duration)| {
+            let team_id = player_team_map.get(uuid).copied().unwrap_or(0);
+            let (current_agg, count, time) = acc.get(uuid).cloned().unwrap_or_else(|| {
+                (
+                    TournamentAggregateStats {
+                        team: team_id,
+                        ..Default::default()
+                    },
+                    0,
+                    0,
+                )
+            });
+            acc.insert(
+                *uuid,
+                (add_stats(&current_agg, stats), count + 1, time + duration),
+            );
+            acc
+        })
+}
+
+pub fn calculate_player_indexes(
+    player_aggregates: &HashMap<Uuid, (TournamentAggregateStats, u32, u32)>,
+) -> Vec<(Uuid, IndexScores)> {
+    let player_weighted: Vec<(Uuid, WeightedScores, u32)> = player_aggregates
+        .iter()
+        .map(|(uuid, (agg, games, _time))| (*uuid, WeightedScores::from_aggregate(agg), *games))
+        .collect();
+
+    let n = player_weighted.len() as f64;
+    let avg_weighted = player_weighted
+        .iter()
+        .fold(WeightedScores::zero(), |acc, (_, w, _)| WeightedScores {
+            passing: acc.passing + w.passing / n,
+            receiving: acc.receiving + w.receiving / n,
+            defense: acc.defense + w.defense / n,
+            pvp: acc.pvp + w.pvp / n,
+        });
+
+    player_weighted
+        .iter()
+        .map(|(uuid, w, games)| {
+            let rating = RatingScores::from_weighted(w, *games, &avg_weighted);
+            (*uuid, IndexScores::from_rating(&rating, &avg_weighted))
+        })
+        .collect()
+}
@@ -0,0 +1,125 @@
+use crate::web::api::GhastApiState;
+use crate::web::guards::{ApiKey, IngestScope};
+use crate::web::live::LiveMatchEvent;
+use crate::web::types::{LiveMatchState, LiveMatchUpdate, MatchPlayer, PlayerData};
+use crate::web::versions::mount_versioned;
+use chrono::Utc;
+use rocket::http::Status;
+use rocket::response::stream::{Event, EventStream};
+use rocket::serde::json::Json;
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::{Build, Rocket, Shutdown, State, get, post};
+use rocket_okapi::okapi::openapi3::OpenApi;
+use rocket_okapi::{openapi, openapi_get_routes_spec};
+use std::collections::BTreeMap;
+
+#[openapi(tag = "Live")]
+#[get("/")]
+pub fn get_live_matches(state: &State<GhastApiState>) -> Json<Vec<LiveMatchState>> {
+    Json(state.live_matches.all())
+}
+
+/// Pushes an `update` event for every snapshot and an `ended` event once the finished match
+/// has been recorded.
+#[openapi(skip)]
+#[get("/stream")]
+pub fn stream_live_matches(
+    state: &State<GhastApiState>,
+    mut shutdown: Shutdown,
+) -> EventStream![Event + '_] {
+    let mut receiver = state.live_matches.subscribe();
+    EventStream! {
+        loop {
+            let event = select! {
+                message = receiver.recv() => match message {
+                    Ok(message) => message,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(_)) => continue,
+                },
+                () = &mut shutdown => break,
+            };
+            yield match event {
+                LiveMatchEvent::Update(live) => Event::json(&live).event("update"),
+                LiveMatchEvent::Ended(ended) => Event::json(&ended).event("ended"),
+            };
+        }
+    }
+}
+
+#[openapi(tag = "Live")]
+#[get("/<server>")]
+pub async fn get_live_match(
+    server: &str,
+    state: &State<GhastApiState>,
+) -> Option<Json<LiveMatchState>> {
+    state.live_matches.get(server).await.map(Json)
+}
+
+#[openapi(tag = "Live")]
+#[post("/<server>", data = "<update>")]
+pub async fn update_live_match(
+    server: &str,
+    update: Json<LiveMatchUpdate>,
+    _key: ApiKey<IngestScope>,
+    state: &State<GhastApiState>,
+) -> Status {
+    let update = update.into_inner();
+    let uuids = update
+        .players
+        .iter()
+        .map(|player| player.uuid)
+        .chain(update.ball_holder)
+        .collect();
+    let username_map = state.username_resolver.resolve_batch(uuids).await;
+    let username = |uuid| {
+        username_map
+            .get(&uuid)
+            .cloned()
+            .flatten()
+            .unwrap_or_else(|| String::from("Unknown"))
+    };
+
+    state
+        .live_matches
+        .update(LiveMatchState {
+            server: String::from(server),
+            updated_at: Utc::now().timestamp_millis() as u64,
+            start_time: update.start_time,
+            map: update.map,
+            team_one_score: update.team_one_score,
+            team_two_score: update.team_two_score,
+            time_elapsed: update.time_elapsed,
+            ball_holder: update.ball_holder.map(|uuid| PlayerData {
+                uuid,
+                username: username(uuid),
+            }),
+            players: update
+                .players
+                .into_iter()
+                .map(|player| MatchPlayer {
+                    username: username(player.uuid),
+                    uuid: player.uuid,
+                    stats: player.stats,
+                    percentiles: BTreeMap::new(),
+                    per_minute_percentiles: BTreeMap::new(),
+                })
+                .collect(),
+        })
+        .await;
+    Status::NoContent
+}
+
+pub fn mount(rocket_build: Rocket<Build>, spec: &mut OpenApi) -> Rocket<Build> {
+    mount_versioned(
+        rocket_build,
+        spec,
+        "/live",
+        openapi_get_routes_spec![
+            get_live_matches,
+            stream_live_matches,
+            get_live_match,
+            update_live_match
+        ],
+    )
+}
@@ -1,22 +1,33 @@
 use crate::web::api::GhastApiState;
+use crate::web::responders::{Cached, Csv};
 use crate::web::routes::utils::{get_match_player_stats, get_matches, get_uber_data};
-use crate::web::types::{MatchApi, MatchPlayerApi, PlayerlessMatchApi, UberApi};
+use crate::web::types::{
+    MatchApi, MatchCsvRow, MatchPlayerApi, MatchPlayerCsvRow, PlayerlessMatchApi, UberApi,
+};
+use crate::web::versions::mount_versioned;
 use ::std::ops::Sub;
 use chrono::{TimeDelta, Utc};
+use rocket::http::Status;
+use rocket::http::uri::Origin;
+use rocket::response::stream::{Event, EventStream};
 use rocket::serde::json::Json;
-use rocket::{Build, Rocket, State, get, routes};
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
+use rocket::{Build, Rocket, Shutdown, State, get};
+use rocket_okapi::okapi::openapi3::OpenApi;
+use rocket_okapi::{openapi, openapi_get_routes_spec};
+use std::collections::HashMap;
 
-#[get("/all")]
-pub async fn get_all_matches(state: &State<GhastApiState>) -> Json<MatchApi> {
-    if let Some(matches) = state.database.get_matches_all().await {
-        return get_matches(state, matches).await;
-    }
-
-    Json(Vec::new())
+async fn all_matches(state: &State<GhastApiState>) -> Result<MatchApi, Status> {
+    let matches = state
+        .database
+        .get_matches_all()
+        .await
+        .ok_or(Status::ServiceUnavailable)?;
+    Ok(get_matches(state, matches).await)
 }
 
-#[get("/recent")]
-pub async fn get_recent_matches(state: &State<GhastApiState>) -> Json<MatchApi> {
+async fn recent_matches(state: &State<GhastApiState>) -> MatchApi {
     if let Some(matches) = state
         .database
         .get_matches_between(Utc::now().sub(TimeDelta::days(3)), Utc::now())
@@ -25,9 +36,84 @@ pub async fn get_recent_matches(state: &State<GhastApiState>) -> Json<MatchApi>
         return get_matches(state, matches).await;
     }
 
-    Json(Vec::new())
+    Vec::new()
+}
+
+#[openapi(tag = "Matches")]
+#[get("/all")]
+pub async fn get_all_matches(
+    uri: &Origin<'_>,
+    state: &State<GhastApiState>,
+) -> Result<Cached<Json<MatchApi>>, Status> {
+    state
+        .response_cache
+        .json(uri, all_matches(state))
+        .await
+        .map(Cached::from)
+}
+
+#[openapi(tag = "Matches")]
+#[get("/all.csv")]
+pub async fn get_all_matches_csv(
+    uri: &Origin<'_>,
+    state: &State<GhastApiState>,
+) -> Result<Cached<Csv<MatchCsvRow>>, Status> {
+    state
+        .response_cache
+        .csv(uri, async {
+            let rows: Vec<MatchCsvRow> = all_matches(state)
+                .await?
+                .into_iter()
+                .map(From::from)
+                .collect();
+            Ok(Csv(rows))
+        })
+        .await
+        .map(Cached::from)
+}
+
+#[openapi(tag = "Matches")]
+#[get("/recent")]
+pub async fn get_recent_matches(state: &State<GhastApiState>) -> Json<MatchApi> {
+    Json(recent_matches(state).await)
+}
+
+#[openapi(tag = "Matches")]
+#[get("/recent.csv")]
+pub async fn get_recent_matches_csv(state: &State<GhastApiState>) -> Csv<MatchCsvRow> {
+    Csv(recent_matches(state)
+        .await
+        .into_iter()
+        .map(From::from)
+        .collect())
+}
+
+/// Pushes a `match` event carrying a `MatchResponse` whenever a new match is recorded.
+#[openapi(skip)]
+#[get("/stream")]
+pub fn stream_matches(
+    state: &State<GhastApiState>,
+    mut shutdown: Shutdown,
+) -> EventStream![Event + '_] {
+    let mut receiver = state.match_watcher.subscribe();
+    EventStream! {
+        loop {
+            let (id, data) = select! {
+                message = receiver.recv() => match message {
+                    Ok(message) => message,
+                    Err(RecvError::Closed) => break,
+                    Err(RecvError::Lagged(_)) => continue,
+                },
+                () = &mut shutdown => break,
+            };
+            for response in get_matches(state, HashMap::from([(id, data)])).await {
+                yield Event::json(&response).event("match");
+            }
+        }
+    }
 }
 
+#[openapi(tag = "Matches")]
 #[get("/<match_id>")]
 pub async fn get_match_from_id(
     match_id: u32,
@@ -37,11 +123,13 @@ pub async fn get_match_from_id(
     Json(data.map(|data| PlayerlessMatchApi { id: match_id, data }))
 }
 
+#[openapi(tag = "Matches")]
 #[get("/<match_id>/uber")]
 pub async fn get_match_uber(match_id: u32, state: &State<GhastApiState>) -> Json<Option<UberApi>> {
     return Json(get_uber_data(match_id, state).await);
 }
 
+#[openapi(tag = "Matches")]
 #[get("/<match_id>/player_stats")]
 pub async fn get_player_stats_for_match(
     match_id: u32,
@@ -54,15 +142,31 @@ pub async fn get_player_stats_for_match(
     None
 }
 
-pub fn mount(rocket_build: Rocket<Build>) -> Rocket<Build> {
-    rocket_build.mount(
+#[openapi(tag = "Matches")]
+#[get("/<match_id>/player_stats.csv")]
+pub async fn get_player_stats_for_match_csv(
+    match_id: u32,
+    state: &State<GhastApiState>,
+) -> Option<Csv<MatchPlayerCsvRow>> {
+    let players = get_match_player_stats(match_id, state).await?;
+    Some(Csv(players.into_iter().map(From::from).collect()))
+}
+
+pub fn mount(rocket_build: Rocket<Build>, spec: &mut OpenApi) -> Rocket<Build> {
+    mount_versioned(
+        rocket_build,
+        spec,
         "/matches",
-        routes![
+        openapi_get_routes_spec![
             get_all_matches,
+            get_all_matches_csv,
             get_recent_matches,
+            get_recent_matches_csv,
+            stream_matches,
             get_match_from_id,
             get_player_stats_for_match,
-            get_match_uber,
+            get_player_stats_for_match_csv,
+            get_match_uber
         ],
     )
 }
@@ -0,0 +1,41 @@
+use crate::util::metrics::METRICS;
+use crate::web::api::GhastApiState;
+use crate::web::guards::{ApiKey, MetricsScope};
+use crate::web::openapi::mount_documented;
+use rocket::http::ContentType;
+use rocket::{Build, Rocket, State, get};
+use rocket_okapi::okapi::openapi3::OpenApi;
+use rocket_okapi::{openapi, openapi_get_routes_spec};
+
+/// Prometheus text exposition. Pool gauges are refreshed on each scrape, domain gauges in the
+/// background.
+#[openapi(tag = "Metrics")]
+#[get("/metrics")]
+pub async fn get_metrics(
+    _key: ApiKey<MetricsScope>,
+    state: &State<GhastApiState>,
+) -> Option<(ContentType, String)> {
+    let (size, idle) = state.database.pool_status();
+    METRICS
+        .pool_connections
+        .with_label_values(&["idle"])
+        .set(idle as i64);
+    METRICS
+        .pool_connections
+        .with_label_values(&["active"])
+        .set(i64::from(size) - idle as i64);
+    let body = METRICS.render()?;
+    Some((
+        ContentType::new("text", "plain").with_params(("version", "0.0.4")),
+        body,
+    ))
+}
+
+pub fn mount(rocket_build: Rocket<Build>, spec: &mut OpenApi) -> Rocket<Build> {
+    mount_documented(
+        rocket_build,
+        spec,
+        "/",
+        openapi_get_routes_spec![get_metrics],
+    )
+}
@@ -1,3 +1,16 @@
+pub mod admin;
+pub mod events;
+pub mod graphql;
+pub mod health;
+pub mod indexes;
+pub mod live;
 pub mod r#match;
+pub mod metrics;
+pub mod pass_network;
+pub mod players;
+pub mod rate_limit;
+pub mod stats;
 pub mod tournament;
 mod utils;
+pub mod violations;
+pub mod win_probability;
@@ -0,0 +1,277 @@
+use crate::db::model::match_event::{MatchEvent, MatchEventKind};
+use crate::web::api::GhastApiState;
+use crate::web::routes::utils::make_player_data;
+use crate::web::types::{PassNetworkApi, PassNetworkEdge, PassNetworkNode};
+use crate::web::versions::mount_versioned;
+use rocket::http::ContentType;
+use rocket::serde::json::Json;
+use rocket::{Build, Rocket, State, get};
+use rocket_okapi::okapi::openapi3::OpenApi;
+use rocket_okapi::{openapi, openapi_get_routes_spec};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+use uuid::Uuid;
+
+#[derive(Default)]
+struct EdgeCounts {
+    passes: u32,
+    touchdown_passes: u32,
+}
+
+/// Counts completed passes between players. A pass and the catch that answers it describe the
+/// same completion, so the catch is only counted when no matching pass came right before it.
+fn add_match_passes(events: &[MatchEvent], edges: &mut HashMap<(Uuid, Uuid), EdgeCounts>) {
+    let mut pending_catch: Option<(Uuid, Uuid)> = None;
+    let mut last_completion: Option<(Uuid, Uuid)> = None;
+    for event in events {
+        match event.kind {
+            MatchEventKind::Pass | MatchEventKind::Catch => {
+                let (passer, receiver) = if event.kind == MatchEventKind::Pass {
+                    (event.actor, event.target)
+                } else {
+                    (event.target, event.actor)
+                };
+                let (Some(passer), Some(receiver)) = (passer, receiver) else {
+                    continue;
+                };
+                if passer == receiver {
+                    continue;
+                }
+                if event.kind == MatchEventKind::Catch
+                    && pending_catch.take() == Some((passer, receiver))
+                {
+                    continue;
+                }
+                if event.kind == MatchEventKind::Pass {
+                    pending_catch = Some((passer, receiver));
+                }
+                edges.entry((passer, receiver)).or_default().passes += 1;
+                last_completion = Some((passer, receiver));
+            }
+            MatchEventKind::Touchdown => {
+                if let Some(edge) = last_completion.take()
+                    && event.actor == Some(edge.1)
+                {
+                    edges.entry(edge).or_default().touchdown_passes += 1;
+                }
+                pending_catch = None;
+            }
+            MatchEventKind::Pickup | MatchEventKind::Strip | MatchEventKind::Interception => {
+                pending_catch = None;
+                last_completion = None;
+            }
+            MatchEventKind::Throw | MatchEventKind::Kill => {}
+        }
+    }
+}
+
+async fn build_network(
+    edges: HashMap<(Uuid, Uuid), EdgeCounts>,
+    team_map: HashMap<Uuid, i32>,
+    state: &State<GhastApiState>,
+) -> PassNetworkApi {
+    let players: BTreeSet<Uuid> = team_map
+        .keys()
+        .copied()
+        .chain(
+            edges
+                .keys()
+                .flat_map(|(passer, receiver)| [*passer, *receiver]),
+        )
+        .collect();
+    let username_map = state
+        .username_resolver
+        .resolve_batch(players.iter().copied().collect())
+        .await;
+
+    let mut nodes: Vec<PassNetworkNode> = players
+        .into_iter()
+        .map(|uuid| PassNetworkNode {
+            player: make_player_data(uuid, &username_map),
+            team: team_map.get(&uuid).copied(),
+            passes: edges
+                .iter()
+                .filter(|((passer, _), _)| *passer == uuid)
+                .map(|(_, counts)| counts.passes)
+                .sum(),
+            catches: edges
+                .iter()
+                .filter(|((_, receiver), _)| *receiver == uuid)
+                .map(|(_, counts)| counts.passes)
+                .sum(),
+        })
+        .collect();
+    nodes.sort_by(|a, b| {
+        a.team
+            .cmp(&b.team)
+            .then_with(|| a.player.username.cmp(&b.player.username))
+    });
+
+    let mut edges: Vec<PassNetworkEdge> = edges
+        .into_iter()
+        .map(|((passer, receiver), counts)| PassNetworkEdge {
+            passer,
+            receiver,
+            passes: counts.passes,
+            touchdown_passes: counts.touchdown_passes,
+        })
+        .collect();
+    edges.sort_by(|a, b| {
+        b.passes
+            .cmp(&a.passes)
+            .then_with(|| (a.passer, a.receiver).cmp(&(b.passer, b.receiver)))
+    });
+
+    PassNetworkApi { nodes, edges }
+}
+
+/// Renders the network as a Graphviz digraph, one cluster per team. Edge width follows the number
+/// of passes and edges that produced a touchdown are drawn in gold.
+fn to_dot(network: &PassNetworkApi) -> String {
+    let mut dot = String::from("digraph pass_network {\n    node [shape=box];\n");
+    let teams: BTreeSet<Option<i32>> = network.nodes.iter().map(|node| node.team).collect();
+    for team in teams {
+        let indent = match team {
+            Some(team) => {
+                let _ = writeln!(dot, "    subgraph cluster_team_{team} {{");
+                let _ = writeln!(dot, "        label=\"Team {team}\";");
+                "        "
+            }
+            None => "    ",
+        };
+        for node in network.nodes.iter().filter(|node| node.team == team) {
+            let _ = writeln!(
+                dot,
+                "{indent}\"{}\" [label=\"{}\"];",
+                node.player.uuid,
+                node.player.username.replace('"', "\\\"")
+            );
+        }
+        if team.is_some() {
+            dot.push_str("    }\n");
+        }
+    }
+    for edge in &network.edges {
+        let colour = if edge.touchdown_passes > 0 {
+            "gold"
+        } else {
+            "black"
+        };
+        let _ = writeln!(
+            dot,
+            "    \"{}\" -> \"{}\" [label=\"{}\", penwidth={}, color={colour}];",
+            edge.passer,
+            edge.receiver,
+            edge.passes,
+            1 + edge.passes.min(9)
+        );
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+fn graphviz() -> ContentType {
+    ContentType::new("text", "vnd.graphviz")
+}
+
+async fn get_match_network(match_id: u32, state: &State<GhastApiState>) -> Option<PassNetworkApi> {
+    let events = state.database.get_match_events(match_id).await?;
+    let mut edges = HashMap::new();
+    add_match_passes(&events, &mut edges);
+    let team_map = state
+        .database
+        .get_player_match_stats(match_id)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(uuid, stats)| (uuid, stats.team))
+        .collect();
+    Some(build_network(edges, team_map, state).await)
+}
+
+async fn get_tournament_network(
+    tournament_id: u32,
+    state: &State<GhastApiState>,
+) -> Option<PassNetworkApi> {
+    state.database.get_tournament_by_id(tournament_id).await?;
+    let tournament_ids = [tournament_id];
+    let (team_players, matches) = tokio::join!(
+        state.database.get_tournament_team_players(&tournament_ids),
+        state.database.get_tournament_matches(&tournament_ids),
+    );
+
+    let match_ids: Vec<u32> = matches
+        .unwrap_or_default()
+        .iter()
+        .map(|m| m.match_id)
+        .collect();
+    let mut edges = HashMap::new();
+    for events in state
+        .database
+        .get_match_events_for(&match_ids)
+        .await
+        .unwrap_or_default()
+        .values()
+    {
+        add_match_passes(events, &mut edges);
+    }
+    let team_map = team_players
+        .unwrap_or_default()
+        .into_iter()
+        .map(|p| (p.player_uuid, p.team_id))
+        .collect();
+    Some(build_network(edges, team_map, state).await)
+}
+
+#[openapi(tag = "Pass networks")]
+#[get("/<match_id>/pass-network")]
+pub async fn get_match_pass_network(
+    match_id: u32,
+    state: &State<GhastApiState>,
+) -> Option<Json<PassNetworkApi>> {
+    get_match_network(match_id, state).await.map(Json)
+}
+
+#[openapi(tag = "Pass networks")]
+#[get("/<match_id>/pass-network.dot")]
+pub async fn get_match_pass_network_dot(
+    match_id: u32,
+    state: &State<GhastApiState>,
+) -> Option<(ContentType, String)> {
+    let network = get_match_network(match_id, state).await?;
+    Some((graphviz(), to_dot(&network)))
+}
+
+#[openapi(tag = "Pass networks")]
+#[get("/<tournament_id>/pass-network")]
+pub async fn get_tournament_pass_network(
+    tournament_id: u32,
+    state: &State<GhastApiState>,
+) -> Option<Json<PassNetworkApi>> {
+    get_tournament_network(tournament_id, state).await.map(Json)
+}
+
+#[openapi(tag = "Pass networks")]
+#[get("/<tournament_id>/pass-network.dot")]
+pub async fn get_tournament_pass_network_dot(
+    tournament_id: u32,
+    state: &State<GhastApiState>,
+) -> Option<(ContentType, String)> {
+    let network = get_tournament_network(tournament_id, state).await?;
+    Some((graphviz(), to_dot(&network)))
+}
+
+pub fn mount(rocket_build: Rocket<Build>, spec: &mut OpenApi) -> Rocket<Build> {
+    let rocket_build = mount_versioned(
+        rocket_build,
+        spec,
+        "/matches",
+        openapi_get_routes_spec![get_match_pass_network, get_match_pass_network_dot],
+    );
+    mount_versioned(
+        rocket_build,
+        spec,
+        "/tournaments",
+        openapi_get_routes_spec![get_tournament_pass_network, get_tournament_pass_network_dot],
+    )
+}
@@ -0,0 +1,379 @@
+use crate::db::model::match_data::PlayerlessMatchData;
+use crate::db::model::name_history::PlayerNameRecord;
+use crate::db::model::player_match_stats::PlayerMatchStats;
+use crate::util::distributions::{StatDistributions, percentile_rank};
+use crate::web::api::GhastApiState;
+use crate::web::routes::indexes::{IndexScores, aggregate_player_stats, calculate_player_indexes};
+use crate::web::routes::utils::make_player_data;
+use crate::web::types::{
+    ComparedPlayer, ComparisonWindow, HeadToHead, PlayerComparisonApi, PlayerRating,
+    PlayerSearchResult, StatLine,
+};
+use crate::web::versions::mount_versioned;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::{Build, Rocket, State, get};
+use rocket_okapi::okapi::openapi3::OpenApi;
+use rocket_okapi::{openapi, openapi_get_routes_spec};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+const MIN_COMPARED: usize = 2;
+const MAX_COMPARED: usize = 6;
+
+const DEFAULT_SEARCH_RESULTS: usize = 10;
+const MAX_SEARCH_RESULTS: usize = 50;
+const MAX_QUERY_LENGTH: usize = 32;
+/// Names fetched per result, since a player's older names collapse into a single result.
+const SEARCH_CANDIDATES_PER_RESULT: usize = 4;
+
+type MatchStats = HashMap<u32, HashMap<Uuid, PlayerMatchStats>>;
+
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Lower is better: an exact match, then a prefix, then a substring, then names within a few
+/// typos of the query (or of their own prefix of the same length).
+fn match_score(query: &str, name: &str) -> Option<usize> {
+    let name = name.to_lowercase();
+    if name == query {
+        return Some(0);
+    }
+    if name.starts_with(query) {
+        return Some(1);
+    }
+    if name.contains(query) {
+        return Some(2);
+    }
+    let query: Vec<char> = query.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let max_distance = (query.len() / 4).max(1);
+    let distance = edit_distance(&query, &name)
+        .min(edit_distance(&query, &name[..query.len().min(name.len())]));
+    (distance <= max_distance).then_some(3 + distance)
+}
+
+/// Where each of a player's per-match averages would fall among single pub games.
+fn percentiles(line: &StatLine, distributions: &StatDistributions) -> StatLine {
+    let rank = |name: &str, value: f64| {
+        distributions.population(name).map_or(0.0, |population| {
+            percentile_rank(&population.per_game, value)
+        })
+    };
+    StatLine {
+        kills: rank("kills", line.kills),
+        deaths: rank("deaths", line.deaths),
+        assists: rank("assists", line.assists),
+        damage_dealt: rank("damage_dealt", line.damage_dealt),
+        damage_taken: rank("damage_taken", line.damage_taken),
+        pickups: rank("pickups", line.pickups),
+        throws: rank("throws", line.throws),
+        passes: rank("passes", line.passes),
+        catches: rank("catches", line.catches),
+        strips: rank("strips", line.strips),
+        touchdowns: rank("touchdowns", line.touchdowns),
+        touchdown_passes: rank("touchdown_passes", line.touchdown_passes),
+        passing_blocks: rank("passing_blocks", line.passing_blocks),
+        receive_blocks: rank("receive_blocks", line.receive_blocks),
+        defensive_interceptions: rank("defensive_interceptions", line.defensive_interceptions),
+        pass_interceptions: rank("pass_interceptions", line.pass_interceptions),
+        damage_carrier: rank("damage_carrier", line.damage_carrier),
+    }
+}
+
+fn head_to_head(
+    player_one: Uuid,
+    player_two: Uuid,
+    matches: &HashMap<u32, PlayerlessMatchData>,
+    stats_by_match: &MatchStats,
+) -> HeadToHead {
+    let mut result = HeadToHead {
+        player_one,
+        player_two,
+        opposed: 0,
+        player_one_wins: 0,
+        player_two_wins: 0,
+        draws: 0,
+        together: 0,
+        together_wins: 0,
+    };
+    for (match_id, match_stats) in stats_by_match {
+        let (Some(one), Some(two), Some(data)) = (
+            match_stats.get(&player_one),
+            match_stats.get(&player_two),
+            matches.get(match_id),
+        ) else {
+            continue;
+        };
+        if one.team == two.team {
+            result.together += 1;
+            if data.winner == one.team {
+                result.together_wins += 1;
+            }
+        } else {
+            result.opposed += 1;
+            if data.winner == one.team {
+                result.player_one_wins += 1;
+            } else if data.winner == two.team {
+                result.player_two_wins += 1;
+            } else {
+                result.draws += 1;
+            }
+        }
+    }
+    result
+}
+
+/// Indexes are scored against everyone who played in `matches`, which are the matches of the
+/// players being compared.
+fn build_window(
+    players: &[Uuid],
+    matches: &HashMap<u32, PlayerlessMatchData>,
+    stats_by_match: &MatchStats,
+    username_map: &HashMap<Uuid, Option<String>>,
+    distributions: Option<&StatDistributions>,
+    (from, to): (Option<u64>, Option<u64>),
+) -> ComparisonWindow {
+    let durations: HashMap<u32, u32> = matches
+        .iter()
+        .map(|(id, data)| (*id, data.duration))
+        .collect();
+    let aggregates = aggregate_player_stats(stats_by_match, &durations, &HashMap::new());
+    let indexes: HashMap<Uuid, IndexScores> =
+        calculate_player_indexes(&aggregates).into_iter().collect();
+
+    let mut totals: HashMap<Uuid, StatLine> = HashMap::new();
+    let mut records: HashMap<Uuid, (u32, u32, u32)> = HashMap::new();
+    for (match_id, match_stats) in stats_by_match {
+        let winner = matches.get(match_id).map_or(-1, |data| data.winner);
+        for (uuid, stats) in match_stats {
+            let total = totals.entry(*uuid).or_default();
+            *total = total.add(&StatLine::from(stats));
+            let (wins, losses, draws) = records.entry(*uuid).or_default();
+            match winner {
+                1 | 2 if winner == stats.team => *wins += 1,
+                1 | 2 => *losses += 1,
+                _ => *draws += 1,
+            }
+        }
+    }
+    let per_match: HashMap<Uuid, StatLine> = totals
+        .into_iter()
+        .map(|(uuid, total)| {
+            let games = aggregates.get(&uuid).map_or(1, |(_, games, _)| *games);
+            (uuid, total.scale(1.0 / f64::from(games.max(1))))
+        })
+        .collect();
+
+    ComparisonWindow {
+        from,
+        to,
+        players: players
+            .iter()
+            .map(|uuid| {
+                let (stats, matches_played, time_played) =
+                    aggregates.get(uuid).cloned().unwrap_or_default();
+                let (wins, losses, draws) = records.get(uuid).copied().unwrap_or_default();
+                let line = per_match.get(uuid).cloned().unwrap_or_default();
+                ComparedPlayer {
+                    player: make_player_data(*uuid, username_map),
+                    matches_played,
+                    time_played,
+                    wins,
+                    losses,
+                    draws,
+                    stats,
+                    percentiles: match distributions {
+                        Some(distributions) if matches_played > 0 => {
+                            percentiles(&line, distributions)
+                        }
+                        _ => StatLine::default(),
+                    },
+                    per_match: line,
+                    indexes: indexes
+                        .get(uuid)
+                        .map(IndexScores::to_player_index_scores)
+                        .unwrap_or_default(),
+                }
+            })
+            .collect(),
+        head_to_head: players
+            .iter()
+            .enumerate()
+            .flat_map(|(i, one)| players[i + 1..].iter().map(move |two| (*one, *two)))
+            .map(|(one, two)| head_to_head(one, two, matches, stats_by_match))
+            .collect(),
+    }
+}
+
+/// Compares 2 to 6 players over their whole history and, when `from` or `to` (epoch
+/// milliseconds) is given, over that window of matches as well.
+#[openapi(tag = "Players")]
+#[get("/compare?<uuids>&<from>&<to>")]
+pub async fn compare_players(
+    uuids: &str,
+    from: Option<u64>,
+    to: Option<u64>,
+    state: &State<GhastApiState>,
+) -> Result<Json<PlayerComparisonApi>, Status> {
+    let mut players: Vec<Uuid> = Vec::new();
+    for uuid in uuids.split(',') {
+        let uuid = Uuid::parse_str(uuid.trim()).map_err(|_| Status::BadRequest)?;
+        if !players.contains(&uuid) {
+            players.push(uuid);
+        }
+    }
+    if !(MIN_COMPARED..=MAX_COMPARED).contains(&players.len()) {
+        return Err(Status::BadRequest);
+    }
+
+    let history = state
+        .database
+        .get_player_match_history(&players, None)
+        .await
+        .ok_or(Status::InternalServerError)?;
+    let mut match_ids: Vec<u32> = history
+        .values()
+        .flat_map(|lines| lines.iter().map(|(match_id, _)| *match_id))
+        .collect();
+    match_ids.sort_unstable();
+    match_ids.dedup();
+    let (matches, stats_by_match, username_map) = tokio::join!(
+        state.database.get_matches_by_ids(&match_ids),
+        state.database.get_player_stats_for_matches(&match_ids),
+        state.username_resolver.resolve_batch(players.clone()),
+    );
+    let model = state.win_probability.model();
+    let distributions = state.distributions.get();
+    let matches = matches.ok_or(Status::InternalServerError)?;
+    let stats_by_match = stats_by_match.ok_or(Status::InternalServerError)?;
+
+    let window = (from.is_some() || to.is_some()).then(|| {
+        let in_window = |data: &PlayerlessMatchData| {
+            from.is_none_or(|from| data.start_time >= from)
+                && to.is_none_or(|to| data.start_time <= to)
+        };
+        let window_matches: HashMap<u32, PlayerlessMatchData> = matches
+            .iter()
+            .filter(|(_, data)| in_window(data))
+            .map(|(id, data)| (*id, data.clone()))
+            .collect();
+        let window_stats: MatchStats = stats_by_match
+            .iter()
+            .filter(|(id, _)| window_matches.contains_key(id))
+            .map(|(id, stats)| (*id, stats.clone()))
+            .collect();
+        build_window(
+            &players,
+            &window_matches,
+            &window_stats,
+            &username_map,
+            distributions.as_deref(),
+            (from, to),
+        )
+    });
+
+    Ok(Json(PlayerComparisonApi {
+        lifetime: build_window(
+            &players,
+            &matches,
+            &stats_by_match,
+            &username_map,
+            distributions.as_deref(),
+            (None, None),
+        ),
+        window,
+        ratings: players
+            .iter()
+            .map(|uuid| PlayerRating {
+                player: make_player_data(*uuid, &username_map),
+                rating: model.as_ref().and_then(|model| model.rating(uuid)),
+            })
+            .collect(),
+    }))
+}
+
+/// Finds players by any name they have gone by, best match first and one result per player.
+#[openapi(tag = "Players")]
+#[get("/search?<q>&<limit>")]
+pub async fn search_players(
+    q: &str,
+    limit: Option<usize>,
+    state: &State<GhastApiState>,
+) -> Result<Json<Vec<PlayerSearchResult>>, Status> {
+    let query = q.trim().to_lowercase();
+    if query.is_empty() || query.chars().count() > MAX_QUERY_LENGTH {
+        return Err(Status::BadRequest);
+    }
+    let history = state
+        .database
+        .search_name_history(&query, MAX_SEARCH_RESULTS * SEARCH_CANDIDATES_PER_RESULT)
+        .await
+        .ok_or(Status::InternalServerError)?;
+
+    let mut best: HashMap<Uuid, (usize, PlayerNameRecord)> = HashMap::new();
+    for record in history {
+        let Some(score) = match_score(&query, &record.name) else {
+            continue;
+        };
+        match best.get(&record.uuid) {
+            Some((best_score, best_record))
+                if (*best_score, std::cmp::Reverse(best_record.last_seen))
+                    <= (score, std::cmp::Reverse(record.last_seen)) => {}
+            _ => {
+                best.insert(record.uuid, (score, record));
+            }
+        }
+    }
+    let mut matches: Vec<(usize, PlayerNameRecord)> = best.into_values().collect();
+    matches.sort_by(|(a_score, a), (b_score, b)| {
+        a_score
+            .cmp(b_score)
+            .then_with(|| b.last_seen.cmp(&a.last_seen))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    matches.truncate(
+        limit
+            .unwrap_or(DEFAULT_SEARCH_RESULTS)
+            .min(MAX_SEARCH_RESULTS),
+    );
+
+    let username_map = state
+        .username_resolver
+        .resolve_batch(matches.iter().map(|(_, record)| record.uuid).collect())
+        .await;
+    Ok(Json(
+        matches
+            .into_iter()
+            .map(|(_, record)| {
+                let player = make_player_data(record.uuid, &username_map);
+                PlayerSearchResult {
+                    current: player.username == record.name,
+                    player,
+                    alias: record.name,
+                    first_seen: record.first_seen,
+                    last_seen: record.last_seen,
+                }
+            })
+            .collect(),
+    ))
+}
+
+pub fn mount(rocket_build: Rocket<Build>, spec: &mut OpenApi) -> Rocket<Build> {
+    mount_versioned(
+        rocket_build,
+        spec,
+        "/players",
+        openapi_get_routes_spec![compare_players, search_players],
+    )
+}
@@ -0,0 +1,13 @@
+use crate::web::rate_limit::{LIMITED_PATH, RetryAfter};
+use crate::web::request_id::traced;
+use crate::web::responders::TooManyRequests;
+use rocket::{Build, Rocket, get, routes};
+
+#[get("/")]
+pub fn rate_limited(retry_after: RetryAfter) -> TooManyRequests {
+    TooManyRequests(retry_after.0)
+}
+
+pub fn mount(rocket_build: Rocket<Build>) -> Rocket<Build> {
+    rocket_build.mount(LIMITED_PATH, traced(routes![rate_limited]))
+}
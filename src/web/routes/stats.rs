@@ -0,0 +1,107 @@
+use crate::web::api::GhastApiState;
+use crate::web::types::{
+    Distribution, HistogramBin, PercentileValue, StatDistribution, StatDistributionsApi,
+};
+use crate::web::versions::mount_versioned;
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::{Build, Rocket, State, get};
+use rocket_okapi::okapi::openapi3::OpenApi;
+use rocket_okapi::{openapi, openapi_get_routes_spec};
+
+const DEFAULT_BINS: usize = 20;
+const MAX_BINS: usize = 200;
+
+/// Summarises an already sorted population. Percentiles are read off by nearest rank every five
+/// points, and the histogram splits the range into equal-width bins.
+fn describe(sorted: &[f64], bins: usize) -> Distribution {
+    let (Some(&min), Some(&max)) = (sorted.first(), sorted.last()) else {
+        return Distribution {
+            count: 0,
+            mean: 0.0,
+            stddev: 0.0,
+            min: 0.0,
+            max: 0.0,
+            percentiles: Vec::new(),
+            histogram: Vec::new(),
+        };
+    };
+    let n = sorted.len() as f64;
+    let mean = sorted.iter().sum::<f64>() / n;
+    let variance = sorted.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+
+    let percentiles = (0..=100)
+        .step_by(5)
+        .map(|percentile| {
+            let rank = ((f64::from(percentile) / 100.0) * (n - 1.0)).round() as usize;
+            PercentileValue {
+                percentile,
+                value: sorted[rank],
+            }
+        })
+        .collect();
+
+    let width = (max - min) / bins as f64;
+    let mut counts = vec![0u32; bins];
+    for value in sorted {
+        let bin = if width > 0.0 {
+            (((value - min) / width) as usize).min(bins - 1)
+        } else {
+            0
+        };
+        counts[bin] += 1;
+    }
+    let histogram = counts
+        .into_iter()
+        .enumerate()
+        .map(|(i, count)| HistogramBin {
+            start: (i as f64).mul_add(width, min),
+            end: ((i + 1) as f64).mul_add(width, min),
+            count,
+        })
+        .collect();
+
+    Distribution {
+        count: sorted.len() as u32,
+        mean,
+        stddev: variance.sqrt(),
+        min,
+        max,
+        percentiles,
+        histogram,
+    }
+}
+
+/// Answers 503 until the population has been loaded for the first time.
+#[openapi(tag = "Stats")]
+#[get("/distributions?<bins>")]
+pub async fn get_distributions(
+    bins: Option<usize>,
+    state: &State<GhastApiState>,
+) -> Result<Json<StatDistributionsApi>, Status> {
+    let bins = bins.unwrap_or(DEFAULT_BINS).clamp(1, MAX_BINS);
+    let distributions = state
+        .distributions
+        .get()
+        .ok_or(Status::ServiceUnavailable)?;
+    Ok(Json(
+        distributions
+            .populations
+            .iter()
+            .map(|population| StatDistribution {
+                stat: String::from(population.name),
+                per_game: describe(&population.per_game, bins),
+                per_minute: describe(&population.per_minute, bins),
+            })
+            .collect(),
+    ))
+}
+
+pub fn mount(rocket_build: Rocket<Build>, spec: &mut OpenApi) -> Rocket<Build> {
+    mount_versioned(
+        rocket_build,
+        spec,
+        "/stats",
+        openapi_get_routes_spec![get_distributions],
+    )
+}
@@ -1,172 +1,27 @@
 use crate::db::model::player_match_stats::PlayerMatchStats;
 use crate::db::model::tournament::{TournamentMatchMapping, TournamentTeam, TournamentTeamPlayer};
 use crate::web::api::GhastApiState;
+use crate::web::guards::{ApiKey, TournamentAdminScope};
+use crate::web::responders::{Cached, Csv};
+use crate::web::routes::indexes::{IndexScores, aggregate_player_stats, calculate_player_indexes};
+use crate::web::routes::utils::make_player_data;
 use crate::web::types::{
-    PlayerIndexScores, TournamentAggregateStats, TournamentDetailedResponse, TournamentListApi,
-    TournamentListResponse, TournamentMatchResponse, TournamentMvpResponse, TournamentPlayerInfo,
-    TournamentPlayerWithStats, TournamentTeamResponse,
+    PlayerData, SetTournamentWinnerRequest, TournamentAggregateStats, TournamentDetailedResponse,
+    TournamentListApi, TournamentListResponse, TournamentMatchResponse, TournamentMvpResponse,
+    TournamentPlayerCsvRow, TournamentPlayerWithStats, TournamentTeamResponse,
 };
+use crate::web::types_v0;
+use crate::web::versions::{mount_v0, mount_v1};
+use log::info;
+use rocket::http::Status;
+use rocket::http::uri::Origin;
 use rocket::serde::json::Json;
-use rocket::{Build, Rocket, State, get, routes};
+use rocket::{Build, Rocket, State, get, put, routes};
+use rocket_okapi::okapi::openapi3::OpenApi;
+use rocket_okapi::{openapi, openapi_get_routes_spec};
 use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
-fn make_player_info(
-    uuid: &Uuid,
-    username_map: &HashMap<Uuid, Option<String>>,
-) -> TournamentPlayerInfo {
-    TournamentPlayerInfo {
-        uuid: uuid.to_string(),
-        username: username_map
-            .get(uuid)
-            .cloned()
-            .flatten()
-            .unwrap_or_else(|| String::from("Unknown")),
-    }
-}
-
-fn add_stats(agg: &TournamentAggregateStats, stats: &PlayerMatchStats) -> TournamentAggregateStats {
-    TournamentAggregateStats {
-        assists: agg.assists + stats.assists,
-        catches: agg.catches + stats.catches,
-        damage_carrier: agg.damage_carrier + stats.damage_carrier,
-        damage_dealt: agg.damage_dealt + stats.damage_dealt,
-        damage_taken: agg.damage_taken + stats.damage_taken,
-        deaths: agg.deaths + stats.deaths,
-        defensive_interceptions: agg.defensive_interceptions + stats.defensive_interceptions,
-        kills: agg.kills + stats.kills,
-        killstreak: agg.killstreak.max(stats.killstreak),
-        pass_interceptions: agg.pass_interceptions + stats.pass_interceptions,
-        passes: agg.passes + stats.passes,
-        passing_blocks: agg.passing_blocks + stats.passing_blocks,
-        pickups: agg.pickups + stats.pickups,
-        receive_blocks: agg.receive_blocks + stats.receive_blocks,
-        strips: agg.strips + stats.strips,
-        throws: agg.throws + stats.throws,
-        touchdown_passes: agg.touchdown_passes + stats.touchdown_passes,
-        touchdowns: agg.touchdowns + stats.touchdowns,
-        ..*agg
-    }
-}
-
-struct WeightedScores {
-    passing: f64,
-    receiving: f64,
-    defense: f64,
-    pvp: f64,
-}
-
-impl WeightedScores {
-    fn from_aggregate(agg: &TournamentAggregateStats) -> Self {
-        Self {
-            passing: 0.03f64.mul_add(
-                f64::from(agg.passing_blocks),
-                11.0f64.mul_add(
-                    f64::from(agg.touchdown_passes),
-                    -1.4 * f64::from(agg.pass_interceptions),
-                ),
-            ),
-            receiving: 0.09f64.mul_add(
-                f64::from(agg.catches),
-                0.03f64.mul_add(
-                    f64::from(agg.receive_blocks),
-                    11.0 * f64::from(agg.touchdowns),
-                ),
-            ),
-            defense: 6.0f64.mul_add(
-                f64::from(agg.defensive_interceptions),
-                6.0f64.mul_add(f64::from(agg.strips), 0.055 * f64::from(agg.damage_carrier)),
-            ),
-            pvp: 0.085f64.mul_add(f64::from(agg.kills), 0.015 * agg.damage_dealt),
-        }
-    }
-
-    fn zero() -> Self {
-        Self {
-            passing: 0.0,
-            receiving: 0.0,
-            defense: 0.0,
-            pvp: 0.0,
-        }
-    }
-
-    #[inline]
-    fn offense(&self) -> f64 {
-        self.passing + self.receiving
-    }
-
-    #[inline]
-    fn total(&self) -> f64 {
-        self.offense() + self.defense + self.pvp
-    }
-}
-
-struct RatingScores {
-    offense: f64,
-    passing: f64,
-    receiving: f64,
-    defense: f64,
-    pvp: f64,
-    total: f64,
-}
-
-impl RatingScores {
-    fn from_weighted(weighted: &WeightedScores, games: u32, avg: &WeightedScores) -> Self {
-        let gp = f64::from(games);
-        let bayesian = |w: f64, avg_w: f64| w.mul_add(gp, avg_w * 2.0) / (gp + 2.0);
-
-        Self {
-            offense: bayesian(weighted.offense(), avg.offense()),
-            passing: bayesian(weighted.passing, avg.passing),
-            receiving: bayesian(weighted.receiving, avg.receiving),
-            defense: bayesian(weighted.defense, avg.defense),
-            pvp: bayesian(weighted.pvp, avg.pvp),
-            total: bayesian(weighted.total(), avg.total()),
-        }
-    }
-}
-
-struct IndexScores {
-    offense: f64,
-    passing: f64,
-    receiving: f64,
-    defense: f64,
-    pvp: f64,
-    total: f64,
-}
-
-impl IndexScores {
-    fn from_rating(rating: &RatingScores, avg_weighted: &WeightedScores) -> Self {
-        let safe_div = |val: f64, divisor: f64| {
-            if divisor.abs() > 0.001 {
-                val / divisor
-            } else {
-                0.0
-            }
-        };
-
-        Self {
-            offense: safe_div(rating.offense, avg_weighted.offense()),
-            passing: safe_div(rating.passing, avg_weighted.passing),
-            receiving: safe_div(rating.receiving, avg_weighted.receiving),
-            defense: safe_div(rating.defense, avg_weighted.defense),
-            pvp: safe_div(rating.pvp, avg_weighted.pvp),
-            total: safe_div(rating.total, avg_weighted.total()),
-        }
-    }
-
-    fn to_player_index_scores(&self) -> PlayerIndexScores {
-        PlayerIndexScores {
-            offense: self.offense,
-            passing: self.passing,
-            receiving: self.receiving,
-            defense: self.defense,
-            pvp: self.pvp,
-            total: self.total,
-        }
-    }
-}
-
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 enum Award {
     Mvp,
@@ -199,77 +54,17 @@ impl Award {
     }
 }
 
-struct AwardWinners {
-    mvp: Option<Uuid>,
-    opot: Option<Uuid>,
-    dpot: Option<Uuid>,
-    oldl: Option<Uuid>,
-    passer: Option<Uuid>,
-    receiver: Option<Uuid>,
-    all_tournament: Vec<Uuid>,
-}
-
-fn aggregate_player_stats(
-    stats_by_match: &HashMap<u32, HashMap<Uuid, PlayerMatchStats>>,
-    match_duration_map: &HashMap<u32, u32>,
-    player_team_map: &HashMap<Uuid, i32>,
-) -> HashMap<Uuid, (TournamentAggregateStats, u32, u32)> {
-    stats_by_match
-        .iter()
-        .flat_map(|(match_id, match_stats)| {
-            let duration = match_duration_map.get(match_id).copied().unwrap_or(0);
-            match_stats
-                .iter()
-                .map(move |(uuid, stats)| (uuid, stats, duration))
-        })
-        .fold(HashMap::new(), |mut acc, (uuid, stats, duration)| {
-            let team_id = player_team_map.get(uuid).copied().unwrap_or(0);
-            let (current_agg, count, time) = acc.get(uuid).cloned().unwrap_or_else(|| {
-                (
-                    TournamentAggregateStats {
-                        team: team_id,
-                        ..Default::default()
-                    },
-                    0,
-                    0,
-                )
-            });
-            acc.insert(
-                *uuid,
-                (add_stats(&current_agg, stats), count + 1, time + duration),
-            );
-            acc
-        })
-}
-
-fn calculate_player_indexes(
-    player_aggregates: &HashMap<Uuid, (TournamentAggregateStats, u32, u32)>,
-) -> Vec<(Uuid, IndexScores)> {
-    let player_weighted: Vec<(Uuid, WeightedScores, u32)> = player_aggregates
-        .iter()
-        .map(|(uuid, (agg, games, _time))| (*uuid, WeightedScores::from_aggregate(agg), *games))
-        .collect();
-
-    let n = player_weighted.len() as f64;
-    let avg_weighted = player_weighted
-        .iter()
-        .fold(WeightedScores::zero(), |acc, (_, w, _)| WeightedScores {
-            passing: acc.passing + w.passing / n,
-            receiving: acc.receiving + w.receiving / n,
-            defense: acc.defense + w.defense / n,
-            pvp: acc.pvp + w.pvp / n,
-        });
-
-    player_weighted
-        .iter()
-        .map(|(uuid, w, games)| {
-            let rating = RatingScores::from_weighted(w, *games, &avg_weighted);
-            (*uuid, IndexScores::from_rating(&rating, &avg_weighted))
-        })
-        .collect()
+pub struct AwardWinners {
+    pub mvp: Option<Uuid>,
+    pub opot: Option<Uuid>,
+    pub dpot: Option<Uuid>,
+    pub oldl: Option<Uuid>,
+    pub passer: Option<Uuid>,
+    pub receiver: Option<Uuid>,
+    pub all_tournament: Vec<Uuid>,
 }
 
-fn determine_awards(player_indexes: &[(Uuid, IndexScores)]) -> AwardWinners {
+pub fn determine_awards(player_indexes: &[(Uuid, IndexScores)]) -> AwardWinners {
     let (award_map, _) = Award::PRIORITY_ORDER.iter().fold(
         (HashMap::with_capacity(6), HashSet::with_capacity(6)),
         |(mut map, mut awarded), &award| {
@@ -303,17 +98,40 @@ fn determine_awards(player_indexes: &[(Uuid, IndexScores)]) -> AwardWinners {
     }
 }
 
+/// Summed stats, matches played and seconds played, by player.
+type PlayerAggregates = HashMap<Uuid, (TournamentAggregateStats, u32, u32)>;
+
+/// Each player's totals over the tournament's matches, with the index scores they lead to.
+/// Stats for players missing from the rosters still count, under team 0.
+pub fn score_players(
+    matches: &[TournamentMatchMapping],
+    team_players: &[TournamentTeamPlayer],
+    stats_by_match: &HashMap<u32, HashMap<Uuid, PlayerMatchStats>>,
+) -> (PlayerAggregates, Vec<(Uuid, IndexScores)>) {
+    let match_duration_map: HashMap<u32, u32> =
+        matches.iter().map(|m| (m.match_id, m.duration)).collect();
+    let player_team_map: HashMap<Uuid, i32> = team_players
+        .iter()
+        .map(|p| (p.player_uuid, p.team_id))
+        .collect();
+
+    let player_aggregates =
+        aggregate_player_stats(stats_by_match, &match_duration_map, &player_team_map);
+    let player_indexes = calculate_player_indexes(&player_aggregates);
+    (player_aggregates, player_indexes)
+}
+
 fn generate_team_response(
     teams: &[TournamentTeam],
     team_players: &[TournamentTeamPlayer],
-    player_aggregates: &HashMap<Uuid, (TournamentAggregateStats, u32, u32)>,
+    player_aggregates: &PlayerAggregates,
     player_index_map: &HashMap<Uuid, &IndexScores>,
     username_map: &HashMap<Uuid, Option<String>>,
 ) -> Vec<TournamentTeamResponse> {
     teams
         .iter()
         .map(|team| {
-            let captain = make_player_info(&team.captain_uuid, username_map);
+            let captain = make_player_data(team.captain_uuid, username_map);
             let team_name = format!("Team {}", captain.username);
 
             let players: Vec<TournamentPlayerWithStats> = team_players
@@ -341,7 +159,7 @@ fn generate_team_response(
                         .unwrap_or_default();
 
                     TournamentPlayerWithStats {
-                        uuid: p.player_uuid.to_string(),
+                        uuid: p.player_uuid,
                         username: username_map
                             .get(&p.player_uuid)
                             .cloned()
@@ -385,15 +203,7 @@ fn generate_mvp_response(
     awards: &AwardWinners,
     username_map: &HashMap<Uuid, Option<String>>,
 ) -> TournamentMvpResponse {
-    let to_player = |uuid: Option<Uuid>| {
-        uuid.map_or_else(
-            || TournamentPlayerInfo {
-                uuid: String::new(),
-                username: String::from("Unknown"),
-            },
-            |u| make_player_info(&u, username_map),
-        )
-    };
+    let to_player = |uuid: Option<Uuid>| uuid.map(|uuid| make_player_data(uuid, username_map));
 
     TournamentMvpResponse {
         mvp: to_player(awards.mvp),
@@ -405,11 +215,12 @@ fn generate_mvp_response(
     }
 }
 
-#[get("/all")]
-pub async fn get_all_tournaments(state: &State<GhastApiState>) -> Json<TournamentListApi> {
-    let Some(tournaments) = state.database.get_tournaments_all().await else {
-        return Json(Vec::new());
-    };
+async fn all_tournaments(state: &State<GhastApiState>) -> Result<TournamentListApi, Status> {
+    let tournaments = state
+        .database
+        .get_tournaments_all()
+        .await
+        .ok_or(Status::ServiceUnavailable)?;
 
     let all_captain_uuids: Vec<Uuid> = tournaments
         .iter()
@@ -418,19 +229,19 @@ pub async fn get_all_tournaments(state: &State<GhastApiState>) -> Json<Tournamen
         .into_iter()
         .collect();
 
-    let username_map = {
-        let lock = state.username_resolver.lock().await;
-        lock.resolve_batch(all_captain_uuids).await
-    };
+    let username_map = state
+        .username_resolver
+        .resolve_batch(all_captain_uuids)
+        .await;
 
-    let response: Vec<TournamentListResponse> = tournaments
+    Ok(tournaments
         .into_iter()
         .map(|t| TournamentListResponse {
             id: t.id,
             captains: t
                 .captain_uuids
                 .iter()
-                .map(|uuid| make_player_info(uuid, &username_map))
+                .map(|uuid| make_player_data(*uuid, &username_map))
                 .collect(),
             date: t.date,
             match_count: t.match_count,
@@ -438,24 +249,33 @@ pub async fn get_all_tournaments(state: &State<GhastApiState>) -> Json<Tournamen
             player_count: t.player_count,
             winner_team_id: t.winner_team_id,
         })
-        .collect();
+        .collect())
+}
 
-    Json(response)
+#[openapi(tag = "Tournaments")]
+#[get("/all")]
+pub async fn get_all_tournaments(
+    uri: &Origin<'_>,
+    state: &State<GhastApiState>,
+) -> Result<Cached<Json<TournamentListApi>>, Status> {
+    state
+        .response_cache
+        .json(uri, all_tournaments(state))
+        .await
+        .map(Cached::from)
 }
 
-#[get("/<tournament_id>")]
-pub async fn get_tournament_by_id(
+async fn get_tournament_details(
     tournament_id: u32,
     state: &State<GhastApiState>,
-) -> Json<Option<TournamentDetailedResponse>> {
-    let Some(tournament) = state.database.get_tournament_by_id(tournament_id).await else {
-        return Json(None);
-    };
+) -> Option<TournamentDetailedResponse> {
+    let tournament = state.database.get_tournament_by_id(tournament_id).await?;
 
+    let tournament_ids = [tournament_id];
     let (teams, team_players, matches) = tokio::join!(
-        state.database.get_tournament_teams(tournament_id),
-        state.database.get_tournament_team_players(tournament_id),
-        state.database.get_tournament_matches(tournament_id),
+        state.database.get_tournament_teams(&tournament_ids),
+        state.database.get_tournament_team_players(&tournament_ids),
+        state.database.get_tournament_matches(&tournament_ids),
     );
 
     let teams = teams.unwrap_or_default();
@@ -463,22 +283,14 @@ pub async fn get_tournament_by_id(
     let matches = matches.unwrap_or_default();
 
     let match_ids: Vec<u32> = matches.iter().map(|m| m.match_id).collect();
-    let match_duration_map: HashMap<u32, u32> =
-        matches.iter().map(|m| (m.match_id, m.duration)).collect();
     let stats_by_match = state
         .database
         .get_player_stats_for_matches(&match_ids)
         .await
         .unwrap_or_default();
 
-    let player_team_map: HashMap<Uuid, i32> = team_players
-        .iter()
-        .map(|p| (p.player_uuid, p.team_id))
-        .collect();
-
-    let player_aggregates =
-        aggregate_player_stats(&stats_by_match, &match_duration_map, &player_team_map);
-    let player_indexes = calculate_player_indexes(&player_aggregates);
+    let (player_aggregates, player_indexes) =
+        score_players(&matches, &team_players, &stats_by_match);
     let player_index_map: HashMap<Uuid, &IndexScores> = player_indexes
         .iter()
         .map(|(uuid, idx)| (*uuid, idx))
@@ -499,10 +311,10 @@ pub async fn get_tournament_by_id(
         .chain(awards.all_tournament.iter().copied())
         .collect();
 
-    let username_map = {
-        let lock = state.username_resolver.lock().await;
-        lock.resolve_batch(all_uuids.into_iter().collect()).await
-    };
+    let username_map = state
+        .username_resolver
+        .resolve_batch(all_uuids.into_iter().collect())
+        .await;
 
     let team_responses = generate_team_response(
         &teams,
@@ -514,13 +326,13 @@ pub async fn get_tournament_by_id(
     let match_responses = generate_match_response(&matches);
     let mvp_response = generate_mvp_response(&awards, &username_map);
 
-    let all_tournament_response: Vec<TournamentPlayerInfo> = awards
+    let all_tournament_response: Vec<PlayerData> = awards
         .all_tournament
         .iter()
-        .map(|uuid| make_player_info(uuid, &username_map))
+        .map(|uuid| make_player_data(*uuid, &username_map))
         .collect();
 
-    Json(Some(TournamentDetailedResponse {
+    Some(TournamentDetailedResponse {
         all_tournament: all_tournament_response,
         date: tournament.date,
         matches: match_responses,
@@ -528,12 +340,164 @@ pub async fn get_tournament_by_id(
         name: tournament.name,
         teams: team_responses,
         winner_team_id: tournament.winner_team_id,
-    }))
+    })
+}
+
+#[openapi(tag = "Tournaments")]
+#[get("/<tournament_id>")]
+pub async fn get_tournament_by_id(
+    tournament_id: u32,
+    uri: &Origin<'_>,
+    state: &State<GhastApiState>,
+) -> Result<Cached<Json<TournamentDetailedResponse>>, Status> {
+    state
+        .response_cache
+        .json(uri, async {
+            get_tournament_details(tournament_id, state)
+                .await
+                .ok_or(Status::NotFound)
+        })
+        .await
+        .map(Cached::from)
+}
+
+async fn tournament_stats_rows(
+    tournament_id: u32,
+    state: &State<GhastApiState>,
+) -> Option<Vec<TournamentPlayerCsvRow>> {
+    let details = get_tournament_details(tournament_id, state).await?;
+    Some(
+        details
+            .teams
+            .into_iter()
+            .flat_map(|team| team.players)
+            .map(From::from)
+            .collect(),
+    )
+}
+
+#[openapi(tag = "Tournaments")]
+#[get("/<tournament_id>/stats.csv")]
+pub async fn get_tournament_stats_csv(
+    tournament_id: u32,
+    uri: &Origin<'_>,
+    state: &State<GhastApiState>,
+) -> Result<Cached<Csv<TournamentPlayerCsvRow>>, Status> {
+    state
+        .response_cache
+        .csv(uri, async {
+            tournament_stats_rows(tournament_id, state)
+                .await
+                .map(Csv)
+                .ok_or(Status::NotFound)
+        })
+        .await
+        .map(Cached::from)
+}
+
+/// Records which team won, which is what the awards announcement waits for. 404 if there's no such
+/// tournament or the team didn't play in it.
+#[openapi(tag = "Tournaments")]
+#[put("/<tournament_id>/winner", data = "<request>")]
+pub async fn set_tournament_winner(
+    tournament_id: u32,
+    request: Json<SetTournamentWinnerRequest>,
+    admin: ApiKey<TournamentAdminScope>,
+    state: &State<GhastApiState>,
+) -> Status {
+    match state
+        .database
+        .set_tournament_winner(tournament_id, request.winner_team_id)
+        .await
+    {
+        Some(true) => {
+            info!(
+                "Tournament {tournament_id} won by team {} according to {:?}",
+                request.winner_team_id, admin.key_id
+            );
+            Status::NoContent
+        }
+        Some(false) => Status::NotFound,
+        None => Status::InternalServerError,
+    }
 }
 
-pub fn mount(rocket_build: Rocket<Build>) -> Rocket<Build> {
-    rocket_build.mount(
+#[get("/all")]
+pub async fn get_all_tournaments_v0(
+    uri: &Origin<'_>,
+    state: &State<GhastApiState>,
+) -> Result<Cached<Json<types_v0::TournamentListApi>>, Status> {
+    state
+        .response_cache
+        .json(uri, async {
+            Ok(all_tournaments(state)
+                .await?
+                .into_iter()
+                .map(From::from)
+                .collect::<types_v0::TournamentListApi>())
+        })
+        .await
+        .map(Cached::from)
+}
+
+#[get("/<tournament_id>")]
+pub async fn get_tournament_by_id_v0(
+    tournament_id: u32,
+    uri: &Origin<'_>,
+    state: &State<GhastApiState>,
+) -> Result<Cached<Json<types_v0::TournamentDetailedResponse>>, Status> {
+    state
+        .response_cache
+        .json(uri, async {
+            get_tournament_details(tournament_id, state)
+                .await
+                .map(types_v0::TournamentDetailedResponse::from)
+                .ok_or(Status::NotFound)
+        })
+        .await
+        .map(Cached::from)
+}
+
+#[get("/<tournament_id>/stats.csv")]
+pub async fn get_tournament_stats_csv_v0(
+    tournament_id: u32,
+    uri: &Origin<'_>,
+    state: &State<GhastApiState>,
+) -> Result<Cached<Csv<types_v0::TournamentPlayerCsvRow>>, Status> {
+    state
+        .response_cache
+        .csv(uri, async {
+            let rows = tournament_stats_rows(tournament_id, state)
+                .await
+                .ok_or(Status::NotFound)?;
+            Ok(Csv(rows
+                .into_iter()
+                .map(From::from)
+                .collect::<Vec<types_v0::TournamentPlayerCsvRow>>()))
+        })
+        .await
+        .map(Cached::from)
+}
+
+pub fn mount(rocket_build: Rocket<Build>, spec: &mut OpenApi) -> Rocket<Build> {
+    let rocket_build = mount_v0(
+        rocket_build,
+        "/tournaments",
+        routes![
+            get_all_tournaments_v0,
+            get_tournament_by_id_v0,
+            get_tournament_stats_csv_v0
+        ],
+    );
+    mount_v1(
+        rocket_build,
+        spec,
         "/tournaments",
-        routes![get_all_tournaments, get_tournament_by_id],
+        openapi_get_routes_spec![
+            get_all_tournaments,
+            get_tournament_by_id,
+            get_tournament_stats_csv,
+            set_tournament_winner
+        ],
     )
 }
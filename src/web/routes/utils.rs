@@ -1,26 +1,30 @@
 use crate::db::model::match_data::{MatchData, PlayerlessMatchData};
+use crate::db::model::match_event::MatchEvent;
 use crate::web::api::GhastApiState;
+use crate::web::routes::indexes::{
+    IndexScores, add_stats, aggregate_player_stats, calculate_player_indexes,
+};
 use crate::web::types::{
-    MatchApi, MatchPlayer, MatchPlayerApi, MatchResponse, PlayerData, UberApi,
+    CareerComparison, MatchApi, MatchEventResponse, MatchPlayer, MatchPlayerApi, MatchResponse,
+    PlayerData, StatLine, TournamentAggregateStats, UberApi, UberPlayer, UberTeamTotals,
 };
 use rocket::State;
-use rocket::serde::json::Json;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use uuid::Uuid;
 
 pub async fn get_matches(
     state: &State<GhastApiState>,
     matches: HashMap<u32, MatchData>,
-) -> Json<MatchApi> {
+) -> MatchApi {
     let all_players: HashSet<Uuid> = matches
         .values()
         .flat_map(|data| data.players.iter().copied())
         .collect();
-    let all_username_map = {
-        let lock = state.username_resolver.lock().await;
-        lock.resolve_batch(all_players.into_iter().collect()).await
-    };
-    let formed_matches = matches
+    let all_username_map = state
+        .username_resolver
+        .resolve_batch(all_players.into_iter().collect())
+        .await;
+    matches
         .into_iter()
         .map(|(id, data)| {
             let player_data = data
@@ -55,9 +59,7 @@ pub async fn get_matches(
                 players: player_data,
             }
         })
-        .collect();
-
-    Json(formed_matches)
+        .collect()
 }
 
 pub async fn get_match_player_stats(
@@ -66,10 +68,12 @@ pub async fn get_match_player_stats(
 ) -> Option<MatchPlayerApi> {
     let matches = state.database.get_player_match_stats(match_id).await?;
     let keys = matches.keys().copied().collect::<Vec<_>>();
-    let username_map = {
-        let lock = state.username_resolver.lock().await;
-        lock.resolve_batch(keys).await
-    };
+    let (username_map, data) = tokio::join!(
+        state.username_resolver.resolve_batch(keys),
+        state.database.get_match_by_id(match_id),
+    );
+    let duration = data.map_or(0, |data| data.duration);
+    let distributions = state.distributions.get();
 
     let match_player_stats = matches
         .into_iter()
@@ -79,7 +83,15 @@ pub async fn get_match_player_stats(
                 .cloned()
                 .flatten()
                 .unwrap_or_else(|| String::from("Unknown")),
-            uuid: uuid.to_string(),
+            uuid,
+            percentiles: distributions
+                .as_ref()
+                .map(|d| d.percentile_ranks(&stats))
+                .unwrap_or_default(),
+            per_minute_percentiles: distributions
+                .as_ref()
+                .map(|d| d.per_minute_percentile_ranks(&stats, duration))
+                .unwrap_or_default(),
             stats,
         })
         .collect();
@@ -89,10 +101,157 @@ pub async fn get_match_player_stats(
 
 pub async fn get_uber_data(match_id: u32, state: &State<GhastApiState>) -> Option<UberApi> {
     let data = state.database.get_match_by_id(match_id).await?;
-    let players = get_match_player_stats(match_id, state).await;
+    let stats = state
+        .database
+        .get_player_match_stats(match_id)
+        .await
+        .unwrap_or_default();
+    let uuids: Vec<Uuid> = stats.keys().copied().collect();
+    let (username_map, career_totals) = tokio::join!(
+        state.username_resolver.resolve_batch(uuids.clone()),
+        state.database.get_player_career_totals(&uuids),
+    );
+    let distributions = state.distributions.get();
+    let career_totals = career_totals.unwrap_or_default();
+
+    let minutes = f64::from(data.duration) / 60.0;
+    let per_minute = |line: &StatLine| {
+        if minutes > 0.0 {
+            line.scale(1.0 / minutes)
+        } else {
+            StatLine::default()
+        }
+    };
+
+    let team_map: HashMap<Uuid, i32> = stats.iter().map(|(uuid, s)| (*uuid, s.team)).collect();
+    let aggregates = aggregate_player_stats(
+        &HashMap::from([(match_id, stats.clone())]),
+        &HashMap::from([(match_id, data.duration)]),
+        &team_map,
+    );
+    let indexes: HashMap<Uuid, IndexScores> =
+        calculate_player_indexes(&aggregates).into_iter().collect();
+    let mvp = indexes
+        .iter()
+        .max_by(|a, b| a.1.total.total_cmp(&b.1.total))
+        .map(|(uuid, _)| make_player_data(*uuid, &username_map));
+
+    let mut team_totals: BTreeMap<i32, (TournamentAggregateStats, StatLine)> = BTreeMap::new();
+    for player_stats in stats.values() {
+        let (totals, line) = team_totals.entry(player_stats.team).or_insert_with(|| {
+            (
+                TournamentAggregateStats {
+                    team: player_stats.team,
+                    ..Default::default()
+                },
+                StatLine::default(),
+            )
+        });
+        *totals = add_stats(totals, player_stats);
+        *line = line.add(&StatLine::from(player_stats));
+    }
+
+    let mut players: Vec<UberPlayer> = stats
+        .into_iter()
+        .map(|(uuid, player_stats)| {
+            let line = StatLine::from(&player_stats);
+            // The career totals include this match, so take it back out to compare against
+            // every other game they've played.
+            let career = career_totals
+                .get(&uuid)
+                .filter(|(matches, _)| *matches > 1)
+                .map(|(matches, totals)| {
+                    let previous = matches - 1;
+                    let averages = StatLine::from(totals)
+                        .subtract(&line)
+                        .scale(1.0 / f64::from(previous));
+                    CareerComparison {
+                        matches: previous,
+                        deltas: line.subtract(&averages),
+                        averages,
+                    }
+                });
+
+            UberPlayer {
+                per_minute: per_minute(&line),
+                indexes: indexes
+                    .get(&uuid)
+                    .map(IndexScores::to_player_index_scores)
+                    .unwrap_or_default(),
+                career,
+                player: MatchPlayer {
+                    username: make_player_data(uuid, &username_map).username,
+                    uuid,
+                    percentiles: distributions
+                        .as_ref()
+                        .map(|d| d.percentile_ranks(&player_stats))
+                        .unwrap_or_default(),
+                    per_minute_percentiles: distributions
+                        .as_ref()
+                        .map(|d| d.per_minute_percentile_ranks(&player_stats, data.duration))
+                        .unwrap_or_default(),
+                    stats: player_stats,
+                },
+            }
+        })
+        .collect();
+    players.sort_by(|a, b| b.indexes.total.total_cmp(&a.indexes.total));
+
     Some(UberApi {
         id: match_id,
         data,
-        players: players.unwrap_or_default(),
+        players,
+        teams: team_totals
+            .into_iter()
+            .map(|(team, (totals, line))| UberTeamTotals {
+                team,
+                totals,
+                per_minute: per_minute(&line),
+            })
+            .collect(),
+        mvp,
     })
 }
+
+pub fn make_player_data(uuid: Uuid, username_map: &HashMap<Uuid, Option<String>>) -> PlayerData {
+    PlayerData {
+        uuid,
+        username: username_map
+            .get(&uuid)
+            .cloned()
+            .flatten()
+            .unwrap_or_else(|| String::from("Unknown")),
+    }
+}
+
+pub async fn resolve_event_usernames(
+    events: &[MatchEvent],
+    state: &State<GhastApiState>,
+) -> HashMap<Uuid, Option<String>> {
+    let uuids = events
+        .iter()
+        .flat_map(|event| [event.actor, event.target])
+        .flatten()
+        .collect::<BTreeSet<_>>();
+    state
+        .username_resolver
+        .resolve_batch(uuids.into_iter().collect())
+        .await
+}
+
+pub fn make_event_response(
+    event: &MatchEvent,
+    username_map: &HashMap<Uuid, Option<String>>,
+) -> MatchEventResponse {
+    MatchEventResponse {
+        sequence: event.sequence,
+        time: event.time,
+        kind: event.kind,
+        actor: event.actor.map(|uuid| make_player_data(uuid, username_map)),
+        target: event
+            .target
+            .map(|uuid| make_player_data(uuid, username_map)),
+        team: event.team,
+        location: event.location,
+    }
+}
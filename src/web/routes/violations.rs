@@ -0,0 +1,360 @@
+use crate::db::model::match_event::{MatchEvent, MatchEventKind};
+use crate::db::model::player_match_stats::PlayerMatchStats;
+use crate::web::api::GhastApiState;
+use crate::web::routes::utils::{make_event_response, make_player_data, resolve_event_usernames};
+use crate::web::types::{
+    MatchViolationsApi, OnePassViolation, PlayerViolationCount, PlayerViolationsApi,
+};
+use crate::web::versions::mount_versioned;
+use rocket::serde::json::Json;
+use rocket::{Build, Rocket, State, get};
+use rocket_okapi::okapi::openapi3::OpenApi;
+use rocket_okapi::{openapi, openapi_get_routes_spec};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// A touchdown scored without a completed pass, with the possession that led up to it.
+struct Violation<'a> {
+    touchdown: &'a MatchEvent,
+    possession: Vec<&'a MatchEvent>,
+}
+
+#[derive(Default)]
+struct Possession<'a> {
+    team: Option<i32>,
+    events: Vec<&'a MatchEvent>,
+    passed: bool,
+}
+
+/// Longest a touchdown can follow a strip and still count as scored by the strip, one server
+/// tick. The ball is only stripped that close to a touchdown inside the endzone.
+const STRIP_TOUCHDOWN_MS: u64 = 50;
+/// How far, horizontally, a strip can be from the touchdown it scored.
+const STRIP_TOUCHDOWN_REACH: f64 = 2.0;
+
+/// Whether `touchdown` was scored by the same player stripping the ball in the endzone, which
+/// is told apart from a strip elsewhere by the touchdown coming on the same tick and, where the
+/// server sent locations, in the same place.
+fn stripped_in_endzone(strip: &MatchEvent, touchdown: &MatchEvent) -> bool {
+    let same_tick = touchdown.time.saturating_sub(strip.time) <= STRIP_TOUCHDOWN_MS;
+    let same_place = match (strip.location, touchdown.location) {
+        (Some(from), Some(to)) => (from.x - to.x).hypot(from.z - to.z) <= STRIP_TOUCHDOWN_REACH,
+        _ => true,
+    };
+    strip.kind == MatchEventKind::Strip
+        && strip.actor.is_some()
+        && strip.actor == touchdown.actor
+        && same_tick
+        && same_place
+}
+
+/// Applies the One Pass Rule: a team has to complete a pass to a teammate during a possession
+/// before it may score. A touchdown straight off a strip in the endzone is the one exception.
+fn find_violations<'a>(
+    events: &'a [MatchEvent],
+    team_map: &HashMap<Uuid, i32>,
+) -> Vec<Violation<'a>> {
+    let team_of = |uuid: Option<Uuid>, event: &MatchEvent| {
+        uuid.and_then(|uuid| team_map.get(&uuid).copied())
+            .or(event.team)
+    };
+    let is_teammate_pass = |from: Option<Uuid>, to: Option<Uuid>, event: &MatchEvent| {
+        from.is_some() && to.is_some() && from != to && team_of(from, event) == team_of(to, event)
+    };
+
+    let mut violations = Vec::new();
+    let mut possession = Possession::default();
+    for event in events {
+        match event.kind {
+            MatchEventKind::Pickup
+            | MatchEventKind::Strip
+            | MatchEventKind::Interception
+            | MatchEventKind::Catch => {
+                let team = team_of(event.actor, event);
+                if team != possession.team {
+                    possession = Possession {
+                        team,
+                        ..Possession::default()
+                    };
+                }
+                // On a catch the target is the passer.
+                if event.kind == MatchEventKind::Catch
+                    && is_teammate_pass(event.target, event.actor, event)
+                {
+                    possession.passed = true;
+                }
+                possession.events.push(event);
+            }
+            MatchEventKind::Throw => possession.events.push(event),
+            MatchEventKind::Pass => {
+                if is_teammate_pass(event.actor, event.target, event) {
+                    possession.passed = true;
+                }
+                possession.events.push(event);
+            }
+            MatchEventKind::Touchdown => {
+                let team = team_of(event.actor, event);
+                let own_possession = team == possession.team;
+                let legal = own_possession
+                    && (possession.passed
+                        || possession
+                            .events
+                            .last()
+                            .is_some_and(|last| stripped_in_endzone(last, event)));
+                if !legal {
+                    violations.push(Violation {
+                        touchdown: event,
+                        possession: if own_possession {
+                            possession.events.clone()
+                        } else {
+                            Vec::new()
+                        },
+                    });
+                }
+                possession = Possession::default();
+            }
+            MatchEventKind::Kill => {}
+        }
+    }
+    violations
+}
+
+fn team_map(stats: HashMap<Uuid, PlayerMatchStats>) -> HashMap<Uuid, i32> {
+    stats
+        .into_iter()
+        .map(|(uuid, stats)| (uuid, stats.team))
+        .collect()
+}
+
+#[openapi(tag = "Violations")]
+#[get("/<match_id>/violations")]
+pub async fn get_violations(
+    match_id: u32,
+    state: &State<GhastApiState>,
+) -> Option<Json<MatchViolationsApi>> {
+    let events = state.database.get_match_events(match_id).await?;
+    let team_map = team_map(
+        state
+            .database
+            .get_player_match_stats(match_id)
+            .await
+            .unwrap_or_default(),
+    );
+    let violations = find_violations(&events, &team_map);
+    let username_map = resolve_event_usernames(&events, state).await;
+
+    let mut counts: HashMap<Uuid, u32> = HashMap::new();
+    for violation in &violations {
+        if let Some(scorer) = violation.touchdown.actor {
+            *counts.entry(scorer).or_default() += 1;
+        }
+    }
+    let mut players: Vec<PlayerViolationCount> = counts
+        .into_iter()
+        .map(|(uuid, violations)| PlayerViolationCount {
+            player: make_player_data(uuid, &username_map),
+            violations,
+        })
+        .collect();
+    players.sort_by(|a, b| {
+        b.violations
+            .cmp(&a.violations)
+            .then_with(|| a.player.username.cmp(&b.player.username))
+    });
+
+    Some(Json(MatchViolationsApi {
+        violations: violations
+            .into_iter()
+            .map(|violation| OnePassViolation {
+                touchdown: make_event_response(violation.touchdown, &username_map),
+                possession: violation
+                    .possession
+                    .into_iter()
+                    .map(|event| make_event_response(event, &username_map))
+                    .collect(),
+            })
+            .collect(),
+        players,
+    }))
+}
+
+#[openapi(tag = "Violations")]
+#[get("/<uuid>/violations")]
+pub async fn get_player_violations(
+    uuid: &str,
+    state: &State<GhastApiState>,
+) -> Option<Json<PlayerViolationsApi>> {
+    let uuid = Uuid::parse_str(uuid).ok()?;
+    let match_ids = state.database.get_player_touchdown_matches(uuid).await?;
+    let (Some(events_by_match), Some(mut stats_by_match)) = tokio::join!(
+        state.database.get_match_events_for(&match_ids),
+        state.database.get_player_stats_for_matches(&match_ids),
+    ) else {
+        return None;
+    };
+
+    let mut violations = 0;
+    let mut matches = Vec::new();
+    for match_id in match_ids {
+        let Some(events) = events_by_match.get(&match_id) else {
+            continue;
+        };
+        let team_map = team_map(stats_by_match.remove(&match_id).unwrap_or_default());
+        let in_match = find_violations(events, &team_map)
+            .iter()
+            .filter(|violation| violation.touchdown.actor == Some(uuid))
+            .count() as u32;
+        if in_match > 0 {
+            violations += in_match;
+            matches.push(match_id);
+        }
+    }
+
+    let username_map = state.username_resolver.resolve_batch(vec![uuid]).await;
+    Some(Json(PlayerViolationsApi {
+        player: make_player_data(uuid, &username_map),
+        violations,
+        matches,
+    }))
+}
+
+pub fn mount(rocket_build: Rocket<Build>, spec: &mut OpenApi) -> Rocket<Build> {
+    let rocket_build = mount_versioned(
+        rocket_build,
+        spec,
+        "/matches",
+        openapi_get_routes_spec![get_violations],
+    );
+    mount_versioned(
+        rocket_build,
+        spec,
+        "/players",
+        openapi_get_routes_spec![get_player_violations],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::model::match_event::EventLocation;
+
+    const RED: Uuid = Uuid::from_u128(1);
+    const RED_TEAMMATE: Uuid = Uuid::from_u128(2);
+    const BLUE: Uuid = Uuid::from_u128(3);
+
+    fn team_map() -> HashMap<Uuid, i32> {
+        HashMap::from([(RED, 1), (RED_TEAMMATE, 1), (BLUE, 2)])
+    }
+
+    fn event(
+        time: u64,
+        kind: MatchEventKind,
+        actor: Uuid,
+        target: Option<Uuid>,
+        location: Option<(f64, f64)>,
+    ) -> MatchEvent {
+        MatchEvent {
+            sequence: time as u32,
+            time,
+            kind,
+            actor: Some(actor),
+            target,
+            team: None,
+            location: location.map(|(x, z)| EventLocation { x, y: 64.0, z }),
+        }
+    }
+
+    fn violation_times(events: &[MatchEvent]) -> Vec<u64> {
+        find_violations(events, &team_map())
+            .iter()
+            .map(|violation| violation.touchdown.time)
+            .collect()
+    }
+
+    #[test]
+    fn touchdown_after_pass_is_legal() {
+        let events = [
+            event(1000, MatchEventKind::Pickup, RED, None, None),
+            event(2000, MatchEventKind::Pass, RED, Some(RED_TEAMMATE), None),
+            event(2500, MatchEventKind::Catch, RED_TEAMMATE, Some(RED), None),
+            event(9000, MatchEventKind::Touchdown, RED_TEAMMATE, None, None),
+        ];
+        assert!(violation_times(&events).is_empty());
+    }
+
+    #[test]
+    fn touchdown_without_pass_is_a_violation() {
+        let events = [
+            event(1000, MatchEventKind::Pickup, RED, None, None),
+            event(9000, MatchEventKind::Touchdown, RED, None, None),
+        ];
+        let violations = find_violations(&events, &team_map());
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].possession.len(), 1);
+    }
+
+    #[test]
+    fn touchdown_after_midfield_strip_is_a_violation() {
+        let events = [
+            event(1000, MatchEventKind::Pickup, BLUE, None, None),
+            event(
+                3000,
+                MatchEventKind::Strip,
+                RED,
+                Some(BLUE),
+                Some((0.0, 0.0)),
+            ),
+            event(
+                9000,
+                MatchEventKind::Touchdown,
+                RED,
+                None,
+                Some((0.0, 60.0)),
+            ),
+        ];
+        assert_eq!(violation_times(&events), vec![9000]);
+    }
+
+    #[test]
+    fn touchdown_off_endzone_strip_is_legal() {
+        let events = [
+            event(1000, MatchEventKind::Pickup, BLUE, None, None),
+            event(
+                8950,
+                MatchEventKind::Strip,
+                RED,
+                Some(BLUE),
+                Some((0.5, 60.0)),
+            ),
+            event(
+                9000,
+                MatchEventKind::Touchdown,
+                RED,
+                None,
+                Some((0.0, 60.0)),
+            ),
+        ];
+        assert!(violation_times(&events).is_empty());
+    }
+
+    #[test]
+    fn strip_far_from_touchdown_on_same_tick_is_a_violation() {
+        let events = [
+            event(
+                8950,
+                MatchEventKind::Strip,
+                RED,
+                Some(BLUE),
+                Some((0.0, 20.0)),
+            ),
+            event(
+                9000,
+                MatchEventKind::Touchdown,
+                RED,
+                None,
+                Some((0.0, 60.0)),
+            ),
+        ];
+        assert_eq!(violation_times(&events), vec![9000]);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::web::api::GhastApiState;
+use crate::web::routes::utils::get_matches;
+use crate::web::types::{
+    ExcitingMatchResponse, MatchWinProbabilityApi, WinPredictionApi, WinPredictionRequest,
+    WinProbabilityPoint,
+};
+use crate::web::versions::mount_versioned;
+use rocket::serde::json::Json;
+use rocket::{Build, Rocket, State, get, post};
+use rocket_okapi::okapi::openapi3::OpenApi;
+use rocket_okapi::{openapi, openapi_get_routes_spec};
+use std::collections::HashMap;
+
+#[openapi(tag = "Win probability")]
+#[post("/predict", data = "<lineups>")]
+pub async fn predict_lineups(
+    lineups: Json<WinPredictionRequest>,
+    state: &State<GhastApiState>,
+) -> Option<Json<WinPredictionApi>> {
+    let model = state.win_probability.model()?;
+    let prediction = model.predict(&lineups.team_one, &lineups.team_two);
+    Some(Json(WinPredictionApi {
+        team_one: prediction.team_one_probability,
+        team_two: 1.0 - prediction.team_one_probability,
+        team_one_rating: prediction.team_one_rating,
+        team_two_rating: prediction.team_two_rating,
+    }))
+}
+
+#[openapi(tag = "Win probability")]
+#[get("/<match_id>/win-probability")]
+pub async fn get_match_win_probability(
+    match_id: u32,
+    state: &State<GhastApiState>,
+) -> Option<Json<MatchWinProbabilityApi>> {
+    let model = state.win_probability.model()?;
+    let swing = model.swing_for(match_id)?;
+    Some(Json(MatchWinProbabilityApi {
+        pre_match: swing.pre_match,
+        timeline: swing
+            .points
+            .iter()
+            .map(|point| WinProbabilityPoint {
+                time: point.time,
+                team_one_score: point.team_one_score,
+                team_two_score: point.team_two_score,
+                team_one: point.team_one_probability,
+            })
+            .collect(),
+        excitement: swing.excitement,
+        comeback: swing.comeback,
+        from_events: swing.from_events,
+    }))
+}
+
+/// The matches most worth watching back, by how far the win probability swung. Only matches
+/// with an event log are ranked, the rest have no swings to speak of.
+#[openapi(tag = "Win probability")]
+#[get("/exciting?<limit>")]
+pub async fn get_exciting_matches(
+    limit: Option<usize>,
+    state: &State<GhastApiState>,
+) -> Option<Json<Vec<ExcitingMatchResponse>>> {
+    let model = state.win_probability.model()?;
+    let mut swings: Vec<_> = model
+        .swings()
+        .filter(|(_, swing)| swing.from_events)
+        .collect();
+    swings.sort_by(|(a_id, a), (b_id, b)| {
+        b.excitement
+            .total_cmp(&a.excitement)
+            .then_with(|| b_id.cmp(a_id))
+    });
+    swings.truncate(limit.unwrap_or(10));
+
+    let all_matches = state.database.get_matches_all().await?;
+    let matches = swings
+        .iter()
+        .filter_map(|(id, _)| Some((*id, all_matches.get(id)?.clone())))
+        .collect();
+    let mut responses: HashMap<u32, _> = get_matches(state, matches)
+        .await
+        .into_iter()
+        .map(|response| (response.id, response))
+        .collect();
+
+    Some(Json(
+        swings
+            .into_iter()
+            .filter_map(|(id, swing)| {
+                Some(ExcitingMatchResponse {
+                    match_response: responses.remove(&id)?,
+                    excitement: swing.excitement,
+                    comeback: swing.comeback,
+                })
+            })
+            .collect(),
+    ))
+}
+
+pub fn mount(rocket_build: Rocket<Build>, spec: &mut OpenApi) -> Rocket<Build> {
+    mount_versioned(
+        rocket_build,
+        spec,
+        "/matches",
+        openapi_get_routes_spec![
+            predict_lineups,
+            get_match_win_probability,
+            get_exciting_matches
+        ],
+    )
+}
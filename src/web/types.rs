@@ -1,22 +1,27 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
+use crate::db::model::api_key::ApiKeyRecord;
+use crate::db::model::match_event::{EventLocation, MatchEventKind};
 use crate::db::model::{match_data::PlayerlessMatchData, player_match_stats::PlayerMatchStats};
+use crate::util::api_keys::Scope;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct PlayerData {
     pub uuid: Uuid,
     pub username: String,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct MatchResponse {
     pub id: u32,
     pub data: PlayerlessMatchData,
     pub players: Vec<PlayerData>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct PlayerlessMatchApi {
     pub id: u32,
     pub data: PlayerlessMatchData,
@@ -24,29 +29,139 @@ pub struct PlayerlessMatchApi {
 
 pub type MatchApi = Vec<MatchResponse>;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct MatchPlayer {
     pub username: String,
-    pub uuid: String,
+    pub uuid: Uuid,
     pub stats: PlayerMatchStats,
+    /// Percentile rank of each stat among all pub games, empty while a match is still live or
+    /// before the population has been loaded.
+    #[serde(default)]
+    pub percentiles: BTreeMap<String, f64>,
+    /// The same, for each stat per minute played.
+    #[serde(default)]
+    pub per_minute_percentiles: BTreeMap<String, f64>,
 }
 
 pub type MatchPlayerApi = Vec<MatchPlayer>;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct UberApi {
     pub id: u32,
     pub data: PlayerlessMatchData,
-    pub players: Vec<MatchPlayer>,
+    pub players: Vec<UberPlayer>,
+    pub teams: Vec<UberTeamTotals>,
+    pub mvp: Option<PlayerData>,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct TournamentPlayerInfo {
-    pub uuid: String,
-    pub username: String,
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct UberPlayer {
+    #[serde(flatten)]
+    pub player: MatchPlayer,
+    pub per_minute: StatLine,
+    pub indexes: PlayerIndexScores,
+    pub career: Option<CareerComparison>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct UberTeamTotals {
+    pub team: i32,
+    pub totals: TournamentAggregateStats,
+    pub per_minute: StatLine,
+}
+
+/// A player's per-match averages over every other match they played, and how far this match
+/// was above (positive) or below them.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CareerComparison {
+    pub matches: u32,
+    pub averages: StatLine,
+    pub deltas: StatLine,
+}
+
+/// The counting stats of `PlayerMatchStats` as floats, so they can be averaged and turned into rates.
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
+pub struct StatLine {
+    pub kills: f64,
+    pub deaths: f64,
+    pub assists: f64,
+    pub damage_dealt: f64,
+    pub damage_taken: f64,
+    pub pickups: f64,
+    pub throws: f64,
+    pub passes: f64,
+    pub catches: f64,
+    pub strips: f64,
+    pub touchdowns: f64,
+    pub touchdown_passes: f64,
+    pub passing_blocks: f64,
+    pub receive_blocks: f64,
+    pub defensive_interceptions: f64,
+    pub pass_interceptions: f64,
+    pub damage_carrier: f64,
 }
 
-#[derive(Serialize, Deserialize, Clone, Default)]
+impl StatLine {
+    pub fn zip_with(&self, other: &Self, f: impl Fn(f64, f64) -> f64) -> Self {
+        Self {
+            kills: f(self.kills, other.kills),
+            deaths: f(self.deaths, other.deaths),
+            assists: f(self.assists, other.assists),
+            damage_dealt: f(self.damage_dealt, other.damage_dealt),
+            damage_taken: f(self.damage_taken, other.damage_taken),
+            pickups: f(self.pickups, other.pickups),
+            throws: f(self.throws, other.throws),
+            passes: f(self.passes, other.passes),
+            catches: f(self.catches, other.catches),
+            strips: f(self.strips, other.strips),
+            touchdowns: f(self.touchdowns, other.touchdowns),
+            touchdown_passes: f(self.touchdown_passes, other.touchdown_passes),
+            passing_blocks: f(self.passing_blocks, other.passing_blocks),
+            receive_blocks: f(self.receive_blocks, other.receive_blocks),
+            defensive_interceptions: f(self.defensive_interceptions, other.defensive_interceptions),
+            pass_interceptions: f(self.pass_interceptions, other.pass_interceptions),
+            damage_carrier: f(self.damage_carrier, other.damage_carrier),
+        }
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        self.zip_with(other, |a, b| a + b)
+    }
+
+    pub fn subtract(&self, other: &Self) -> Self {
+        self.zip_with(other, |a, b| a - b)
+    }
+
+    pub fn scale(&self, factor: f64) -> Self {
+        self.zip_with(self, |a, _| a * factor)
+    }
+}
+
+impl From<&PlayerMatchStats> for StatLine {
+    fn from(stats: &PlayerMatchStats) -> Self {
+        Self {
+            kills: f64::from(stats.kills),
+            deaths: f64::from(stats.deaths),
+            assists: f64::from(stats.assists),
+            damage_dealt: stats.damage_dealt,
+            damage_taken: stats.damage_taken,
+            pickups: f64::from(stats.pickups),
+            throws: f64::from(stats.throws),
+            passes: f64::from(stats.passes),
+            catches: f64::from(stats.catches),
+            strips: f64::from(stats.strips),
+            touchdowns: f64::from(stats.touchdowns),
+            touchdown_passes: f64::from(stats.touchdown_passes),
+            passing_blocks: f64::from(stats.passing_blocks),
+            receive_blocks: f64::from(stats.receive_blocks),
+            defensive_interceptions: f64::from(stats.defensive_interceptions),
+            pass_interceptions: f64::from(stats.pass_interceptions),
+            damage_carrier: f64::from(stats.damage_carrier),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
 pub struct PlayerIndexScores {
     pub offense: f64,
     pub passing: f64,
@@ -56,10 +171,9 @@ pub struct PlayerIndexScores {
     pub total: f64,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
 pub struct TournamentPlayerWithStats {
-    pub uuid: String,
+    pub uuid: Uuid,
     pub username: String,
     pub stats: TournamentAggregateStats,
     pub matches_played: u32,
@@ -68,7 +182,7 @@ pub struct TournamentPlayerWithStats {
     pub team_name: String,
 }
 
-#[derive(Serialize, Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default, JsonSchema)]
 pub struct TournamentAggregateStats {
     pub assists: u32,
     pub catches: u32,
@@ -91,11 +205,10 @@ pub struct TournamentAggregateStats {
     pub touchdowns: u32,
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct TournamentListResponse {
     pub id: u32,
-    pub captains: Vec<TournamentPlayerInfo>,
+    pub captains: Vec<PlayerData>,
     pub date: u64,
     pub match_count: u32,
     pub name: String,
@@ -105,15 +218,14 @@ pub struct TournamentListResponse {
 
 pub type TournamentListApi = Vec<TournamentListResponse>;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct TournamentTeamResponse {
-    pub captain: TournamentPlayerInfo,
+    pub captain: PlayerData,
     pub id: i32,
     pub players: Vec<TournamentPlayerWithStats>,
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct TournamentMatchResponse {
     pub duration: u32,
     pub match_id: u32,
@@ -125,20 +237,20 @@ pub struct TournamentMatchResponse {
     pub team_two_score: u32,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Awards go to distinct players, so a small enough tournament leaves some of them unawarded.
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct TournamentMvpResponse {
-    pub dpot: TournamentPlayerInfo,
-    pub mvp: TournamentPlayerInfo,
-    pub oldl: TournamentPlayerInfo,
-    pub opot: TournamentPlayerInfo,
-    pub passer: TournamentPlayerInfo,
-    pub receiver: TournamentPlayerInfo,
+    pub dpot: Option<PlayerData>,
+    pub mvp: Option<PlayerData>,
+    pub oldl: Option<PlayerData>,
+    pub opot: Option<PlayerData>,
+    pub passer: Option<PlayerData>,
+    pub receiver: Option<PlayerData>,
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct TournamentDetailedResponse {
-    pub all_tournament: Vec<TournamentPlayerInfo>,
+    pub all_tournament: Vec<PlayerData>,
     pub date: u64,
     pub matches: Vec<TournamentMatchResponse>,
     pub mvp: TournamentMvpResponse,
@@ -146,3 +258,489 @@ pub struct TournamentDetailedResponse {
     pub teams: Vec<TournamentTeamResponse>,
     pub winner_team_id: i32,
 }
+
+#[derive(Serialize, JsonSchema)]
+pub struct MatchCsvRow {
+    pub id: u32,
+    pub server: String,
+    pub start_time: u64,
+    pub duration: u32,
+    pub winner: i32,
+    pub team_one_score: u32,
+    pub team_two_score: u32,
+    pub map: String,
+    pub is_tourney: bool,
+    pub team_one_name: String,
+    pub team_two_name: String,
+    pub team_one_color: Option<u32>,
+    pub team_two_color: Option<u32>,
+    pub players: String,
+}
+
+impl From<MatchResponse> for MatchCsvRow {
+    fn from(response: MatchResponse) -> Self {
+        let data = response.data;
+        Self {
+            id: response.id,
+            server: data.server,
+            start_time: data.start_time,
+            duration: data.duration,
+            winner: data.winner,
+            team_one_score: data.team_one_score,
+            team_two_score: data.team_two_score,
+            map: data.map,
+            is_tourney: data.is_tourney,
+            team_one_name: data.team_one_name,
+            team_two_name: data.team_two_name,
+            team_one_color: data.team_one_color,
+            team_two_color: data.team_two_color,
+            players: response
+                .players
+                .into_iter()
+                .map(|player| player.username)
+                .collect::<Vec<_>>()
+                .join(";"),
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct MatchPlayerCsvRow {
+    pub username: String,
+    pub uuid: Uuid,
+    pub team: i32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub assists: u32,
+    pub killstreak: u32,
+    pub damage_dealt: f64,
+    pub damage_taken: f64,
+    pub pickups: u32,
+    pub throws: u32,
+    pub passes: u32,
+    pub catches: u32,
+    pub strips: u32,
+    pub touchdowns: u32,
+    pub touchdown_passes: u32,
+    pub passing_blocks: f32,
+    pub receive_blocks: f32,
+    pub defensive_interceptions: u32,
+    pub pass_interceptions: u32,
+    pub damage_carrier: f32,
+}
+
+impl From<MatchPlayer> for MatchPlayerCsvRow {
+    fn from(player: MatchPlayer) -> Self {
+        let stats = player.stats;
+        Self {
+            username: player.username,
+            uuid: player.uuid,
+            team: stats.team,
+            kills: stats.kills,
+            deaths: stats.deaths,
+            assists: stats.assists,
+            killstreak: stats.killstreak,
+            damage_dealt: stats.damage_dealt,
+            damage_taken: stats.damage_taken,
+            pickups: stats.pickups,
+            throws: stats.throws,
+            passes: stats.passes,
+            catches: stats.catches,
+            strips: stats.strips,
+            touchdowns: stats.touchdowns,
+            touchdown_passes: stats.touchdown_passes,
+            passing_blocks: stats.passing_blocks,
+            receive_blocks: stats.receive_blocks,
+            defensive_interceptions: stats.defensive_interceptions,
+            pass_interceptions: stats.pass_interceptions,
+            damage_carrier: stats.damage_carrier,
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct TournamentPlayerCsvRow {
+    pub uuid: Uuid,
+    pub username: String,
+    pub team_name: String,
+    pub matches_played: u32,
+    pub time_played: u32,
+    pub assists: u32,
+    pub catches: u32,
+    pub damage_carrier: f32,
+    pub damage_dealt: f64,
+    pub damage_taken: f64,
+    pub deaths: u32,
+    pub defensive_interceptions: u32,
+    pub kills: u32,
+    pub killstreak: u32,
+    pub pass_interceptions: u32,
+    pub passes: u32,
+    pub passing_blocks: f32,
+    pub pickups: u32,
+    pub receive_blocks: f32,
+    pub strips: u32,
+    pub team: i32,
+    pub throws: u32,
+    pub touchdown_passes: u32,
+    pub touchdowns: u32,
+    pub offense: f64,
+    pub passing: f64,
+    pub receiving: f64,
+    pub defense: f64,
+    pub pvp: f64,
+    pub total: f64,
+}
+
+impl From<TournamentPlayerWithStats> for TournamentPlayerCsvRow {
+    fn from(player: TournamentPlayerWithStats) -> Self {
+        let stats = player.stats;
+        let indexes = player.indexes;
+        Self {
+            uuid: player.uuid,
+            username: player.username,
+            team_name: player.team_name,
+            matches_played: player.matches_played,
+            time_played: player.time_played,
+            assists: stats.assists,
+            catches: stats.catches,
+            damage_carrier: stats.damage_carrier,
+            damage_dealt: stats.damage_dealt,
+            damage_taken: stats.damage_taken,
+            deaths: stats.deaths,
+            defensive_interceptions: stats.defensive_interceptions,
+            kills: stats.kills,
+            killstreak: stats.killstreak,
+            pass_interceptions: stats.pass_interceptions,
+            passes: stats.passes,
+            passing_blocks: stats.passing_blocks,
+            pickups: stats.pickups,
+            receive_blocks: stats.receive_blocks,
+            strips: stats.strips,
+            team: stats.team,
+            throws: stats.throws,
+            touchdown_passes: stats.touchdown_passes,
+            touchdowns: stats.touchdowns,
+            offense: indexes.offense,
+            passing: indexes.passing,
+            receiving: indexes.receiving,
+            defense: indexes.defense,
+            pvp: indexes.pvp,
+            total: indexes.total,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct LivePlayerUpdate {
+    pub uuid: Uuid,
+    pub stats: PlayerMatchStats,
+}
+
+/// Snapshot pushed by a game server while a match is still being played.
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct LiveMatchUpdate {
+    /// When the match started, in epoch milliseconds, as it will be recorded in `match_data`.
+    /// Together with the map this tells the match apart from the server's previous one.
+    pub start_time: u64,
+    pub map: String,
+    pub team_one_score: u32,
+    pub team_two_score: u32,
+    pub time_elapsed: u32,
+    pub ball_holder: Option<Uuid>,
+    pub players: Vec<LivePlayerUpdate>,
+}
+
+#[derive(Serialize, Deserialize, Clone, JsonSchema)]
+pub struct LiveMatchState {
+    pub server: String,
+    pub updated_at: u64,
+    pub start_time: u64,
+    pub map: String,
+    pub team_one_score: u32,
+    pub team_two_score: u32,
+    pub time_elapsed: u32,
+    pub ball_holder: Option<PlayerData>,
+    pub players: Vec<MatchPlayer>,
+}
+
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct LiveMatchEnded {
+    pub server: String,
+    pub match_id: u32,
+    /// Where the last live snapshot disagrees with the match as it was recorded.
+    pub mismatches: Vec<LiveStatMismatch>,
+}
+
+/// A score or stat that ended up different in `player_match_data` than in the last live
+/// snapshot. `player` is empty for the team scores.
+#[derive(Serialize, Clone, JsonSchema)]
+pub struct LiveStatMismatch {
+    pub player: Option<PlayerData>,
+    pub stat: String,
+    pub live: u32,
+    pub recorded: u32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MatchEventResponse {
+    pub sequence: u32,
+    pub time: u64,
+    pub kind: MatchEventKind,
+    pub actor: Option<PlayerData>,
+    pub target: Option<PlayerData>,
+    pub team: Option<i32>,
+    pub location: Option<EventLocation>,
+}
+
+/// A stat whose event count disagrees with the end-of-match total in `player_match_data`.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct EventStatMismatch {
+    pub uuid: Uuid,
+    pub username: String,
+    pub stat: String,
+    pub events: u32,
+    pub recorded: u32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MatchEventsApi {
+    pub events: Vec<MatchEventResponse>,
+    pub mismatches: Vec<EventStatMismatch>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct OnePassViolation {
+    pub touchdown: MatchEventResponse,
+    pub possession: Vec<MatchEventResponse>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PlayerViolationCount {
+    pub player: PlayerData,
+    pub violations: u32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MatchViolationsApi {
+    pub violations: Vec<OnePassViolation>,
+    pub players: Vec<PlayerViolationCount>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PlayerViolationsApi {
+    pub player: PlayerData,
+    pub violations: u32,
+    pub matches: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PassNetworkNode {
+    pub player: PlayerData,
+    pub team: Option<i32>,
+    pub passes: u32,
+    pub catches: u32,
+}
+
+/// Completed passes from one player to another, and how many of them led straight to a score.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PassNetworkEdge {
+    pub passer: Uuid,
+    pub receiver: Uuid,
+    pub passes: u32,
+    pub touchdown_passes: u32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PassNetworkApi {
+    pub nodes: Vec<PassNetworkNode>,
+    pub edges: Vec<PassNetworkEdge>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct WinPredictionRequest {
+    pub team_one: Vec<Uuid>,
+    pub team_two: Vec<Uuid>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct WinPredictionApi {
+    pub team_one: f64,
+    pub team_two: f64,
+    pub team_one_rating: f64,
+    pub team_two_rating: f64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct WinProbabilityPoint {
+    pub time: u64,
+    pub team_one_score: u32,
+    pub team_two_score: u32,
+    pub team_one: f64,
+}
+
+/// `from_events` is false when the match has no event log, in which case the timeline only has
+/// the kickoff and final whistle.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MatchWinProbabilityApi {
+    pub pre_match: f64,
+    pub timeline: Vec<WinProbabilityPoint>,
+    pub excitement: f64,
+    pub comeback: f64,
+    pub from_events: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ExcitingMatchResponse {
+    #[serde(flatten)]
+    pub match_response: MatchResponse,
+    pub excitement: f64,
+    pub comeback: f64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ComparedPlayer {
+    pub player: PlayerData,
+    pub matches_played: u32,
+    pub time_played: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    pub stats: TournamentAggregateStats,
+    pub per_match: StatLine,
+    pub percentiles: StatLine,
+    pub indexes: PlayerIndexScores,
+}
+
+/// Results between two players, both against each other and on the same team.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct HeadToHead {
+    pub player_one: Uuid,
+    pub player_two: Uuid,
+    pub opposed: u32,
+    pub player_one_wins: u32,
+    pub player_two_wins: u32,
+    pub draws: u32,
+    pub together: u32,
+    pub together_wins: u32,
+}
+
+/// Percentiles and indexes in a window are relative to everyone who played inside it.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ComparisonWindow {
+    pub from: Option<u64>,
+    pub to: Option<u64>,
+    pub players: Vec<ComparedPlayer>,
+    pub head_to_head: Vec<HeadToHead>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PlayerRating {
+    pub player: PlayerData,
+    pub rating: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PlayerComparisonApi {
+    pub lifetime: ComparisonWindow,
+    pub window: Option<ComparisonWindow>,
+    pub ratings: Vec<PlayerRating>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct HistogramBin {
+    pub start: f64,
+    pub end: f64,
+    pub count: u32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PercentileValue {
+    pub percentile: u32,
+    pub value: f64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct Distribution {
+    pub count: u32,
+    pub mean: f64,
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+    pub percentiles: Vec<PercentileValue>,
+    pub histogram: Vec<HistogramBin>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct StatDistribution {
+    pub stat: String,
+    pub per_game: Distribution,
+    pub per_minute: Distribution,
+}
+
+pub type StatDistributionsApi = Vec<StatDistribution>;
+
+/// A player found by name search. `alias` is the name that matched, which may be an old one.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PlayerSearchResult {
+    pub player: PlayerData,
+    pub alias: String,
+    pub current: bool,
+    pub first_seen: u64,
+    pub last_seen: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct HealthApi {
+    pub status: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DatabaseCheck {
+    pub ok: bool,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MigrationCheck {
+    pub ok: bool,
+    pub applied: Vec<i64>,
+    pub pending: Vec<i64>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ResolverCacheStatus {
+    pub cached_names: u64,
+    pub cached_misses: u64,
+    pub mojang_lookups: bool,
+}
+
+/// `status` is `ready` only when both the database and migrations checks pass.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ReadinessApi {
+    pub status: String,
+    pub database: DatabaseCheck,
+    pub migrations: MigrationCheck,
+    pub username_resolver: ResolverCacheStatus,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SetTournamentWinnerRequest {
+    /// 0 to clear the winner.
+    pub winner_team_id: i32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreateApiKeyRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+/// A freshly minted key. `key` is only ever shown here, since just its hash is kept.
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct CreatedApiKeyApi {
+    #[serde(flatten)]
+    pub record: ApiKeyRecord,
+    pub key: String,
+}
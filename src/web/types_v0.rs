@@ -0,0 +1,261 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::web::types::{self, PlayerData, PlayerIndexScores, TournamentAggregateStats};
+
+// The tournament responses as the unversioned routes have always served them: camelCase, apart
+// from the nested stats, with uuids as plain strings and unawarded players as an empty uuid.
+
+#[derive(Serialize)]
+pub struct TournamentPlayerInfo {
+    pub uuid: String,
+    pub username: String,
+}
+
+impl From<PlayerData> for TournamentPlayerInfo {
+    fn from(player: PlayerData) -> Self {
+        Self {
+            uuid: player.uuid.to_string(),
+            username: player.username,
+        }
+    }
+}
+
+impl From<Option<PlayerData>> for TournamentPlayerInfo {
+    fn from(player: Option<PlayerData>) -> Self {
+        player.map_or_else(
+            || Self {
+                uuid: String::new(),
+                username: String::from("Unknown"),
+            },
+            From::from,
+        )
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentPlayerWithStats {
+    pub uuid: String,
+    pub username: String,
+    pub stats: TournamentAggregateStats,
+    pub matches_played: u32,
+    pub time_played: u32,
+    pub indexes: PlayerIndexScores,
+    pub team_name: String,
+}
+
+impl From<types::TournamentPlayerWithStats> for TournamentPlayerWithStats {
+    fn from(player: types::TournamentPlayerWithStats) -> Self {
+        Self {
+            uuid: player.uuid.to_string(),
+            username: player.username,
+            stats: player.stats,
+            matches_played: player.matches_played,
+            time_played: player.time_played,
+            indexes: player.indexes,
+            team_name: player.team_name,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentListResponse {
+    pub id: u32,
+    pub captains: Vec<TournamentPlayerInfo>,
+    pub date: u64,
+    pub match_count: u32,
+    pub name: String,
+    pub player_count: u32,
+    pub winner_team_id: i32,
+}
+
+impl From<types::TournamentListResponse> for TournamentListResponse {
+    fn from(tournament: types::TournamentListResponse) -> Self {
+        Self {
+            id: tournament.id,
+            captains: tournament.captains.into_iter().map(From::from).collect(),
+            date: tournament.date,
+            match_count: tournament.match_count,
+            name: tournament.name,
+            player_count: tournament.player_count,
+            winner_team_id: tournament.winner_team_id,
+        }
+    }
+}
+
+pub type TournamentListApi = Vec<TournamentListResponse>;
+
+#[derive(Serialize)]
+pub struct TournamentTeamResponse {
+    pub captain: TournamentPlayerInfo,
+    pub id: i32,
+    pub players: Vec<TournamentPlayerWithStats>,
+}
+
+impl From<types::TournamentTeamResponse> for TournamentTeamResponse {
+    fn from(team: types::TournamentTeamResponse) -> Self {
+        Self {
+            captain: team.captain.into(),
+            id: team.id,
+            players: team.players.into_iter().map(From::from).collect(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentMatchResponse {
+    pub duration: u32,
+    pub match_id: u32,
+    pub server: String,
+    pub start_time: u64,
+    pub team_one_id: i32,
+    pub team_one_score: u32,
+    pub team_two_id: i32,
+    pub team_two_score: u32,
+}
+
+impl From<types::TournamentMatchResponse> for TournamentMatchResponse {
+    fn from(m: types::TournamentMatchResponse) -> Self {
+        Self {
+            duration: m.duration,
+            match_id: m.match_id,
+            server: m.server,
+            start_time: m.start_time,
+            team_one_id: m.team_one_id,
+            team_one_score: m.team_one_score,
+            team_two_id: m.team_two_id,
+            team_two_score: m.team_two_score,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TournamentMvpResponse {
+    pub dpot: TournamentPlayerInfo,
+    pub mvp: TournamentPlayerInfo,
+    pub oldl: TournamentPlayerInfo,
+    pub opot: TournamentPlayerInfo,
+    pub passer: TournamentPlayerInfo,
+    pub receiver: TournamentPlayerInfo,
+}
+
+impl From<types::TournamentMvpResponse> for TournamentMvpResponse {
+    fn from(awards: types::TournamentMvpResponse) -> Self {
+        Self {
+            dpot: awards.dpot.into(),
+            mvp: awards.mvp.into(),
+            oldl: awards.oldl.into(),
+            opot: awards.opot.into(),
+            passer: awards.passer.into(),
+            receiver: awards.receiver.into(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentDetailedResponse {
+    pub all_tournament: Vec<TournamentPlayerInfo>,
+    pub date: u64,
+    pub matches: Vec<TournamentMatchResponse>,
+    pub mvp: TournamentMvpResponse,
+    pub name: String,
+    pub teams: Vec<TournamentTeamResponse>,
+    pub winner_team_id: i32,
+}
+
+impl From<types::TournamentDetailedResponse> for TournamentDetailedResponse {
+    fn from(tournament: types::TournamentDetailedResponse) -> Self {
+        Self {
+            all_tournament: tournament
+                .all_tournament
+                .into_iter()
+                .map(From::from)
+                .collect(),
+            date: tournament.date,
+            matches: tournament.matches.into_iter().map(From::from).collect(),
+            mvp: tournament.mvp.into(),
+            name: tournament.name,
+            teams: tournament.teams.into_iter().map(From::from).collect(),
+            winner_team_id: tournament.winner_team_id,
+        }
+    }
+}
+
+/// Differs from the v1 row only in its camelCase team and playtime columns.
+#[derive(Serialize)]
+pub struct TournamentPlayerCsvRow {
+    pub uuid: Uuid,
+    pub username: String,
+    #[serde(rename = "teamName")]
+    pub team_name: String,
+    #[serde(rename = "matchesPlayed")]
+    pub matches_played: u32,
+    #[serde(rename = "timePlayed")]
+    pub time_played: u32,
+    pub assists: u32,
+    pub catches: u32,
+    pub damage_carrier: f32,
+    pub damage_dealt: f64,
+    pub damage_taken: f64,
+    pub deaths: u32,
+    pub defensive_interceptions: u32,
+    pub kills: u32,
+    pub killstreak: u32,
+    pub pass_interceptions: u32,
+    pub passes: u32,
+    pub passing_blocks: f32,
+    pub pickups: u32,
+    pub receive_blocks: f32,
+    pub strips: u32,
+    pub team: i32,
+    pub throws: u32,
+    pub touchdown_passes: u32,
+    pub touchdowns: u32,
+    pub offense: f64,
+    pub passing: f64,
+    pub receiving: f64,
+    pub defense: f64,
+    pub pvp: f64,
+    pub total: f64,
+}
+
+impl From<types::TournamentPlayerCsvRow> for TournamentPlayerCsvRow {
+    fn from(row: types::TournamentPlayerCsvRow) -> Self {
+        Self {
+            uuid: row.uuid,
+            username: row.username,
+            team_name: row.team_name,
+            matches_played: row.matches_played,
+            time_played: row.time_played,
+            assists: row.assists,
+            catches: row.catches,
+            damage_carrier: row.damage_carrier,
+            damage_dealt: row.damage_dealt,
+            damage_taken: row.damage_taken,
+            deaths: row.deaths,
+            defensive_interceptions: row.defensive_interceptions,
+            kills: row.kills,
+            killstreak: row.killstreak,
+            pass_interceptions: row.pass_interceptions,
+            passes: row.passes,
+            passing_blocks: row.passing_blocks,
+            pickups: row.pickups,
+            receive_blocks: row.receive_blocks,
+            strips: row.strips,
+            team: row.team,
+            throws: row.throws,
+            touchdown_passes: row.touchdown_passes,
+            touchdowns: row.touchdowns,
+            offense: row.offense,
+            passing: row.passing,
+            receiving: row.receiving,
+            defense: row.defense,
+            pvp: row.pvp,
+            total: row.total,
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use crate::web::openapi::mount_documented;
+use crate::web::request_id::traced;
+use rocket::http::Header;
+use rocket::route::{Handler, Outcome, Route};
+use rocket::{Build, Data, Request, Rocket, async_trait};
+use rocket_okapi::okapi::openapi3::OpenApi;
+
+pub const API_V1: &str = "/api/v1";
+
+/// The path a request would have had on the unversioned routes, so limits and cache lifetimes
+/// configured by route prefix apply to both versions alike.
+pub fn unversioned(path: &str) -> &str {
+    match path.strip_prefix(API_V1) {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+        _ => path,
+    }
+}
+
+/// Marks responses from the unversioned routes as deprecated, pointing at their v1 successor.
+#[derive(Clone)]
+struct Deprecated(Box<dyn Handler>);
+
+#[async_trait]
+impl Handler for Deprecated {
+    async fn handle<'r>(&self, request: &'r Request<'_>, data: Data<'r>) -> Outcome<'r> {
+        match self.0.handle(request, data).await {
+            Outcome::Success(mut response) => {
+                response.set_header(Header::new("Deprecation", "true"));
+                response.set_header(Header::new(
+                    "Link",
+                    format!("<{API_V1}{}>; rel=\"successor-version\"", request.uri()),
+                ));
+                Outcome::Success(response)
+            }
+            outcome => outcome,
+        }
+    }
+}
+
+/// Serves `routes` at `base` as the deprecated v0 API. They are left out of the OpenAPI document.
+pub fn mount_v0(rocket_build: Rocket<Build>, base: &str, routes: Vec<Route>) -> Rocket<Build> {
+    let routes = routes
+        .into_iter()
+        .map(|mut route| {
+            route.handler = Box::new(Deprecated(route.handler.clone()));
+            route
+        })
+        .collect();
+    rocket_build.mount(base, traced(routes))
+}
+
+/// Serves routes made by `openapi_get_routes_spec!` at `base` under `/api/v1`, documenting them.
+pub fn mount_v1(
+    rocket_build: Rocket<Build>,
+    spec: &mut OpenApi,
+    base: &str,
+    routes_and_docs: (Vec<Route>, OpenApi),
+) -> Rocket<Build> {
+    mount_documented(
+        rocket_build,
+        spec,
+        &format!("{API_V1}{base}"),
+        routes_and_docs,
+    )
+}
+
+/// Serves routes whose responses are the same in both versions under v1, and at `base` as v0.
+pub fn mount_versioned(
+    rocket_build: Rocket<Build>,
+    spec: &mut OpenApi,
+    base: &str,
+    routes_and_docs: (Vec<Route>, OpenApi),
+) -> Rocket<Build> {
+    let rocket_build = mount_v0(rocket_build, base, routes_and_docs.0.clone());
+    mount_v1(rocket_build, spec, base, routes_and_docs)
+}